@@ -1,3 +1,4 @@
+use cookie::time::OffsetDateTime;
 use cookie::Cookie;
 use derive_more::Deref;
 use derive_more::DerefMut;
@@ -7,11 +8,17 @@ pub struct SteamCookie(Cookie<'static>);
 
 impl From<reqwest::cookie::Cookie<'_>> for SteamCookie {
     fn from(value: reqwest::cookie::Cookie) -> Self {
-        let mut cookie = Cookie::build(value.name().to_owned(), value.value().to_owned()).path("/");
+        let mut cookie = Cookie::build(value.name().to_owned(), value.value().to_owned())
+            .path("/")
+            .secure(value.secure())
+            .http_only(value.http_only());
 
         if let Some(domain) = value.domain() {
             cookie = cookie.domain(domain.to_owned());
         }
+        if let Some(expires) = value.expires() {
+            cookie = cookie.expires(OffsetDateTime::from(expires));
+        }
 
         Self(cookie.finish())
     }
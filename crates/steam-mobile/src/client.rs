@@ -1,33 +1,49 @@
-use std::{fmt::Debug, marker::PhantomData, ops::Deref, sync::Arc, time::Duration};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    ops::Deref,
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 
 use backoff::future::retry;
 use base64::Engine;
 use cookie::{Cookie, CookieJar};
+use futures::lock::Mutex;
+use futures::stream;
+use futures::Stream;
 use futures::TryFutureExt;
 use futures_timer::Delay;
 use parking_lot::RwLock;
-use proxied::{Proxy, ProxifyClient};
+use proxied::Proxy;
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_TYPE},
-    redirect::Policy,
     Client, IntoUrl, Method, Response, Url,
 };
 use scraper::Html;
 use serde::{de::DeserializeOwned, Serialize};
 use steam_protobuf::{ProtobufDeserialize, ProtobufSerialize};
+use steamid_parser::SteamID;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     adapter::SteamCookie,
-    errors::{AuthError, InternalError, LinkerError},
+    cookie_export::{self, export_jar, import_into_jar, ExportedCookie},
+    errors::{ApiKeyError, AuthError, InternalError, LinkerError},
+    proxy_pool::{ProxyPool, ProxyPoolTransport},
     retry::login_retry_strategy,
+    transport::{HttpTransport, ReqwestTransport},
     user::{IsUser, PresentMaFile, SteamUser},
     utils::{dump_cookies_by_domain, dump_cookies_by_domain_and_name, retrieve_header_location},
     web_handler::{
-        cache_api_key,
+        api_key::{validate_api_key_domain, ApiKeyInfo},
+        api_key_register, api_key_retrieve, api_key_revoke, cache_api_key,
         confirmation::{Confirmation, Confirmations},
         get_confirmations,
         login::login_and_store_cookies,
+        notifications::{
+            notification_counts, notification_summary, NotificationChange, NotificationCounts, NotificationSummary,
+        },
         send_confirmations,
         steam_guard_linker::{
             account_has_phone, add_authenticator_to_account, add_phone_to_account, check_email_confirmation, check_sms,
@@ -35,7 +51,7 @@ use crate::{
             QueryStatusResponse, RemoveAuthenticatorScheme, STEAM_ADD_PHONE_CATCHUP_SECS,
         },
     },
-    CacheGuard, ConfirmationAction, MobileAuthFile, STEAM_COMMUNITY_HOST,
+    CacheGuard, ConfirmationAction, MobileAuthFile, SteamCache, STEAM_COMMUNITY_HOST,
 };
 
 /// Main authenticator. We use it to spawn and act as our "mobile" client.
@@ -52,6 +68,10 @@ struct InnerAuthenticator<MaFileState> {
     pub(crate) client: MobileClient,
     pub(crate) user: SteamUser<MaFileState>,
     pub(crate) cache: Option<CacheGuard>,
+    /// Serializes operations that change the account API Key.
+    pub(crate) api_key_lock: Mutex<()>,
+    /// Serializes operations that fetch and process confirmations.
+    pub(crate) confirmation_lock: Mutex<()>,
 }
 
 /// A successfully logged-in state. Many assumptions are made on this state.
@@ -69,6 +89,13 @@ impl<AuthState, M> SteamAuthenticator<AuthState, M> {
     const fn user(&self) -> &SteamUser<M> {
         &self.inner.user
     }
+
+    /// Account name of the user behind this authenticator.
+    ///
+    /// This is also the key used to assign proxies when built with [`SteamAuthenticator::with_proxy_pool`].
+    pub fn account_name(&self) -> &str {
+        &self.inner.user.username
+    }
 }
 
 impl<MaFileState> SteamAuthenticator<Unauthenticated, MaFileState>
@@ -85,10 +112,65 @@ where
                 client: MobileClient::new(proxy),
                 user,
                 cache: None,
+                api_key_lock: Mutex::new(()),
+                confirmation_lock: Mutex::new(()),
+            },
+            auth_level: PhantomData::<Unauthenticated>,
+        }
+    }
+
+    /// Same as [`Self::new`], but the proxy is taken from `pool`, and rotated whenever Steam throttles the account or
+    /// the proxy stops responding.
+    ///
+    /// Check [`crate::proxy_pool`] for more details.
+    #[must_use]
+    pub fn with_proxy_pool(user: SteamUser<MaFileState>, pool: ProxyPool) -> Self {
+        let transport = ProxyPoolTransport::new(pool, user.username.clone());
+        Self::with_transport(user, Arc::new(transport))
+    }
+
+    /// Same as [`Self::new`], but every request is sent through `transport` instead of the default mobile client.
+    ///
+    /// Check [`crate::transport`] for more details.
+    #[must_use]
+    pub fn with_transport(user: SteamUser<MaFileState>, transport: Arc<dyn HttpTransport>) -> Self {
+        Self {
+            inner: InnerAuthenticator {
+                client: MobileClient::with_transport(transport),
+                user,
+                cache: None,
+                api_key_lock: Mutex::new(()),
+                confirmation_lock: Mutex::new(()),
             },
             auth_level: PhantomData::<Unauthenticated>,
         }
     }
+
+    /// Resumes a session of `steamid` with its `access_token`, without logging in again.
+    ///
+    /// Import the cookies of the session with [`SteamAuthenticator::import_cookies`] afterwards. No API Key is cached.
+    #[must_use]
+    pub fn resume_session(
+        self,
+        steamid: SteamID,
+        access_token: String,
+    ) -> SteamAuthenticator<Authenticated, MaFileState> {
+        let cache = SteamCache {
+            steamid,
+            api_key: None,
+            oauth_token: String::new(),
+            access_token,
+        };
+
+        SteamAuthenticator {
+            inner: InnerAuthenticator {
+                cache: Some(Arc::new(RwLock::new(cache))),
+                ..self.inner
+            },
+            auth_level: PhantomData,
+        }
+    }
+
     /// Log on into Steam website and populates the inner client with cookies for the Steam Store,
     /// Steam community and Steam help domains.
     ///
@@ -139,6 +221,8 @@ where
                 client,
                 user,
                 cache: Some(Arc::new(RwLock::new(cache))),
+                api_key_lock: Mutex::new(()),
+                confirmation_lock: Mutex::new(()),
             },
             auth_level: PhantomData,
         })
@@ -164,6 +248,43 @@ where
             .map(ToString::to_string)
     }
 
+    /// Returns the SteamID of the logged in account.
+    pub fn steam_id(&self) -> SteamID {
+        self.cache().read().steamid.clone()
+    }
+
+    /// Returns the access token of the current session.
+    ///
+    /// Most Web API endpoints accept it as `access_token` in place of an API Key.
+    pub fn access_token(&self) -> String {
+        self.cache().read().access_token.clone()
+    }
+
+    /// Fetches the API Key currently registered on the account, along with its domain name.
+    ///
+    /// The cached key returned by [`Self::api_key`] is refreshed with the result.
+    pub async fn api_key_info(&self) -> Result<ApiKeyInfo, ApiKeyError> {
+        let result = api_key_retrieve(self.client()).await;
+        match &result {
+            Ok(info) => self.cache().write().set_api_key(Some(info.key.clone())),
+            Err(ApiKeyError::NotRegistered) => self.cache().write().set_api_key(None),
+            Err(_) => {}
+        }
+        result
+    }
+
+    /// Revokes the account API Key.
+    ///
+    /// Useful if you suspect the key has been leaked, since anyone holding it is able to watch and cancel your
+    /// trade offers.
+    pub async fn revoke_api_key(&self) -> Result<(), ApiKeyError> {
+        let _guard = self.inner.api_key_lock.lock().await;
+
+        api_key_revoke(self.client()).await?;
+        self.cache().write().set_api_key(None);
+        Ok(())
+    }
+
     /// Returns this account SteamGuard information.
     pub async fn steam_guard_status(&self) -> Result<QueryStatusResponse, AuthError> {
         twofactor_status(self.client(), self.cache()).await.map_err(Into::into)
@@ -297,9 +418,143 @@ where
     pub fn dump_cookie(&self, steam_domain_host: &str, steam_cookie_name: &str) -> Option<String> {
         dump_cookies_by_domain_and_name(&self.client().cookie_store.read(), steam_domain_host, steam_cookie_name)
     }
+
+    /// Fetches pending notification counts, such as received trade offers, comments, items and gifts.
+    pub async fn notification_counts(&self) -> Result<NotificationCounts, InternalError> {
+        notification_counts(self.client()).await
+    }
+
+    /// Fetches pending notification counts along with the most recent notifications.
+    pub async fn notifications(&self) -> Result<NotificationSummary, InternalError> {
+        let query = self.cache().read().query_tokens();
+        notification_summary(self.client(), query).await
+    }
+
+    /// Polls [`Self::notifications`] every `interval`, yielding only when something changed since the last poll.
+    ///
+    /// The first poll is compared against an empty summary, so anything already pending is yielded right away.
+    /// Failed polls are yielded as errors, and polling goes on.
+    pub fn notifications_stream(
+        &self,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<NotificationChange, InternalError>> + Send + '_ {
+        let initial_state = (NotificationSummary::default(), true);
+
+        stream::unfold(initial_state, move |(mut last, mut first_poll)| async move {
+            loop {
+                if !first_poll {
+                    Delay::new(interval).await;
+                }
+                first_poll = false;
+
+                match self.notifications().await {
+                    Ok(current) => {
+                        let change = last.changes(&current);
+                        last = current;
+                        if let Some(change) = change {
+                            return Some((Ok(change), (last, first_poll)));
+                        }
+                    }
+                    Err(e) => return Some((Err(e), (last, first_poll))),
+                }
+            }
+        })
+    }
+
+    /// Dumps every cookie of the current session.
+    ///
+    /// Cookies are only sent to the exact host of their domain, so none of them include subdomains. Check
+    /// [`crate::cookie_export`] for the supported formats.
+    pub fn export_cookies(&self) -> Vec<ExportedCookie> {
+        export_jar(&self.client().cookie_store.read())
+    }
+
+    /// Dumps every cookie of the current session in the Netscape `cookies.txt` format.
+    pub fn export_cookies_netscape(&self) -> String {
+        cookie_export::to_netscape(&self.export_cookies())
+    }
+
+    /// Dumps every cookie of the current session as a JSON array of [`ExportedCookie`].
+    pub fn export_cookies_json(&self) -> Result<String, InternalError> {
+        cookie_export::to_json(&self.export_cookies())
+    }
+
+    /// Adds `cookies` to the session, replacing the ones with same name and domain.
+    ///
+    /// Cookies with the same name on different domains, such as the `sessionid` of the Steam Community and of the
+    /// store, are kept apart. Returns how many cookies were imported.
+    pub fn import_cookies<I>(&self, cookies: I) -> usize
+    where
+        I: IntoIterator<Item = ExportedCookie>,
+    {
+        import_into_jar(&mut self.client().cookie_store.write(), cookies)
+    }
+
+    /// Imports cookies from the Netscape `cookies.txt` format. Check [`Self::import_cookies`].
+    pub fn import_cookies_netscape(&self, cookies_txt: &str) -> Result<usize, InternalError> {
+        cookie_export::from_netscape(cookies_txt).map(|cookies| self.import_cookies(cookies))
+    }
+
+    /// Imports cookies from a JSON array of [`ExportedCookie`]. Check [`Self::import_cookies`].
+    pub fn import_cookies_json(&self, cookies_json: &str) -> Result<usize, InternalError> {
+        cookie_export::from_json(cookies_json).map(|cookies| self.import_cookies(cookies))
+    }
 }
 
 impl SteamAuthenticator<Authenticated, PresentMaFile> {
+    /// Registers a new API Key for the account under `domain`, confirming it with the mobile authenticator if Steam
+    /// asks for it.
+    ///
+    /// Fails with [`ApiKeyError::AlreadyRegistered`] if the account already has a key. Use
+    /// [`Self::rotate_api_key`] to replace it.
+    pub async fn register_api_key(&self, domain: &str) -> Result<String, ApiKeyError> {
+        let _guard = self.inner.api_key_lock.lock().await;
+
+        match api_key_retrieve(self.client()).await {
+            Ok(_) => return Err(ApiKeyError::AlreadyRegistered),
+            Err(ApiKeyError::NotRegistered) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.register_api_key_unchecked(domain).await
+    }
+
+    /// Revokes the current API Key, if any, and registers a new one under `domain`.
+    ///
+    /// Other API Key operations on this authenticator wait until the rotation is finished. If the registration
+    /// fails after the old key has been revoked, the account is left without a key and [`Self::api_key`] returns
+    /// `None`.
+    pub async fn rotate_api_key(&self, domain: &str) -> Result<String, ApiKeyError> {
+        let _guard = self.inner.api_key_lock.lock().await;
+
+        // Fail before revoking, so a bad domain doesn't leave us without a key.
+        validate_api_key_domain(domain)?;
+
+        match api_key_retrieve(self.client()).await {
+            Ok(_) => {
+                api_key_revoke(self.client()).await?;
+                self.cache().write().set_api_key(None);
+            }
+            Err(ApiKeyError::NotRegistered) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.register_api_key_unchecked(domain).await
+    }
+
+    async fn register_api_key_unchecked(&self, domain: &str) -> Result<String, ApiKeyError> {
+        let steamid = self.cache().read().steam_id();
+        let api_key = api_key_register(self.client(), self.user(), steamid, domain).await?;
+        self.cache().write().set_api_key(Some(api_key.clone()));
+        Ok(api_key)
+    }
+
+    /// Lock to hold while fetching confirmations and processing them, so concurrent users of this authenticator don't
+    /// act on the same list. [`Self::handle_confirmations`] holds it already.
+    pub fn confirmation_lock(&self) -> &Mutex<()> {
+        &self.inner.confirmation_lock
+    }
+
     /// Fetch all confirmations available with the authenticator.
     pub async fn fetch_confirmations(&self) -> Result<Confirmations, AuthError> {
         let steamid = self.cache().read().steam_id();
@@ -318,6 +573,7 @@ impl SteamAuthenticator<Authenticated, PresentMaFile> {
     where
         F: Fn(Confirmations) -> Box<dyn Iterator<Item = Confirmation> + Send> + Send,
     {
+        let _guard = self.confirmation_lock().lock().await;
         let confirmations = self.fetch_confirmations().await?;
         if !confirmations.is_empty() {
             self.process_confirmations(operation, f(confirmations)).await
@@ -353,10 +609,13 @@ impl SteamAuthenticator<Authenticated, PresentMaFile> {
     }
 }
 
+/// Only used to assemble requests, which are then sent through the [`HttpTransport`].
+static REQUEST_ASSEMBLER: LazyLock<Client> = LazyLock::new(Client::new);
+
 #[derive(Debug)]
 pub struct MobileClient {
-    /// Standard HTTP Client to make requests.
-    pub inner_http_client: Client,
+    /// Transport that sends every request of this client.
+    pub transport: Arc<dyn HttpTransport>,
     /// Cookie jar that manually handle cookies, because reqwest doesn't let us handle its cookies.
     pub cookie_store: Arc<RwLock<CookieJar>>,
 }
@@ -382,7 +641,7 @@ impl MobileClient {
     {
         let url = url.into_url().unwrap();
         debug!("Request url: {}", url);
-        let request_builder = self.inner_http_client.request(method.clone(), url);
+        let request_builder = REQUEST_ASSEMBLER.request(method.clone(), url);
 
         let req = if method == Method::GET {
             let encoded = base64::engine::general_purpose::URL_SAFE.encode(proto_message.to_bytes().unwrap());
@@ -397,7 +656,7 @@ impl MobileClient {
             return Err(InternalError::GeneralFailure("Unsupported Method".to_string()));
         };
 
-        let response = req.send().await?;
+        let response = self.transport.execute(req.build()?).await?;
         debug!("Response {:?}", response);
 
         let res_bytes = response.bytes().await?;
@@ -489,8 +748,7 @@ impl MobileClient {
             domain_cookies.unwrap_or_default().parse().unwrap(),
         );
 
-        let req_builder = self
-            .inner_http_client
+        let req_builder = REQUEST_ASSEMBLER
             .request(method, parsed_url)
             .headers(header_map)
             .query(&query_params);
@@ -518,7 +776,7 @@ impl MobileClient {
         };
         debug!("{:?}", &request);
 
-        let res = self.inner_http_client.execute(request).err_into().await;
+        let res = self.transport.execute(request).await;
         if let Ok(ref response) = res {
             debug!("Response status: {:?}", response.status());
             debug!("Response headers: {:?}", response.headers());
@@ -636,46 +894,21 @@ impl MobileClient {
         mobile_cookies
     }
 
-    /// Initiate mobile client with default headers
-    fn init_mobile_client(proxy: Option<Proxy>) -> Client {
-        let user_agent = "Dalvik/2.1.0 (Linux; U; Android 9; Valve Steam App Version/3)";
-        let mut default_headers = HeaderMap::new();
-        default_headers.insert(
-            reqwest::header::ACCEPT,
-            "text/javascript, text/html, application/xml, text/xml, */*"
-                .parse()
-                .unwrap(),
-        );
-        default_headers.insert(reqwest::header::REFERER, crate::MOBILE_REFERER.parse().unwrap());
-        default_headers.insert(
-            "X-Requested-With",
-            "com.valvesoftware.android.steam.community".parse().unwrap(),
-        );
-
-        proxy.proxify(
-            Client::builder()
-                .user_agent(user_agent)
-                .cookie_store(true)
-                .redirect(Policy::limited(5))
-                .default_headers(default_headers)
-                .referer(false),
-        ).build().unwrap()
-    }
-
-    pub fn new(proxy: Option<Proxy>) -> Self {
+    /// Creates a new client that sends every request through `transport`.
+    pub fn with_transport(transport: Arc<dyn HttpTransport>) -> Self {
         Self {
-            inner_http_client: Self::init_mobile_client(proxy),
+            transport,
             cookie_store: Arc::new(RwLock::new(Self::init_cookie_jar())),
         }
     }
 
+    pub fn new(proxy: Option<Proxy>) -> Self {
+        Self::with_transport(Arc::new(ReqwestTransport::mobile(proxy)))
+    }
 }
 
 impl Default for MobileClient {
     fn default() -> Self {
-        Self {
-            inner_http_client: Self::init_mobile_client(None),
-            cookie_store: Arc::new(RwLock::new(Self::init_cookie_jar())),
-        }
+        Self::new(None)
     }
 }
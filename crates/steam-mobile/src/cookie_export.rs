@@ -0,0 +1,273 @@
+//! Export and import of the authenticated session cookies.
+//!
+//! Two formats are supported:
+//! * Netscape `cookies.txt`, understood by curl, wget and most browser extensions;
+//! * JSON, as a list of [`ExportedCookie`].
+//!
+//! This allows, for example, handing a bot session to a headless browser for tasks this library doesn't cover, and then
+//! importing the refreshed cookies back into the authenticator.
+//!
+//! The session keeps its cookies by domain and name, so the `sessionid` of the Steam Community and the one of the
+//! store are kept apart. Cookies are only sent to the exact host of their domain: a leading dot is dropped when they
+//! are imported, and exported cookies never include subdomains.
+
+use std::fmt::Write;
+
+use cookie::time::OffsetDateTime;
+use cookie::Cookie;
+use cookie::CookieJar;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::errors::InternalError;
+
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+const NETSCAPE_HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// A single cookie of the session, in a format-agnostic shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedCookie {
+    #[allow(missing_docs)]
+    pub name: String,
+    #[allow(missing_docs)]
+    pub value: String,
+    /// Domain the cookie is sent to. A leading dot means subdomains are included, but it is dropped when imported
+    /// into a session.
+    pub domain: String,
+    #[allow(missing_docs)]
+    pub path: String,
+    /// Unix timestamp of the expiration. `None` for session cookies.
+    pub expires: Option<i64>,
+    #[allow(missing_docs)]
+    pub secure: bool,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub http_only: bool,
+}
+
+impl ExportedCookie {
+    fn from_cookie(cookie: &Cookie<'_>) -> Self {
+        Self {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain: cookie.domain().unwrap_or_default().to_string(),
+            path: cookie.path().unwrap_or("/").to_string(),
+            expires: cookie.expires_datetime().map(OffsetDateTime::unix_timestamp),
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+        }
+    }
+
+    fn into_cookie(self) -> Cookie<'static> {
+        let domain = match self.domain.strip_prefix('.') {
+            Some(host) => host.to_string(),
+            None => self.domain,
+        };
+        let mut builder = Cookie::build(self.name, self.value)
+            .domain(domain)
+            .path(self.path)
+            .secure(self.secure)
+            .http_only(self.http_only);
+
+        if let Some(expires) = self.expires.and_then(|e| OffsetDateTime::from_unix_timestamp(e).ok()) {
+            builder = builder.expires(expires);
+        }
+        builder.finish()
+    }
+}
+
+/// Dumps every cookie of `jar`.
+pub(crate) fn export_jar(jar: &CookieJar) -> Vec<ExportedCookie> {
+    jar.iter().map(ExportedCookie::from_cookie).collect()
+}
+
+/// Adds `cookies` to `jar`, replacing the ones with same name and domain. A leading dot of their domain is dropped.
+pub(crate) fn import_into_jar<I>(jar: &mut CookieJar, cookies: I) -> usize
+where
+    I: IntoIterator<Item = ExportedCookie>,
+{
+    let mut imported = 0;
+    for cookie in cookies {
+        jar.add_original(cookie.into_cookie());
+        imported += 1;
+    }
+    imported
+}
+
+/// Serializes `cookies` into the Netscape `cookies.txt` format.
+#[must_use]
+pub fn to_netscape(cookies: &[ExportedCookie]) -> String {
+    let mut output = String::from(NETSCAPE_HEADER);
+    output.push('\n');
+
+    for cookie in cookies {
+        let prefix = if cookie.http_only { NETSCAPE_HTTP_ONLY_PREFIX } else { "" };
+        let _ = writeln!(
+            output,
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            prefix,
+            cookie.domain,
+            netscape_bool(cookie.domain.starts_with('.')),
+            cookie.path,
+            netscape_bool(cookie.secure),
+            cookie.expires.unwrap_or(0),
+            cookie.name,
+            cookie.value
+        );
+    }
+    output
+}
+
+/// Parses cookies from the Netscape `cookies.txt` format.
+///
+/// Comments and blank lines are skipped. An expiration of `0` is read as a session cookie.
+pub fn from_netscape(input: &str) -> Result<Vec<ExportedCookie>, InternalError> {
+    let mut cookies = vec![];
+
+    for (line_number, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        let (line, http_only) = match line.strip_prefix(NETSCAPE_HTTP_ONLY_PREFIX) {
+            Some(line) => (line, true),
+            None => (line, false),
+        };
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let [domain, _include_subdomains, path, secure, expires, name, value] = fields[..] else {
+            return Err(InternalError::GeneralFailure(format!(
+                "Invalid cookies.txt line {}: expected 7 tab separated fields, found {}.",
+                line_number + 1,
+                fields.len()
+            )));
+        };
+
+        let expires = expires.parse::<i64>().map_err(|_| {
+            InternalError::GeneralFailure(format!(
+                "Invalid cookies.txt line {}: `{}` is not a valid expiration.",
+                line_number + 1,
+                expires
+            ))
+        })?;
+
+        cookies.push(ExportedCookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: domain.to_string(),
+            path: path.to_string(),
+            expires: (expires != 0).then_some(expires),
+            secure: secure.eq_ignore_ascii_case("TRUE"),
+            http_only,
+        });
+    }
+
+    Ok(cookies)
+}
+
+/// Serializes `cookies` into a JSON array.
+pub fn to_json(cookies: &[ExportedCookie]) -> Result<String, InternalError> {
+    serde_json::to_string_pretty(cookies).map_err(|e| InternalError::GeneralFailure(e.to_string()))
+}
+
+/// Parses cookies from a JSON array.
+pub fn from_json(input: &str) -> Result<Vec<ExportedCookie>, InternalError> {
+    serde_json::from_str(input).map_err(InternalError::DeserializationError)
+}
+
+const fn netscape_bool(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::dump_cookies_by_domain_and_name;
+
+    fn sample_cookies() -> Vec<ExportedCookie> {
+        vec![
+            ExportedCookie {
+                name: "steamLoginSecure".to_string(),
+                value: "76561198040191316%7C%7CeyAidHlwIjo".to_string(),
+                domain: "steamcommunity.com".to_string(),
+                path: "/".to_string(),
+                expires: Some(1_735_689_600),
+                secure: true,
+                http_only: true,
+            },
+            ExportedCookie {
+                name: "sessionid".to_string(),
+                value: "a1b2c3d4e5f6".to_string(),
+                domain: ".store.steampowered.com".to_string(),
+                path: "/".to_string(),
+                expires: None,
+                secure: false,
+                http_only: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn netscape_roundtrip() {
+        let cookies = sample_cookies();
+        let dumped = to_netscape(&cookies);
+
+        assert!(dumped.starts_with(NETSCAPE_HEADER));
+        assert!(dumped.contains("#HttpOnly_steamcommunity.com\tFALSE\t/\tTRUE\t1735689600\tsteamLoginSecure\t"));
+        assert!(dumped.contains(".store.steampowered.com\tTRUE\t/\tFALSE\t0\tsessionid\ta1b2c3d4e5f6"));
+        assert_eq!(from_netscape(&dumped).unwrap(), cookies);
+    }
+
+    #[test]
+    fn netscape_invalid_line() {
+        assert!(from_netscape("steamcommunity.com\tFALSE\t/").is_err());
+        assert!(from_netscape("steamcommunity.com\tFALSE\t/\tTRUE\tnever\tname\tvalue").is_err());
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let cookies = sample_cookies();
+        let dumped = to_json(&cookies).unwrap();
+        assert_eq!(from_json(&dumped).unwrap(), cookies);
+    }
+
+    #[test]
+    fn jar_roundtrip() {
+        let mut jar = CookieJar::new();
+        assert_eq!(import_into_jar(&mut jar, sample_cookies()), 2);
+
+        let mut exported = export_jar(&jar);
+        exported.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut expected = sample_cookies();
+        expected.sort_by(|a, b| a.name.cmp(&b.name));
+        expected[0].domain = "store.steampowered.com".to_string();
+        assert_eq!(exported, expected);
+        assert!(to_netscape(&exported).contains("store.steampowered.com\tFALSE\t/\tFALSE\t0\tsessionid"));
+    }
+
+    #[test]
+    fn same_name_on_two_domains() {
+        let mut jar = CookieJar::new();
+        let cookies = sample_cookies().into_iter().map(|cookie| ExportedCookie {
+            name: "sessionid".to_string(),
+            ..cookie
+        });
+        assert_eq!(import_into_jar(&mut jar, cookies), 2);
+
+        assert_eq!(export_jar(&jar).len(), 2);
+        assert_eq!(
+            dump_cookies_by_domain_and_name(&jar, "steamcommunity.com", "sessionid").as_deref(),
+            Some("76561198040191316%7C%7CeyAidHlwIjo")
+        );
+        assert_eq!(
+            dump_cookies_by_domain_and_name(&jar, "store.steampowered.com", "sessionid").as_deref(),
+            Some("a1b2c3d4e5f6")
+        );
+    }
+}
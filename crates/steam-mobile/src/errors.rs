@@ -6,6 +6,7 @@
 //!
 //!
 //! For a general explanation of EResults, check: [steam errors website](https://steamerrors.com/).
+use steam_language_gen::generated::enums::EResult;
 use thiserror::Error;
 
 #[allow(missing_docs)]
@@ -39,6 +40,16 @@ pub enum ApiKeyError {
     AccessDenied,
     #[error("Key not yet registered.")]
     NotRegistered,
+    #[error("A key is already registered for this account. Revoke it before registering a new one.")]
+    AlreadyRegistered,
+    #[error("`{0}` is not a valid domain name for an API Key.")]
+    InvalidDomain(String),
+    #[error("Steam asked for a mobile confirmation of the new key, but it could not be found.")]
+    ConfirmationNotFound,
+    #[error("Steam refused to register the key. EResult: `{0:?}`")]
+    RegistrationRefused(EResult),
+    #[error("The key is still registered after the revoke request.")]
+    RevokeFailed,
     #[error(transparent)]
     InternalError(#[from] InternalError),
 }
@@ -27,16 +27,24 @@ use parking_lot::RwLock;
 pub use reqwest::header::HeaderMap;
 pub use reqwest::Error as HttpError;
 pub use reqwest::Method;
+pub use reqwest::Request as HttpRequest;
+pub use reqwest::Response as HttpResponse;
 pub use reqwest::Url;
 use serde::Deserialize;
 use serde::Serialize;
 use steamid_parser::SteamID;
 pub use utils::format_captcha_url;
 use uuid::Uuid;
+pub use web_handler::api_key::ApiKeyInfo;
 pub use web_handler::confirmation::Confirmation;
 pub use web_handler::confirmation::ConfirmationAction;
 pub use web_handler::confirmation::Confirmations;
 pub use web_handler::confirmation::EConfirmationType;
+pub use web_handler::notifications::ESteamNotificationType;
+pub use web_handler::notifications::NotificationChange;
+pub use web_handler::notifications::NotificationCounts;
+pub use web_handler::notifications::NotificationSummary;
+pub use web_handler::notifications::SteamNotification;
 pub use web_handler::steam_guard_linker::AddAuthenticatorStep;
 
 use crate::errors::AuthError;
@@ -46,9 +54,12 @@ use crate::utils::read_from_disk;
 
 mod adapter;
 pub(crate) mod client;
+pub mod cookie_export;
 pub mod errors;
 mod page_scraper;
+pub mod proxy_pool;
 pub(crate) mod retry;
+pub mod transport;
 mod types;
 pub mod user;
 pub(crate) mod utils;
@@ -4,12 +4,14 @@ use scraper::Html;
 use scraper::Selector;
 
 use crate::errors::ApiKeyError;
+use crate::web_handler::api_key::ApiKeyInfo;
 
 /// Checks API Key state by parsing the document.
-/// If key is found, returns it, otherwise, it just errors accordingly.
-pub(crate) fn api_key_resolve_status(api_key_html: Html) -> Result<String, ApiKeyError> {
+/// If key is found, returns it along with its domain, otherwise, it just errors accordingly.
+pub(crate) fn api_key_resolve_status(api_key_html: Html) -> Result<ApiKeyInfo, ApiKeyError> {
     let api_page_title_selector = Selector::parse("#mainContents > h2").unwrap();
     let api_page_key_selector = Selector::parse("#bodyContents_ex > p:nth-child(2)").unwrap();
+    let api_page_domain_selector = Selector::parse("#bodyContents_ex > p:nth-child(3)").unwrap();
 
     let title = match api_key_html.select(&api_page_title_selector).next() {
         None => return Err(ApiKeyError::GeneralError("title is blank".to_string())),
@@ -29,7 +31,11 @@ pub(crate) fn api_key_resolve_status(api_key_html: Html) -> Result<String, ApiKe
         return Err(ApiKeyError::NotRegistered);
     }
 
-    let api_key = api_key_text.split("Key: ").nth(1).unwrap();
+    let api_key = api_key_text
+        .split("Key: ")
+        .nth(1)
+        .ok_or_else(|| ApiKeyError::GeneralError(format!("Unexpected key node: {}", api_key_text)))?
+        .trim();
 
     if api_key.len() != 32 {
         return Err(ApiKeyError::GeneralError(format!(
@@ -38,7 +44,17 @@ pub(crate) fn api_key_resolve_status(api_key_html: Html) -> Result<String, ApiKe
         )));
     }
 
-    Ok(api_key.to_string())
+    let domain = api_key_html
+        .select(&api_page_domain_selector)
+        .next()
+        .map(|domain_text| domain_text.text().collect::<String>())
+        .and_then(|domain_text| domain_text.split("Domain Name: ").nth(1).map(|d| d.trim().to_string()))
+        .unwrap_or_default();
+
+    Ok(ApiKeyInfo {
+        key: api_key.to_string(),
+        domain,
+    })
 }
 
 #[cfg(test)]
@@ -57,7 +73,8 @@ mod tests {
     fn test_resolve_api_key_status() {
         let api_doc = Html::parse_document(include_str!("../assets/api_ok.html"));
         let api = api_key_resolve_status(api_doc).unwrap();
-        assert_eq!(api, "D805666DF5E380C5F8A89B8F8A0814B8");
+        assert_eq!(api.key, "D805666DF5E380C5F8A89B8F8A0814B8");
+        assert_eq!(api.domain, "localhost");
     }
 
     // #[test]
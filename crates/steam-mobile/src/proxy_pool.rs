@@ -0,0 +1,426 @@
+//! Pool of proxies shared among many accounts.
+//!
+//! Each account is given a proxy from the pool and keeps it until it is rotated, either because Steam started
+//! throttling it or because the proxy stopped responding. Proxies that fail to connect repeatedly are considered
+//! unhealthy and are not handed to any account until their cooldown is over.
+//!
+//! The same [`ProxyPool`] can be cloned and handed to many [`crate::SteamAuthenticator`]s, through
+//! [`crate::SteamAuthenticator::with_proxy_pool`], and to other crates of this workspace, such as `steam-trading`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+use proxied::Proxy;
+use reqwest::header::HeaderMap;
+use reqwest::Request;
+use reqwest::Response;
+use reqwest::StatusCode;
+use steam_language_gen::generated::enums::EResult;
+use tracing::debug;
+use tracing::warn;
+
+use crate::errors::InternalError;
+use crate::transport::HttpTransport;
+use crate::transport::ReqwestTransport;
+
+const DEFAULT_MAX_CONSECUTIVE_FAILURES: u32 = 3;
+const DEFAULT_UNHEALTHY_COOLDOWN_SECS: u64 = 600;
+
+/// Tunes when a proxy is considered unhealthy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyPoolConfig {
+    /// Connection failures in a row before the proxy is considered unhealthy.
+    pub max_consecutive_failures: u32,
+    /// For how long an unhealthy proxy is kept out of rotation.
+    pub unhealthy_cooldown: Duration,
+}
+
+impl Default for ProxyPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: DEFAULT_MAX_CONSECUTIVE_FAILURES,
+            unhealthy_cooldown: Duration::from_secs(DEFAULT_UNHEALTHY_COOLDOWN_SECS),
+        }
+    }
+}
+
+/// A proxy handed by the [`ProxyPool`].
+///
+/// Used to report back how the proxy behaved.
+#[derive(Clone)]
+pub struct PooledProxy {
+    id: usize,
+    proxy: Proxy,
+}
+
+impl PooledProxy {
+    /// Position of this proxy inside the pool.
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The proxy itself.
+    #[must_use]
+    pub const fn proxy(&self) -> &Proxy {
+        &self.proxy
+    }
+}
+
+impl Debug for PooledProxy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PooledProxy").field("id", &self.id).finish_non_exhaustive()
+    }
+}
+
+struct ProxyEntry {
+    proxy: Proxy,
+    consecutive_failures: u32,
+    unhealthy_until: Option<Instant>,
+}
+
+impl ProxyEntry {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.unhealthy_until.is_none_or(|until| until <= now)
+    }
+}
+
+struct PoolState {
+    proxies: Vec<ProxyEntry>,
+    /// Account name -> index of the assigned proxy.
+    assignments: HashMap<String, usize>,
+}
+
+impl PoolState {
+    fn load(&self, index: usize) -> usize {
+        self.assignments.values().filter(|assigned| **assigned == index).count()
+    }
+
+    /// Healthy proxy with the fewest accounts assigned, skipping `exclude`.
+    ///
+    /// If every proxy is unhealthy, the one closest to the end of its cooldown is picked.
+    fn pick(&self, exclude: Option<usize>) -> Option<usize> {
+        let now = Instant::now();
+        let candidates = (0..self.proxies.len()).filter(|index| Some(*index) != exclude || self.proxies.len() == 1);
+
+        let healthy = candidates
+            .clone()
+            .filter(|index| self.proxies[*index].is_healthy(now))
+            .min_by_key(|index| self.load(*index));
+
+        healthy.or_else(|| {
+            warn!("Every proxy on the pool is unhealthy. Picking the one closest to recover.");
+            candidates.min_by_key(|index| self.proxies[*index].unhealthy_until)
+        })
+    }
+
+    fn pooled(&self, index: usize) -> PooledProxy {
+        PooledProxy {
+            id: index,
+            proxy: self.proxies[index].proxy.clone(),
+        }
+    }
+}
+
+/// Proxies shared among many accounts, with sticky assignment, rotation and health checking.
+///
+/// Cloning is cheap, and clones share the same state.
+#[derive(Clone)]
+pub struct ProxyPool {
+    state: Arc<Mutex<PoolState>>,
+    config: ProxyPoolConfig,
+}
+
+impl Debug for ProxyPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock();
+        f.debug_struct("ProxyPool")
+            .field("proxies", &state.proxies.len())
+            .field("accounts", &state.assignments.len())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl ProxyPool {
+    /// Creates a new pool with the default [`ProxyPoolConfig`].
+    pub fn new<I>(proxies: I) -> Self
+    where
+        I: IntoIterator<Item = Proxy>,
+    {
+        Self::with_config(proxies, ProxyPoolConfig::default())
+    }
+
+    /// Creates a new pool with a custom [`ProxyPoolConfig`].
+    pub fn with_config<I>(proxies: I, config: ProxyPoolConfig) -> Self
+    where
+        I: IntoIterator<Item = Proxy>,
+    {
+        let proxies = proxies
+            .into_iter()
+            .map(|proxy| ProxyEntry {
+                proxy,
+                consecutive_failures: 0,
+                unhealthy_until: None,
+            })
+            .collect();
+
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                proxies,
+                assignments: HashMap::new(),
+            })),
+            config,
+        }
+    }
+
+    /// Total of proxies in the pool, healthy or not.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.state.lock().proxies.len()
+    }
+
+    #[allow(missing_docs)]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total of proxies that can currently be handed to accounts.
+    #[must_use]
+    pub fn healthy_count(&self) -> usize {
+        let now = Instant::now();
+        self.state.lock().proxies.iter().filter(|p| p.is_healthy(now)).count()
+    }
+
+    /// Returns the proxy assigned to `account`, assigning one if needed.
+    ///
+    /// The same proxy is returned on every call, unless it became unhealthy or the account was rotated.
+    /// Returns `None` only if the pool is empty.
+    pub fn assign(&self, account: &str) -> Option<PooledProxy> {
+        let mut state = self.state.lock();
+        let now = Instant::now();
+
+        if let Some(index) = state.assignments.get(account).copied() {
+            if state.proxies[index].is_healthy(now) {
+                return Some(state.pooled(index));
+            }
+        }
+
+        let index = state.pick(None)?;
+        state.assignments.insert(account.to_string(), index);
+        debug!("Assigned proxy {} to {}.", index, account);
+        Some(state.pooled(index))
+    }
+
+    /// Moves `account` to another proxy, and returns it.
+    ///
+    /// If the pool has a single proxy, the same one is returned.
+    pub fn rotate(&self, account: &str) -> Option<PooledProxy> {
+        let mut state = self.state.lock();
+
+        let current = state.assignments.get(account).copied();
+        let index = state.pick(current)?;
+        state.assignments.insert(account.to_string(), index);
+        debug!("Rotated {} from proxy {:?} to {}.", account, current, index);
+        Some(state.pooled(index))
+    }
+
+    /// Stops tracking `account`, freeing its slot on the assigned proxy.
+    pub fn release(&self, account: &str) {
+        self.state.lock().assignments.remove(account);
+    }
+
+    /// Reports that a request through `proxy` went through.
+    pub fn report_success(&self, proxy: &PooledProxy) {
+        if let Some(entry) = self.state.lock().proxies.get_mut(proxy.id) {
+            entry.consecutive_failures = 0;
+            entry.unhealthy_until = None;
+        }
+    }
+
+    /// Reports that `proxy` failed to connect.
+    ///
+    /// Returns `true` if the proxy is now considered unhealthy.
+    pub fn report_failure(&self, proxy: &PooledProxy) -> bool {
+        let mut state = self.state.lock();
+        let Some(entry) = state.proxies.get_mut(proxy.id) else {
+            return false;
+        };
+
+        entry.consecutive_failures += 1;
+        if entry.consecutive_failures >= self.config.max_consecutive_failures {
+            warn!(
+                "Proxy {} failed {} times in a row. Marking it as unhealthy.",
+                proxy.id, entry.consecutive_failures
+            );
+            entry.unhealthy_until = Some(Instant::now() + self.config.unhealthy_cooldown);
+            entry.consecutive_failures = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// Whether Steam is throttling us, either by HTTP status or by EResult.
+fn is_throttled(status: StatusCode, headers: &HeaderMap) -> bool {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return true;
+    }
+
+    headers
+        .get("x-eresult")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i32>().ok())
+        .is_some_and(|eresult| {
+            eresult == EResult::RateLimitExceeded as i32 || eresult == EResult::AccountLoginDeniedThrottle as i32
+        })
+}
+
+/// Transport that sends requests of a single account through a [`ProxyPool`].
+///
+/// When Steam throttles the account, or when the current proxy becomes unhealthy, the account is rotated to another
+/// proxy, the inner client is rebuilt and the request is sent once more, if its body allows it. The cookie jar is kept
+/// by the authenticator, so the session survives the rotation.
+pub struct ProxyPoolTransport {
+    pool: ProxyPool,
+    account: String,
+    current: RwLock<(Option<PooledProxy>, ReqwestTransport)>,
+}
+
+impl Debug for ProxyPoolTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyPoolTransport")
+            .field("account", &self.account)
+            .field("proxy", &self.current.read().0)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProxyPoolTransport {
+    /// Creates a transport for `account`, using the proxy the pool assigns to it.
+    pub fn new<S>(pool: ProxyPool, account: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let account = account.into();
+        let proxy = pool.assign(&account);
+        let transport = ReqwestTransport::mobile(proxy.as_ref().map(|p| p.proxy().clone()));
+
+        Self {
+            pool,
+            account,
+            current: RwLock::new((proxy, transport)),
+        }
+    }
+
+    /// Proxy currently used by this transport.
+    pub fn current_proxy(&self) -> Option<PooledProxy> {
+        self.current.read().0.clone()
+    }
+
+    /// Moves the account to another proxy and rebuilds the inner client.
+    ///
+    /// Returns `false` if there was no other proxy to move to.
+    fn rotate(&self) -> bool {
+        let previous = self.current_proxy().map(|p| p.id());
+        let Some(next) = self.pool.rotate(&self.account) else {
+            return false;
+        };
+
+        if Some(next.id()) == previous {
+            return false;
+        }
+
+        let transport = ReqwestTransport::mobile(Some(next.proxy().clone()));
+        *self.current.write() = (Some(next), transport);
+        true
+    }
+
+    async fn send(&self, request: Request) -> Result<Response, InternalError> {
+        let retry_request = request.try_clone();
+        let (proxy, transport) = self.current.read().clone();
+
+        match transport.client().execute(request).await {
+            Ok(response) => {
+                if let Some(proxy) = &proxy {
+                    self.pool.report_success(proxy);
+                }
+
+                if !is_throttled(response.status(), response.headers()) {
+                    return Ok(response);
+                }
+
+                warn!("{} is being throttled by Steam. Rotating proxy.", self.account);
+                match retry_request {
+                    Some(request) if self.rotate() => self.resend(request).await,
+                    _ => Ok(response),
+                }
+            }
+            Err(error) if error.is_connect() || error.is_timeout() => {
+                let unhealthy = proxy.as_ref().is_some_and(|p| self.pool.report_failure(p));
+
+                match retry_request {
+                    Some(request) if unhealthy && self.rotate() => self.resend(request).await,
+                    _ => Err(error.into()),
+                }
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Sends `request` through the current proxy, without rotating again.
+    async fn resend(&self, request: Request) -> Result<Response, InternalError> {
+        let (proxy, transport) = self.current.read().clone();
+        let response = transport.client().execute(request).await;
+
+        if let Some(proxy) = &proxy {
+            match &response {
+                Ok(_) => self.pool.report_success(proxy),
+                Err(error) if error.is_connect() || error.is_timeout() => {
+                    self.pool.report_failure(proxy);
+                }
+                Err(_) => {}
+            }
+        }
+        response.map_err(Into::into)
+    }
+}
+
+impl HttpTransport for ProxyPoolTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>> {
+        self.send(request).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn throttle_detection() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_throttled(StatusCode::OK, &headers));
+        assert!(is_throttled(StatusCode::TOO_MANY_REQUESTS, &headers));
+
+        headers.insert("x-eresult", HeaderValue::from_static("84"));
+        assert!(is_throttled(StatusCode::OK, &headers));
+
+        headers.insert("x-eresult", HeaderValue::from_static("87"));
+        assert!(is_throttled(StatusCode::OK, &headers));
+
+        headers.insert("x-eresult", HeaderValue::from_static("1"));
+        assert!(!is_throttled(StatusCode::OK, &headers));
+    }
+}
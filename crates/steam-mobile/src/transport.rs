@@ -0,0 +1,257 @@
+//! HTTP transport used by the authenticator to talk with Steam.
+//!
+//! Every request made by [`crate::SteamAuthenticator`] is assembled internally, with the session cookies already
+//! injected, and then handed to a [`HttpTransport`] to be sent. By default this is a [`ReqwestTransport`] that
+//! mimics the Steam mobile app, but any implementation can be plugged with
+//! [`crate::SteamAuthenticator::with_transport`]. This allows, for example:
+//!
+//! * recording and replaying responses in tests, as [`CannedTransport`] does;
+//! * wrapping the default transport with middleware, such as metrics, request signing or custom retries;
+//! * sharing a single connection pool across many accounts.
+//!
+//! ```no_run
+//! # use std::sync::Arc;
+//! # use steam_mobile::errors::InternalError;
+//! # use steam_mobile::transport::{BoxFuture, HttpTransport, ReqwestTransport};
+//! # use steam_mobile::{HttpRequest, HttpResponse};
+//! #[derive(Debug)]
+//! struct Logging<T>(T);
+//!
+//! impl<T: HttpTransport> HttpTransport for Logging<T> {
+//!     fn execute(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, InternalError>> {
+//!         println!("{} {}", request.method(), request.url());
+//!         self.0.execute(request)
+//!     }
+//! }
+//!
+//! let transport = Arc::new(Logging(ReqwestTransport::mobile(None)));
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use futures::future;
+pub use futures::future::BoxFuture;
+use futures::FutureExt;
+use futures::TryFutureExt;
+use parking_lot::Mutex;
+use proxied::Proxy;
+use proxied::ProxifyClient;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use reqwest::redirect::Policy;
+use reqwest::Client;
+use reqwest::Method;
+use reqwest::Request;
+use reqwest::Response;
+use reqwest::ResponseBuilderExt;
+use reqwest::Url;
+
+use crate::errors::InternalError;
+
+const MOBILE_USER_AGENT: &str = "Dalvik/2.1.0 (Linux; U; Android 9; Valve Steam App Version/3)";
+
+/// Sends fully assembled requests to Steam.
+///
+/// Implementors receive requests that already carry the session cookies, and must return the raw response. Cookies
+/// set by the response are stored by the authenticator itself, so implementors don't need to care about them.
+pub trait HttpTransport: Debug + Send + Sync {
+    /// Sends `request` and returns its response.
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>>;
+}
+
+impl<T> HttpTransport for Arc<T>
+where
+    T: HttpTransport + ?Sized,
+{
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>> {
+        (**self).execute(request)
+    }
+}
+
+impl<T> HttpTransport for Box<T>
+where
+    T: HttpTransport + ?Sized,
+{
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>> {
+        (**self).execute(request)
+    }
+}
+
+/// Default transport, backed by a [`reqwest::Client`].
+///
+/// Cloning is cheap, and clones share the same connection pool.
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Uses `client` to send requests.
+    ///
+    /// If the same client is shared across many accounts, build it without `cookie_store`, otherwise cookies of one
+    /// account may leak into requests of another. The authenticator cookie jar is not affected by this.
+    #[must_use]
+    pub const fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Transport with default headers that makes us look like the mobile app.
+    #[must_use]
+    pub fn mobile(proxy: Option<Proxy>) -> Self {
+        Self::new(Self::mobile_client_builder(proxy).build().unwrap())
+    }
+
+    /// Returns the inner [`reqwest::Client`].
+    #[must_use]
+    pub const fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Client builder with default headers that makes us look like the mobile app.
+    fn mobile_client_builder(proxy: Option<Proxy>) -> reqwest::ClientBuilder {
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(
+            reqwest::header::ACCEPT,
+            "text/javascript, text/html, application/xml, text/xml, */*"
+                .parse()
+                .unwrap(),
+        );
+        default_headers.insert(reqwest::header::REFERER, crate::MOBILE_REFERER.parse().unwrap());
+        default_headers.insert(
+            "X-Requested-With",
+            "com.valvesoftware.android.steam.community".parse().unwrap(),
+        );
+
+        proxy.proxify(
+            Client::builder()
+                .user_agent(MOBILE_USER_AGENT)
+                .cookie_store(true)
+                .redirect(Policy::limited(5))
+                .default_headers(default_headers)
+                .referer(false),
+        )
+    }
+}
+
+impl Default for ReqwestTransport {
+    fn default() -> Self {
+        Self::mobile(None)
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>> {
+        self.client.execute(request).err_into().boxed()
+    }
+}
+
+/// Answers every request with the same canned response, and records the requests it was sent.
+///
+/// Useful to drive the authenticator, and crates built on it, in tests without reaching Steam.
+#[derive(Debug)]
+pub struct CannedTransport {
+    status: u16,
+    headers: HeaderMap,
+    body: String,
+    sent: Mutex<Vec<SentRequest>>,
+}
+
+/// A request received by a [`CannedTransport`].
+#[derive(Debug, Clone)]
+pub struct SentRequest {
+    #[allow(missing_docs)]
+    pub method: Method,
+    #[allow(missing_docs)]
+    pub url: Url,
+    /// Headers of the request, including the session cookies.
+    pub headers: HeaderMap,
+}
+
+impl CannedTransport {
+    /// Answers with `status` and `body`.
+    #[must_use]
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+            sent: Mutex::new(vec![]),
+        }
+    }
+
+    /// Adds a header to the canned response, such as a `set-cookie`.
+    #[must_use]
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Requests received so far, oldest first.
+    pub fn sent(&self) -> Vec<SentRequest> {
+        self.sent.lock().clone()
+    }
+}
+
+impl HttpTransport for CannedTransport {
+    fn execute(&self, request: Request) -> BoxFuture<'_, Result<Response, InternalError>> {
+        self.sent.lock().push(SentRequest {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+        });
+
+        let response = http::Response::builder()
+            .status(self.status)
+            .url(request.url().clone())
+            .body(self.body.clone())
+            .map(|mut response| {
+                response.headers_mut().extend(self.headers.clone());
+                Response::from(response)
+            })
+            .map_err(|e| InternalError::GeneralFailure(e.to_string()));
+        future::ready(response).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::COOKIE;
+    use reqwest::header::SET_COOKIE;
+
+    use super::*;
+    use crate::client::MobileClient;
+    use crate::STEAM_COMMUNITY_HOST;
+
+    #[tokio::test]
+    async fn canned_responses() {
+        let transport = Arc::new(
+            CannedTransport::new(200, r#"{"success":true}"#)
+                .with_header(SET_COOKIE, HeaderValue::from_static("sessionid=a1b2c3d4e5f6; Path=/")),
+        );
+        let client = MobileClient::with_transport(transport.clone());
+
+        let response = client
+            .request(
+                "https://steamcommunity.com/market/",
+                Method::GET,
+                None,
+                None::<u8>,
+                None::<u8>,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), r#"{"success":true}"#);
+
+        let sent = transport.sent();
+        assert_eq!(sent[0].url.as_str(), "https://steamcommunity.com/market/");
+        let cookies = sent[0].headers[COOKIE].to_str().unwrap();
+        assert!(cookies.contains("mobileClient=android"));
+        // cookies set by the response are stored by the client
+        assert_eq!(
+            client.get_cookie_value(STEAM_COMMUNITY_HOST, "sessionid").as_deref(),
+            Some("a1b2c3d4e5f6")
+        );
+    }
+}
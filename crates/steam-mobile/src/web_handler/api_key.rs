@@ -2,12 +2,26 @@ use serde_derive::Deserialize;
 use serde_derive::Serialize;
 use steam_language_gen::generated::enums::EResult;
 
+use crate::errors::ApiKeyError;
+
+/// Maximum length Steam accepts for the domain name of an API Key.
+const API_KEY_DOMAIN_MAX_LEN: usize = 255;
+
+/// Web API Key currently registered on the account, along with the domain it was registered with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyInfo {
+    /// The 32 characters long Web API Key.
+    pub key: String,
+    /// Domain name given when the key was registered.
+    pub domain: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NewAPIKeyRequest {
     #[serde(rename = "agreeToTerms")]
     pub agree_to_terms: &'static str,
     /// API Key Name
-    pub domain: &'static str,
+    pub domain: String,
     /// Requires confirming through Steam Mobile Authenticator
     pub request_id: String,
     #[serde(rename = "sessionid")]
@@ -15,10 +29,10 @@ pub struct NewAPIKeyRequest {
 }
 
 impl NewAPIKeyRequest {
-    pub(crate) fn new(request_id: String, session_id: String) -> Self {
+    pub(crate) fn new(domain: String, request_id: String, session_id: String) -> Self {
         Self {
             agree_to_terms: "true",
-            domain: "steam-mobile",
+            domain,
             request_id,
             session_id,
         }
@@ -33,3 +47,43 @@ pub struct NewAPIKeyResponse {
     pub api_key: Option<String>,
     pub request_id: Option<String>,
 }
+
+/// Form submitted by the "Revoke My Steam Web API Key" button.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RevokeAPIKeyRequest {
+    #[serde(rename = "Revoke")]
+    pub revoke: &'static str,
+    #[serde(rename = "sessionid")]
+    pub session_id: String,
+}
+
+impl RevokeAPIKeyRequest {
+    pub(crate) fn new(session_id: String) -> Self {
+        Self {
+            revoke: "Revoke My Steam Web API Key",
+            session_id,
+        }
+    }
+}
+
+/// Checks that `domain` is something Steam will accept as an API Key domain name.
+pub(crate) fn validate_api_key_domain(domain: &str) -> Result<(), ApiKeyError> {
+    if domain.is_empty() || domain.len() > API_KEY_DOMAIN_MAX_LEN || domain.chars().any(char::is_whitespace) {
+        return Err(ApiKeyError::InvalidDomain(domain.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_key_domain_validation() {
+        assert!(validate_api_key_domain("steam-mobile").is_ok());
+        assert!(validate_api_key_domain("localhost").is_ok());
+        assert!(validate_api_key_domain("").is_err());
+        assert!(validate_api_key_domain("my domain").is_err());
+        assert!(validate_api_key_domain(&"a".repeat(256)).is_err());
+    }
+}
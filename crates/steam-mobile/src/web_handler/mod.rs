@@ -29,8 +29,11 @@ use crate::user::PresentMaFile;
 use crate::user::SteamUser;
 use crate::utils::dump_cookie_from_header;
 use crate::utils::dump_cookies_by_domain_and_name;
+use crate::web_handler::api_key::validate_api_key_domain;
+use crate::web_handler::api_key::ApiKeyInfo;
 use crate::web_handler::api_key::NewAPIKeyRequest;
 use crate::web_handler::api_key::NewAPIKeyResponse;
+use crate::web_handler::api_key::RevokeAPIKeyRequest;
 use crate::web_handler::confirmation::Confirmation;
 use crate::web_handler::confirmation::ConfirmationAction;
 use crate::web_handler::login::SESSION_ID_COOKIE;
@@ -46,11 +49,15 @@ use crate::STEAM_STORE_HOST;
 pub mod api_key;
 pub mod confirmation;
 pub mod login;
+pub mod notifications;
 pub mod steam_guard_linker;
 
 const CONFIRMATIONS_GET_ENDPOINT: &str = concatcp!(STEAM_COMMUNITY_BASE, "/mobileconf/getlist");
 const CONFIRMATIONS_SEND_ENDPOINT: &str = concatcp!(STEAM_COMMUNITY_BASE, "/mobileconf/multiajaxop");
 
+/// Domain used when the API Key is registered automatically after login.
+pub(crate) const DEFAULT_API_KEY_DOMAIN: &str = "steam-mobile";
+
 // TODO: Refresh session for long-time running authenticators.
 #[allow(clippy::unused_async)]
 async fn session_refresh() {}
@@ -129,12 +136,12 @@ pub async fn cache_api_key(client: &MobileClient, user: Arc<dyn IsUser>, steamid
         .await;
 
     match api_key_res {
-        Ok(api) => Some(api),
+        Ok(api) => Some(api.key),
 
         Err(ApiKeyError::NotRegistered) => {
             if let Some(user) = user.as_any().downcast_ref::<SteamUser<PresentMaFile>>() {
                 warn!("API key not registered. Registering a new one.");
-                return api_key_register(client, user, steamid).await.ok();
+                return api_key_register(client, user, steamid, DEFAULT_API_KEY_DOMAIN).await.ok();
             }
             warn!("API key not registered.");
             None
@@ -248,24 +255,29 @@ async fn generate_confirmation_query_params<'a>(
 ///
 ///
 /// Will error only if an unknown or network error is raised.
-async fn api_key_retrieve(client: &MobileClient) -> Result<String, ApiKeyError> {
+pub(crate) async fn api_key_retrieve(client: &MobileClient) -> Result<ApiKeyInfo, ApiKeyError> {
     let api_key_url = format!("{}{}", STEAM_COMMUNITY_BASE, "/dev/apikey?l=english");
     let doc = client.get_html(api_key_url.clone(), None, None::<u8>).await?;
     api_key_resolve_status(doc)
 }
 
-/// Sends a request to enable an API Key for the account.
-async fn api_key_register(
+/// Sends a request to enable an API Key for the account, registered under `domain`.
+///
+/// Steam may ask for a mobile confirmation before handing the key, which is accepted with the user's maFile.
+pub(crate) async fn api_key_register(
     client: &MobileClient,
     user: &SteamUser<PresentMaFile>,
     steamid: u64,
+    domain: &str,
 ) -> Result<String, ApiKeyError> {
+    validate_api_key_domain(domain)?;
+
     let api_register_url = format!("{}{}", STEAM_COMMUNITY_BASE, "/dev/requestkey");
     let session_id = client
         .get_cookie_value(STEAM_COMMUNITY_HOST, SESSION_ID_COOKIE)
-        .unwrap();
+        .ok_or_else(|| ApiKeyError::GeneralError("Missing sessionid cookie. Are you logged in?".to_string()))?;
 
-    let register_request = NewAPIKeyRequest::new("0".to_string(), session_id.clone());
+    let register_request = NewAPIKeyRequest::new(domain.to_string(), "0".to_string(), session_id.clone());
     let response = client
         .request_and_decode::<_, NewAPIKeyResponse, _, _>(
             &api_register_url,
@@ -276,8 +288,21 @@ async fn api_key_register(
         )
         .await?;
 
-    // Doesn't require mobile confirmation?
-    if response.requires_confirmation < 1 {}
+    // Key was handed right away, no mobile confirmation needed.
+    if response.requires_confirmation < 1 {
+        return match (response.success, response.api_key) {
+            (EResult::OK, Some(api_key)) => {
+                info!("Successfully registered an API Key.");
+                Ok(api_key)
+            }
+            (EResult::OK, None) => Err(ApiKeyError::GeneralError("Failed to register API Key.".to_string())),
+            (eresult, _) => Err(ApiKeyError::RegistrationRefused(eresult)),
+        };
+    }
+
+    let request_id = response
+        .request_id
+        .ok_or_else(|| ApiKeyError::GeneralError("Confirmation required, but no request id was given.".to_string()))?;
 
     Delay::new(Duration::from_millis(STEAM_DELAY_MS)).await;
 
@@ -286,7 +311,12 @@ async fn api_key_register(
     let api_confirmation = get_confirmations(client, identity_secret.clone(), device_id, steamid)
         .await?
         .into_iter()
-        .filter(|c| c.kind == EConfirmationType::APIKey);
+        .filter(|c| c.kind == EConfirmationType::APIKey)
+        .collect::<Vec<_>>();
+
+    if api_confirmation.is_empty() {
+        return Err(ApiKeyError::ConfirmationNotFound);
+    }
 
     send_confirmations(
         client,
@@ -299,7 +329,7 @@ async fn api_key_register(
     .await?;
     Delay::new(Duration::from_millis(STEAM_DELAY_MS)).await;
 
-    let second_request = NewAPIKeyRequest::new(response.request_id.unwrap(), session_id.clone());
+    let second_request = NewAPIKeyRequest::new(domain.to_string(), request_id, session_id);
     let second_response = client
         .request_and_decode::<_, NewAPIKeyResponse, _, _>(
             api_register_url,
@@ -310,12 +340,43 @@ async fn api_key_register(
         )
         .await?;
 
-    if second_response.success == EResult::OK {
-        info!("Successfully registered an API Key.");
-        return Ok(second_response.api_key.unwrap());
+    match (second_response.success, second_response.api_key) {
+        (EResult::OK, Some(api_key)) => {
+            info!("Successfully registered an API Key.");
+            Ok(api_key)
+        }
+        (EResult::OK, None) => Err(ApiKeyError::GeneralError("Failed to register API Key.".to_string())),
+        (eresult, _) => Err(ApiKeyError::RegistrationRefused(eresult)),
     }
+}
 
-    Err(ApiKeyError::GeneralError("Failed to register API Key.".to_string()))
+/// Revokes the account API Key, and checks that it is indeed gone.
+pub(crate) async fn api_key_revoke(client: &MobileClient) -> Result<(), ApiKeyError> {
+    let api_revoke_url = format!("{}{}", STEAM_COMMUNITY_BASE, "/dev/revokekey");
+    let session_id = client
+        .get_cookie_value(STEAM_COMMUNITY_HOST, SESSION_ID_COOKIE)
+        .ok_or_else(|| ApiKeyError::GeneralError("Missing sessionid cookie. Are you logged in?".to_string()))?;
+
+    client
+        .request_with_session_guard(
+            api_revoke_url,
+            Method::POST,
+            None,
+            Some(RevokeAPIKeyRequest::new(session_id)),
+            None::<&str>,
+        )
+        .await?;
+
+    Delay::new(Duration::from_millis(STEAM_DELAY_MS)).await;
+
+    match api_key_retrieve(client).await {
+        Err(ApiKeyError::NotRegistered) => {
+            info!("Successfully revoked the API Key.");
+            Ok(())
+        }
+        Ok(_) => Err(ApiKeyError::RevokeFailed),
+        Err(e) => Err(e),
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,371 @@
+//! Steam notifications, the ones shown on the green envelope at the top of the community pages.
+//!
+//! Two sources are combined:
+//! * `/actions/GetNotificationCounts`, which returns pending counts by notification kind;
+//! * `ISteamNotificationService/GetSteamNotifications`, which returns the notifications themselves.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use const_format::concatcp;
+use reqwest::Method;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_with::serde_as;
+use serde_with::DisplayFromStr;
+
+use crate::client::MobileClient;
+use crate::errors::InternalError;
+use crate::STEAM_API_BASE;
+use crate::STEAM_COMMUNITY_BASE;
+
+const NOTIFICATION_COUNTS_ENDPOINT: &str = concatcp!(STEAM_COMMUNITY_BASE, "/actions/GetNotificationCounts");
+const STEAM_NOTIFICATIONS_ENDPOINT: &str =
+    concatcp!(STEAM_API_BASE, "/ISteamNotificationService/GetSteamNotifications/v1/");
+
+/// Pending notification counts, by kind.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NotificationCounts {
+    /// Received trade offers that are still active.
+    pub trade_offers: u32,
+    /// Turns waiting on asynchronous games.
+    pub async_games: u32,
+    /// Messages from moderators.
+    pub moderator_messages: u32,
+    /// New comments on the profile, screenshots, workshop items, etc.
+    pub comments: u32,
+    /// New items received in the inventory.
+    pub items: u32,
+    /// Pending friend and group invites.
+    pub invites: u32,
+    /// Received gifts.
+    pub gifts: u32,
+    /// Chat messages received while offline.
+    pub offline_messages: u32,
+    /// Replies to Steam Support tickets.
+    pub help_request_replies: u32,
+    /// Account alerts, such as a new login or a changed email.
+    pub account_alerts: u32,
+}
+
+impl NotificationCounts {
+    /// Sum of every count.
+    #[must_use]
+    pub const fn total(&self) -> u32 {
+        self.trade_offers
+            + self.async_games
+            + self.moderator_messages
+            + self.comments
+            + self.items
+            + self.invites
+            + self.gifts
+            + self.offline_messages
+            + self.help_request_replies
+            + self.account_alerts
+    }
+
+    /// Steam keys the counts by a number, mapped here to its field.
+    fn from_raw(raw: &HashMap<String, u32>) -> Self {
+        let count = |key: &str| raw.get(key).copied().unwrap_or_default();
+        Self {
+            trade_offers: count("1"),
+            async_games: count("2"),
+            moderator_messages: count("3"),
+            comments: count("4"),
+            items: count("5"),
+            invites: count("6"),
+            gifts: count("8"),
+            offline_messages: count("9"),
+            help_request_replies: count("10"),
+            account_alerts: count("11"),
+        }
+    }
+}
+
+/// Kind of a [`SteamNotification`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
+#[allow(missing_docs)]
+pub enum ESteamNotificationType {
+    Invalid,
+    Test,
+    Gift,
+    Comment,
+    Item,
+    FriendInvite,
+    MajorSale,
+    PreloadAvailable,
+    Wishlist,
+    TradeOffer,
+    General,
+    HelpRequest,
+    AsyncGame,
+    ChatMsg,
+    ModeratorMsg,
+    /// A kind not known by this library.
+    Other(u32),
+}
+
+impl From<u32> for ESteamNotificationType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => Self::Invalid,
+            1 => Self::Test,
+            2 => Self::Gift,
+            3 => Self::Comment,
+            4 => Self::Item,
+            5 => Self::FriendInvite,
+            6 => Self::MajorSale,
+            7 => Self::PreloadAvailable,
+            8 => Self::Wishlist,
+            9 => Self::TradeOffer,
+            10 => Self::General,
+            11 => Self::HelpRequest,
+            12 => Self::AsyncGame,
+            13 => Self::ChatMsg,
+            14 => Self::ModeratorMsg,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<ESteamNotificationType> for u32 {
+    fn from(value: ESteamNotificationType) -> Self {
+        match value {
+            ESteamNotificationType::Invalid => 0,
+            ESteamNotificationType::Test => 1,
+            ESteamNotificationType::Gift => 2,
+            ESteamNotificationType::Comment => 3,
+            ESteamNotificationType::Item => 4,
+            ESteamNotificationType::FriendInvite => 5,
+            ESteamNotificationType::MajorSale => 6,
+            ESteamNotificationType::PreloadAvailable => 7,
+            ESteamNotificationType::Wishlist => 8,
+            ESteamNotificationType::TradeOffer => 9,
+            ESteamNotificationType::General => 10,
+            ESteamNotificationType::HelpRequest => 11,
+            ESteamNotificationType::AsyncGame => 12,
+            ESteamNotificationType::ChatMsg => 13,
+            ESteamNotificationType::ModeratorMsg => 14,
+            ESteamNotificationType::Other(other) => other,
+        }
+    }
+}
+
+/// A single notification.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SteamNotification {
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub notification_id: u64,
+    #[serde(rename = "notification_type")]
+    #[allow(missing_docs)]
+    pub kind: ESteamNotificationType,
+    /// Body of the notification. Its content depends on the kind, but it is usually a JSON object encoded as string.
+    #[serde(default)]
+    pub body_data: String,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub read: bool,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub hidden: bool,
+    /// Unix timestamp of when the notification was created.
+    #[serde(default)]
+    pub timestamp: u32,
+}
+
+impl SteamNotification {
+    /// Parses [`Self::body_data`] as JSON.
+    pub fn body(&self) -> Result<serde_json::Value, InternalError> {
+        serde_json::from_str(&self.body_data).map_err(InternalError::DeserializationError)
+    }
+}
+
+/// Everything pending on the account.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationSummary {
+    #[allow(missing_docs)]
+    pub counts: NotificationCounts,
+    /// Most recent notifications, newest first.
+    pub notifications: Vec<SteamNotification>,
+    #[allow(missing_docs)]
+    pub unread_count: u32,
+    /// Pending mobile confirmations.
+    pub confirmation_count: u32,
+    #[allow(missing_docs)]
+    pub pending_gift_count: u32,
+    #[allow(missing_docs)]
+    pub pending_friend_count: u32,
+}
+
+impl NotificationSummary {
+    /// Compares with a newer summary, returning what changed, if anything.
+    #[must_use]
+    pub fn changes(&self, newer: &Self) -> Option<NotificationChange> {
+        let known_ids = self
+            .notifications
+            .iter()
+            .map(|n| n.notification_id)
+            .collect::<HashSet<_>>();
+
+        let new_notifications = newer
+            .notifications
+            .iter()
+            .filter(|n| !known_ids.contains(&n.notification_id))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let unchanged = self.counts == newer.counts
+            && self.unread_count == newer.unread_count
+            && self.confirmation_count == newer.confirmation_count
+            && self.pending_gift_count == newer.pending_gift_count
+            && self.pending_friend_count == newer.pending_friend_count
+            && new_notifications.is_empty();
+
+        (!unchanged).then(|| NotificationChange {
+            previous: self.counts,
+            summary: newer.clone(),
+            new_notifications,
+        })
+    }
+}
+
+/// Emitted by the notification stream when the summary changes between polls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationChange {
+    /// Counts of the previous poll.
+    pub previous: NotificationCounts,
+    /// Summary of the current poll.
+    pub summary: NotificationSummary,
+    /// Notifications not present in the previous poll.
+    pub new_notifications: Vec<SteamNotification>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NotificationCountsResponse {
+    #[serde(default)]
+    notifications: HashMap<String, u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SteamNotificationsResponseBase {
+    response: SteamNotificationsResponse,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct SteamNotificationsResponse {
+    notifications: Vec<SteamNotification>,
+    unread_count: u32,
+    confirmation_count: u32,
+    pending_gift_count: u32,
+    pending_friend_count: u32,
+}
+
+/// Fetches pending notification counts from the community.
+pub(crate) async fn notification_counts(client: &MobileClient) -> Result<NotificationCounts, InternalError> {
+    client
+        .request_with_session_guard_and_decode::<_, _, NotificationCountsResponse>(
+            NOTIFICATION_COUNTS_ENDPOINT.to_owned(),
+            Method::GET,
+            None,
+            None::<&str>,
+            None::<&str>,
+        )
+        .await
+        .map(|response| NotificationCounts::from_raw(&response.notifications))
+}
+
+/// Fetches counts along with the most recent notifications.
+pub(crate) async fn notification_summary(
+    client: &MobileClient,
+    access_token_query: Vec<(&'static str, String)>,
+) -> Result<NotificationSummary, InternalError> {
+    let counts = notification_counts(client).await?;
+
+    // session was already checked by the request above
+    let response = client
+        .request_and_decode::<_, SteamNotificationsResponseBase, _, _>(
+            STEAM_NOTIFICATIONS_ENDPOINT,
+            Method::GET,
+            None,
+            None::<&str>,
+            access_token_query,
+        )
+        .await?
+        .response;
+
+    Ok(NotificationSummary {
+        counts,
+        notifications: response.notifications,
+        unread_count: response.unread_count,
+        confirmation_count: response.confirmation_count,
+        pending_gift_count: response.pending_gift_count,
+        pending_friend_count: response.pending_friend_count,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_from_raw() {
+        let response: NotificationCountsResponse = serde_json::from_str(
+            r#"{"notifications":{"1":2,"2":0,"3":0,"4":1,"5":3,"6":0,"8":1,"9":0,"10":0,"11":0}}"#,
+        )
+        .unwrap();
+        let counts = NotificationCounts::from_raw(&response.notifications);
+
+        assert_eq!(counts.trade_offers, 2);
+        assert_eq!(counts.comments, 1);
+        assert_eq!(counts.items, 3);
+        assert_eq!(counts.gifts, 1);
+        assert_eq!(counts.total(), 7);
+    }
+
+    #[test]
+    fn notifications_response() {
+        let response: SteamNotificationsResponseBase = serde_json::from_str(
+            r#"{"response":{"notifications":[{"notification_id":"4391234","notification_targets":7,
+            "notification_type":9,"body_data":"{\"tradeofferid\":\"6123\"}","read":false,"timestamp":1700000000,
+            "hidden":false},{"notification_id":"4391235","notification_type":99,"body_data":"{}","read":true,
+            "timestamp":1700000001}],"unread_count":1,"confirmation_count":2}}"#,
+        )
+        .unwrap()
+        .response;
+
+        assert_eq!(response.notifications.len(), 2);
+        assert_eq!(response.notifications[0].notification_id, 4_391_234);
+        assert_eq!(response.notifications[0].kind, ESteamNotificationType::TradeOffer);
+        assert_eq!(response.notifications[0].body().unwrap()["tradeofferid"], "6123");
+        assert_eq!(response.notifications[1].kind, ESteamNotificationType::Other(99));
+        assert_eq!(response.unread_count, 1);
+        assert_eq!(response.confirmation_count, 2);
+    }
+
+    #[test]
+    fn summary_changes() {
+        let notification = SteamNotification {
+            notification_id: 1,
+            kind: ESteamNotificationType::Comment,
+            body_data: String::new(),
+            read: false,
+            hidden: false,
+            timestamp: 0,
+        };
+        let previous = NotificationSummary::default();
+        assert!(previous.changes(&previous).is_none());
+
+        let mut current = previous.clone();
+        current.counts.comments = 1;
+        current.notifications.push(notification.clone());
+
+        let change = previous.changes(&current).unwrap();
+        assert_eq!(change.previous.comments, 0);
+        assert_eq!(change.summary.counts.comments, 1);
+        assert_eq!(change.new_notifications, vec![notification]);
+    }
+}
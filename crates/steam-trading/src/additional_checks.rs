@@ -4,11 +4,11 @@ use steam_mobile::Method;
 use steamid_parser::SteamID;
 
 use crate::errors::InternalError;
+use crate::types::trade_link::new_offer_page;
 use crate::OfferError;
 use crate::SteamCompleteAuthenticator;
 use crate::TradeError;
 use crate::TryFutureExt;
-use crate::TRADEOFFER_BASE;
 
 fn is_steam_guard_error(document: &str) -> bool {
     let doc = Html::parse_document(document);
@@ -34,12 +34,7 @@ pub(crate) async fn check_steam_guard_error(
     steamid: SteamID,
     token: &str,
 ) -> Result<(), TradeError> {
-    let endpoint = format!(
-        "{}new/?partner={}&token={}",
-        TRADEOFFER_BASE,
-        steamid.to_steam3(),
-        token
-    );
+    let endpoint = new_offer_page(&steamid, token);
 
     let response = authenticator
         .request_custom_endpoint(endpoint, Method::GET, None, None::<&u8>)
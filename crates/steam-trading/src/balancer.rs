@@ -0,0 +1,570 @@
+//! Moving items between bots, so each one holds its share of every item class.
+//!
+//! A [`TargetDistribution`] tells how each item class is split among the bots, as weights. [`Balancer::plan`] fetches
+//! every inventory and returns the fewest item movements that reach it, grouped as one offer per pair of bots. Planning
+//! sends nothing, so it works as a dry run: the [`BalancePlan`] prints as a list of transfers, to be reviewed before
+//! calling [`Balancer::execute`].
+//!
+//! Executing a plan sends each transfer from the giving bot, after making both bots friends so no trade token is
+//! needed, and accepts it on the receiving bot. Both sides are confirmed with their own authenticators. The trade is
+//! then recorded on an [`AssetLedger`], to know the ids the moved items have on the receiving bot.
+//!
+//! Only tradable items are counted and moved. Items that can't be traded right now stay where they are.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::pin::pin;
+use std::time::Duration;
+
+use futures::future;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use futures_timer::Delay;
+use tracing::debug;
+use tracing::warn;
+
+use crate::history::TradeHistoryOptions;
+use crate::inventory::InventoryItem;
+use crate::ledger::AssetKey;
+use crate::ledger::AssetLedger;
+use crate::ledger::LedgerStore;
+use crate::AcceptOutcome;
+use crate::OfferError;
+use crate::SteamTradeManager;
+use crate::TradeError;
+use crate::TradeOfferBuilder;
+use crate::STANDARD_DELAY;
+use crate::TRADE_MAX_ITEMS;
+
+/// Message of the offers sent by the balancer.
+const BALANCE_MESSAGE: &str = "Inventory balancing";
+
+/// How many of the newest trades are searched for a transfer that was just accepted.
+const RECENT_TRADES: usize = 100;
+
+/// How many times the trade history is read, while Steam hasn't listed an accepted transfer yet.
+const HISTORY_LOOKUP_ATTEMPTS: u32 = 3;
+
+/// Items of the same class are interchangeable for the balancer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ItemClass {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    #[allow(missing_docs)]
+    pub contextid: u32,
+    #[allow(missing_docs)]
+    pub classid: u64,
+}
+
+impl ItemClass {
+    #[allow(missing_docs)]
+    pub fn new(appid: u32, contextid: u32, classid: u64) -> Self {
+        Self {
+            appid,
+            contextid,
+            classid,
+        }
+    }
+
+    /// Class of `item`.
+    pub fn of(item: &InventoryItem) -> Self {
+        Self::new(item.appid, item.contextid, item.classid)
+    }
+}
+
+/// How each item class is split among the bots.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TargetDistribution {
+    /// Weight of each bot, by SteamID64, for each class.
+    weights: BTreeMap<ItemClass, BTreeMap<u64, u64>>,
+}
+
+impl TargetDistribution {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Splits `class` among the bots proportionally to `weights`, by SteamID64.
+    ///
+    /// Bots without a weight are left with none of the class. Classes that were never set are not moved.
+    pub fn set(&mut self, class: ItemClass, weights: impl IntoIterator<Item = (u64, u64)>) -> &mut Self {
+        self.weights.insert(class, weights.into_iter().collect());
+        self
+    }
+
+    /// Splits `class` evenly among `bots`, by SteamID64.
+    pub fn evenly(&mut self, class: ItemClass, bots: impl IntoIterator<Item = u64>) -> &mut Self {
+        self.set(class, bots.into_iter().map(|bot| (bot, 1)))
+    }
+
+    /// Classes to balance.
+    pub fn classes(&self) -> impl Iterator<Item = &ItemClass> {
+        self.weights.keys()
+    }
+
+    /// How many units of `class` each of `bots` should hold, out of the `total` they hold together.
+    ///
+    /// Units that can't be split exactly go to the bots with the largest remainders. `None` if none of `bots` has a
+    /// weight for `class`.
+    fn targets(&self, class: &ItemClass, bots: &[u64], total: u64) -> Option<BTreeMap<u64, u64>> {
+        let weights = self.weights.get(class)?;
+        let weight_of = |bot: &u64| weights.get(bot).copied().unwrap_or_default() as u128;
+        let total_weight = bots.iter().map(weight_of).sum::<u128>();
+        if total_weight == 0 {
+            return None;
+        }
+
+        let mut targets = BTreeMap::new();
+        let mut remainders = Vec::with_capacity(bots.len());
+        for bot in bots {
+            let share = total as u128 * weight_of(bot);
+            targets.insert(*bot, (share / total_weight) as u64);
+            remainders.push((share % total_weight, *bot));
+        }
+
+        let assigned = targets.values().sum::<u64>();
+        // largest remainders first, then the lowest SteamID, so the same stock always gives the same plan
+        remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        for (_, bot) in remainders.into_iter().take((total - assigned) as usize) {
+            // safe to unwrap, every bot has a target
+            *targets.get_mut(&bot).unwrap() += 1;
+        }
+        Some(targets)
+    }
+}
+
+/// Items given by one bot to another, in a single offer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    /// SteamID64 of the giving bot.
+    pub from: u64,
+    /// SteamID64 of the receiving bot.
+    pub to: u64,
+    /// Items given, with the amount of each.
+    pub items: Vec<(InventoryItem, u64)>,
+}
+
+impl Transfer {
+    /// Units given, counting every unit of stackable items.
+    pub fn units(&self) -> u64 {
+        self.items.iter().map(|(_, amount)| amount).sum()
+    }
+}
+
+/// Transfers that balance the bots, as planned by [`Balancer::plan`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BalancePlan {
+    #[allow(missing_docs)]
+    pub transfers: Vec<Transfer>,
+}
+
+impl BalancePlan {
+    /// Whether the bots are already balanced.
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty()
+    }
+
+    /// Plans the movements that bring the `stock` of every bot, by SteamID64, to the targets of `distribution`.
+    ///
+    /// Each bot with more than its target gives the excess to the bots with less, filling one before moving on to the
+    /// next, so no unit is moved twice and few pairs of bots trade.
+    fn from_stock(stock: &BTreeMap<u64, Vec<InventoryItem>>, distribution: &TargetDistribution) -> Self {
+        let bots = stock.keys().copied().collect::<Vec<_>>();
+        let mut movements: BTreeMap<(u64, u64), Vec<(InventoryItem, u64)>> = BTreeMap::new();
+
+        for class in distribution.classes() {
+            let held = stock
+                .iter()
+                .map(|(bot, items)| {
+                    let items = items
+                        .iter()
+                        .filter(|item| ItemClass::of(item) == *class && item.is_tradable())
+                        .collect::<Vec<_>>();
+                    (*bot, items)
+                })
+                .collect::<BTreeMap<_, _>>();
+            let units_of = |items: &[&InventoryItem]| items.iter().map(|item| item.amount).sum::<u64>();
+
+            let total = held.values().map(|items| units_of(items)).sum();
+            let Some(targets) = distribution.targets(class, &bots, total) else {
+                continue;
+            };
+
+            let mut givers = vec![];
+            let mut receivers = vec![];
+            for (bot, items) in held {
+                let (units, target) = (units_of(&items), targets[&bot]);
+                if units > target {
+                    givers.push((bot, units - target, items));
+                } else if units < target {
+                    receivers.push((bot, target - units));
+                }
+            }
+
+            let mut receivers = receivers.into_iter();
+            let mut receiver = receivers.next();
+            for (giver, mut excess, items) in givers {
+                for item in items {
+                    let mut available = item.amount;
+                    while available > 0 && excess > 0 {
+                        let Some((to, needed)) = receiver.as_mut() else {
+                            break;
+                        };
+                        let amount = available.min(excess).min(*needed);
+                        movements.entry((giver, *to)).or_default().push((item.clone(), amount));
+
+                        available -= amount;
+                        excess -= amount;
+                        *needed -= amount;
+                        if *needed == 0 {
+                            receiver = receivers.next();
+                        }
+                    }
+                }
+            }
+        }
+
+        let transfers = movements
+            .into_iter()
+            .flat_map(|((from, to), items)| {
+                items
+                    .chunks(TRADE_MAX_ITEMS as usize)
+                    .map(|items| Transfer {
+                        from,
+                        to,
+                        items: items.to_vec(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Self { transfers }
+    }
+}
+
+impl Display for BalancePlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "Nothing to move, the bots are balanced.");
+        }
+
+        let units = self.transfers.iter().map(Transfer::units).sum::<u64>();
+        writeln!(f, "{} transfers, moving {} items:", self.transfers.len(), units)?;
+        for transfer in &self.transfers {
+            writeln!(f, "{} -> {}", transfer.from, transfer.to)?;
+
+            let mut classes: BTreeMap<ItemClass, (&str, u64)> = BTreeMap::new();
+            for (item, amount) in &transfer.items {
+                let name = item.description.as_ref().map_or("Unknown item", |d| &*d.name);
+                classes.entry(ItemClass::of(item)).or_insert((name, 0)).1 += amount;
+            }
+            for (class, (name, units)) in classes {
+                writeln!(
+                    f,
+                    "    {} x {} ({}/{}/{})",
+                    units, name, class.appid, class.contextid, class.classid
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What happened to a transfer of a [`BalancePlan`].
+#[derive(Debug)]
+pub enum TransferOutcome {
+    /// Items were moved, and the trade was recorded on the ledger.
+    Completed {
+        #[allow(missing_docs)]
+        tradeoffer_id: u64,
+        /// Id each given asset had on the giving bot, and the one it has now on the receiving bot.
+        moved: Vec<(AssetKey, AssetKey)>,
+    },
+    /// Offer was accepted, but the items didn't move yet. Check [`AcceptOutcome`].
+    Held {
+        #[allow(missing_docs)]
+        tradeoffer_id: u64,
+        #[allow(missing_docs)]
+        outcome: AcceptOutcome,
+    },
+    /// Transfer failed. If `tradeoffer_id` is set, the offer was sent and may still be active, or even accepted.
+    Failed {
+        #[allow(missing_docs)]
+        tradeoffer_id: Option<u64>,
+        #[allow(missing_docs)]
+        error: TradeError,
+    },
+}
+
+/// Balances the inventories of several bots. Check the [module documentation](self).
+#[derive(Debug)]
+pub struct Balancer<'m, 'a> {
+    /// Bots by SteamID64.
+    bots: BTreeMap<u64, &'m SteamTradeManager<'a>>,
+    distribution: TargetDistribution,
+}
+
+impl<'m, 'a> Balancer<'m, 'a> {
+    /// Balancer of `bots`, towards `distribution`.
+    pub fn new(bots: impl IntoIterator<Item = &'m SteamTradeManager<'a>>, distribution: TargetDistribution) -> Self {
+        let bots = bots
+            .into_iter()
+            .map(|bot| (bot.authenticator.steam_id().to_steam64(), bot))
+            .collect();
+        Self { bots, distribution }
+    }
+
+    /// Fetches the inventory of every bot, and plans the transfers that balance them. Nothing is sent.
+    ///
+    /// Print the plan to review it before passing it to [`Self::execute`].
+    pub async fn plan(&self) -> Result<BalancePlan, TradeError> {
+        let inventories = self
+            .distribution
+            .classes()
+            .map(|class| (class.appid, class.contextid))
+            .collect::<BTreeSet<_>>();
+
+        let mut stock: BTreeMap<u64, Vec<InventoryItem>> = BTreeMap::new();
+        for (steamid, bot) in &self.bots {
+            let items = stock.entry(*steamid).or_default();
+            for (appid, contextid) in &inventories {
+                items.extend(bot.get_inventory(*appid, *contextid).await?);
+            }
+        }
+
+        let plan = BalancePlan::from_stock(&stock, &self.distribution);
+        debug!("Balance plan with {} transfers.", plan.transfers.len());
+        Ok(plan)
+    }
+
+    /// Sends every transfer of `plan` from its giving bot, accepts it on the receiving one, and records the trades on
+    /// `ledger`.
+    ///
+    /// Transfers don't depend on each other, so a failed one doesn't stop the rest. Returns the outcome of each
+    /// transfer, in the order of the plan.
+    pub async fn execute<S: LedgerStore>(
+        &self,
+        plan: BalancePlan,
+        ledger: &mut AssetLedger<S>,
+    ) -> Vec<TransferOutcome> {
+        let mut outcomes = Vec::with_capacity(plan.transfers.len());
+
+        for (position, transfer) in plan.transfers.into_iter().enumerate() {
+            if position > 0 {
+                Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+            }
+
+            let outcome = self.transfer(transfer, ledger).await;
+            if let TransferOutcome::Failed { tradeoffer_id, error } = &outcome {
+                warn!("Balancing transfer failed. Trade offer: {:?}. {}", tradeoffer_id, error);
+            }
+            outcomes.push(outcome);
+        }
+        outcomes
+    }
+
+    async fn transfer<S: LedgerStore>(&self, transfer: Transfer, ledger: &mut AssetLedger<S>) -> TransferOutcome {
+        let (Some(giver), Some(receiver)) = (self.bots.get(&transfer.from), self.bots.get(&transfer.to)) else {
+            let error = TradeError::GeneralError(format!(
+                "Transfer from {} to {} involves a bot that is not balanced.",
+                transfer.from, transfer.to
+            ));
+            return TransferOutcome::Failed {
+                tradeoffer_id: None,
+                error,
+            };
+        };
+
+        let tradeoffer_id = match Self::send(giver, receiver, &transfer).await {
+            Ok(tradeoffer_id) => tradeoffer_id,
+            Err(error) => {
+                return TransferOutcome::Failed {
+                    tradeoffer_id: None,
+                    error,
+                }
+            }
+        };
+
+        match Self::settle(giver, receiver, &transfer, tradeoffer_id, ledger).await {
+            Ok(outcome) => outcome,
+            Err(error) => TransferOutcome::Failed {
+                tradeoffer_id: Some(tradeoffer_id),
+                error,
+            },
+        }
+    }
+
+    /// Sends and confirms the offer of `transfer`, refusing it if the items would be held.
+    async fn send(
+        giver: &SteamTradeManager<'_>,
+        receiver: &SteamTradeManager<'_>,
+        transfer: &Transfer,
+    ) -> Result<u64, TradeError> {
+        let tradelink = giver.befriend(receiver).await?;
+        let offer = transfer
+            .items
+            .iter()
+            .fold(
+                TradeOfferBuilder::new(tradelink)
+                    .message(BALANCE_MESSAGE)
+                    .refusing_trade_hold(),
+                |builder, (item, amount)| builder.give_amount(item, *amount),
+            )
+            .build()?;
+
+        giver.create_offer_and_confirm(offer).await
+    }
+
+    /// Accepts the offer on the receiving bot, and records the resulting trade.
+    async fn settle<S: LedgerStore>(
+        giver: &SteamTradeManager<'_>,
+        receiver: &SteamTradeManager<'_>,
+        transfer: &Transfer,
+        tradeoffer_id: u64,
+        ledger: &mut AssetLedger<S>,
+    ) -> Result<TransferOutcome, TradeError> {
+        let outcome = receiver.accept_offer(tradeoffer_id).await?;
+        let tradeid = match outcome {
+            AcceptOutcome::Completed { tradeid } => tradeid,
+            outcome => return Ok(TransferOutcome::Held { tradeoffer_id, outcome }),
+        };
+
+        let tradeid = tradeid
+            .as_deref()
+            .and_then(|id| id.parse::<i64>().ok())
+            .ok_or_else(|| OfferError::GeneralFailure("Steam did not return the id of the trade.".to_string()))?;
+        let moved = Self::reconcile(giver, transfer, tradeid, ledger).await?;
+
+        debug!(
+            "Moved {} items from {} to {} with trade offer {}.",
+            transfer.units(),
+            transfer.from,
+            transfer.to,
+            tradeoffer_id
+        );
+        Ok(TransferOutcome::Completed { tradeoffer_id, moved })
+    }
+
+    /// Records trade `tradeid` of `giver` on `ledger`, and returns the id each given asset had and has now.
+    ///
+    /// Steam may take a few moments to list a trade on the history, so it is read again until the trade is found.
+    async fn reconcile<S: LedgerStore>(
+        giver: &SteamTradeManager<'_>,
+        transfer: &Transfer,
+        tradeid: i64,
+        ledger: &mut AssetLedger<S>,
+    ) -> Result<Vec<(AssetKey, AssetKey)>, TradeError> {
+        let mut found = None;
+        for attempt in 1..=HISTORY_LOOKUP_ATTEMPTS {
+            let mut trades = pin!(giver
+                .trade_history(TradeHistoryOptions::default())
+                .take(RECENT_TRADES)
+                .try_filter(|trade| future::ready(trade.tradeid == tradeid)));
+            found = trades.try_next().await?;
+
+            if found.is_some() || attempt == HISTORY_LOOKUP_ATTEMPTS {
+                break;
+            }
+            Delay::new(Duration::from_millis(STANDARD_DELAY * attempt as u64)).await;
+        }
+
+        let trade = found.ok_or(OfferError::TradeNotFound(tradeid))?;
+        ledger.record_trade(trade);
+        ledger.save()?;
+
+        Ok(transfer
+            .items
+            .iter()
+            .map(|(item, _)| {
+                let key = AssetKey::new(item.appid, item.contextid, item.assetid);
+                (key, ledger.current_id(key))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::inventory_item;
+
+    const BOT_A: u64 = 76561198017653157;
+    const BOT_B: u64 = 76561198040191316;
+    const BOT_C: u64 = 76561197984835396;
+
+    fn item(assetid: i64, classid: u64, amount: u64) -> InventoryItem {
+        inventory_item(730, 2, assetid, classid, amount, serde_json::json!({}))
+    }
+
+    fn class(classid: u64) -> ItemClass {
+        ItemClass::new(730, 2, classid)
+    }
+
+    #[test]
+    fn targets() {
+        let mut distribution = TargetDistribution::new();
+        distribution
+            .evenly(class(1), [BOT_A, BOT_B, BOT_C])
+            .set(class(2), [(BOT_A, 3), (BOT_B, 1)]);
+        let bots = [BOT_C, BOT_A, BOT_B];
+
+        let even = distribution.targets(&class(1), &bots, 10).unwrap();
+        assert_eq!(even.values().sum::<u64>(), 10);
+        // the leftover unit goes to the lowest SteamID
+        assert_eq!(even[&BOT_C], 4);
+        assert_eq!(even[&BOT_A], 3);
+
+        let weighted = distribution.targets(&class(2), &bots, 8).unwrap();
+        assert_eq!(weighted[&BOT_A], 6);
+        assert_eq!(weighted[&BOT_B], 2);
+        assert_eq!(weighted[&BOT_C], 0);
+
+        assert!(distribution.targets(&class(3), &bots, 8).is_none());
+    }
+
+    #[test]
+    fn plan_transfers() {
+        let mut stock = BTreeMap::new();
+        stock.insert(
+            BOT_A,
+            vec![
+                item(1, 1, 1),
+                item(2, 1, 1),
+                item(3, 1, 1),
+                item(4, 1, 1),
+                item(5, 2, 9),
+            ],
+        );
+        stock.insert(BOT_B, vec![item(6, 3, 1)]);
+        stock.insert(BOT_C, vec![item(7, 2, 1)]);
+
+        let mut distribution = TargetDistribution::new();
+        distribution
+            .evenly(class(1), [BOT_A, BOT_B])
+            .evenly(class(2), [BOT_A, BOT_B, BOT_C]);
+        let plan = BalancePlan::from_stock(&stock, &distribution);
+
+        // A gives 2 cases of class 1 and 3 of its stack of class 2 to B, and 3 more of the stack to C, which gets the
+        // leftover unit of class 2
+        assert_eq!(plan.transfers.len(), 2);
+        let to_b = plan.transfers.iter().find(|t| t.to == BOT_B).unwrap();
+        assert_eq!(to_b.from, BOT_A);
+        assert_eq!(to_b.units(), 5);
+        let to_c = plan.transfers.iter().find(|t| t.to == BOT_C).unwrap();
+        assert_eq!(to_c.items, vec![(item(5, 2, 9), 3)]);
+
+        let printed = plan.to_string();
+        assert!(printed.starts_with("2 transfers, moving 8 items:"));
+        assert!(printed.contains("    3 x Case 2 (730/2/2)"));
+
+        // once applied, nothing is left to move
+        let mut balanced = BTreeMap::new();
+        balanced.insert(BOT_A, vec![item(1, 1, 1), item(2, 1, 1), item(5, 2, 3)]);
+        balanced.insert(BOT_B, vec![item(3, 1, 1), item(4, 1, 1), item(8, 2, 3)]);
+        balanced.insert(BOT_C, vec![item(7, 2, 1), item(9, 2, 3)]);
+        assert!(BalancePlan::from_stock(&balanced, &distribution).is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! Sending many trade offers at once.
+//!
+//! Steam refuses new offers once an account has [`TRADE_MAX_ONGOING_TRADES`] sent offers active, or
+//! [`TRADE_MAX_TRADES_PER_SINGLE_USER`] active offers to the same partner. A batch is checked against both limits,
+//! counting the offers that are already active, before anything is sent. Check
+//! [`crate::SteamTradeManager::send_batch`].
+
+use std::collections::HashMap;
+
+use steam_language_gen::generated::enums::ETradeOfferState;
+use tappet::response_types::TradeOffer_Trade;
+
+use crate::guard::partner_of;
+use crate::TradeError;
+use crate::TradeOffer;
+use crate::TRADE_MAX_ONGOING_TRADES;
+use crate::TRADE_MAX_TRADES_PER_SINGLE_USER;
+
+/// Queue of offers to be sent together.
+#[derive(Debug, Default, PartialEq)]
+pub struct OfferBatch {
+    offers: Vec<TradeOffer>,
+}
+
+impl OfferBatch {
+    #[allow(missing_docs)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `offer` after the ones already in the batch.
+    pub fn push(&mut self, offer: TradeOffer) -> &mut Self {
+        self.offers.push(offer);
+        self
+    }
+
+    #[allow(missing_docs)]
+    pub fn len(&self) -> usize {
+        self.offers.len()
+    }
+
+    #[allow(missing_docs)]
+    pub fn is_empty(&self) -> bool {
+        self.offers.is_empty()
+    }
+}
+
+impl FromIterator<TradeOffer> for OfferBatch {
+    fn from_iter<T: IntoIterator<Item = TradeOffer>>(iter: T) -> Self {
+        Self {
+            offers: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for OfferBatch {
+    type Item = TradeOffer;
+    type IntoIter = std::vec::IntoIter<TradeOffer>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.offers.into_iter()
+    }
+}
+
+/// Limit that kept an offer from being sent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BatchLimit {
+    /// [`TRADE_MAX_ONGOING_TRADES`] sent offers are already active.
+    OngoingTrades,
+    /// [`TRADE_MAX_TRADES_PER_SINGLE_USER`] sent offers to this partner are already active.
+    TradesPerUser,
+}
+
+/// What happened to an offer of a batch.
+#[derive(Debug)]
+pub enum OfferOutcome {
+    /// Offer was sent, and confirmed if it had to.
+    Sent {
+        #[allow(missing_docs)]
+        tradeoffer_id: u64,
+    },
+    /// Offer was created, but its mobile confirmation could not be found or accepted.
+    ///
+    /// It stays waiting for a confirmation until it is confirmed or canceled.
+    Unconfirmed {
+        #[allow(missing_docs)]
+        tradeoffer_id: u64,
+    },
+    /// Offer was not sent, since it would exceed a limit.
+    Skipped(BatchLimit),
+    /// Offer could not be sent.
+    Failed(TradeError),
+}
+
+impl OfferOutcome {
+    /// Id of the created offer, if it was created.
+    pub fn tradeoffer_id(&self) -> Option<u64> {
+        match self {
+            Self::Sent { tradeoffer_id } | Self::Unconfirmed { tradeoffer_id } => Some(*tradeoffer_id),
+            Self::Skipped(_) | Self::Failed(_) => None,
+        }
+    }
+}
+
+/// Sent offers that count towards the limits, by partner SteamID64.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct OngoingOffers {
+    total: usize,
+    by_partner: HashMap<u64, usize>,
+}
+
+impl OngoingOffers {
+    /// Counts the offers sent by us that are still waiting for an answer or a confirmation.
+    pub(crate) fn from_offers<'o, I>(offers: I) -> Self
+    where
+        I: IntoIterator<Item = &'o TradeOffer_Trade>,
+    {
+        let mut ongoing = Self::default();
+        offers
+            .into_iter()
+            .filter(|offer| offer.is_our_offer)
+            .filter(|offer| {
+                matches!(
+                    offer.state,
+                    ETradeOfferState::Active | ETradeOfferState::CreatedNeedsConfirmation
+                )
+            })
+            .for_each(|offer| {
+                let partner = partner_of(offer);
+                ongoing.total += 1;
+                *ongoing.by_partner.entry(partner).or_default() += 1;
+            });
+        ongoing
+    }
+
+    /// Counts a new offer to `partner`, if it doesn't exceed any limit.
+    pub(crate) fn reserve(&mut self, partner: u64) -> Result<(), BatchLimit> {
+        if self.total >= TRADE_MAX_ONGOING_TRADES as usize {
+            return Err(BatchLimit::OngoingTrades);
+        }
+
+        let partner_offers = self.by_partner.entry(partner).or_default();
+        if *partner_offers >= TRADE_MAX_TRADES_PER_SINGLE_USER as usize {
+            return Err(BatchLimit::TradesPerUser);
+        }
+
+        *partner_offers += 1;
+        self.total += 1;
+        Ok(())
+    }
+
+    /// Forgets an offer reserved with [`Self::reserve`] that was not sent after all.
+    pub(crate) fn release(&mut self, partner: u64) {
+        if let Some(partner_offers) = self.by_partner.get_mut(&partner) {
+            *partner_offers = partner_offers.saturating_sub(1);
+            self.total = self.total.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::trade_offer;
+
+    const PARTNER: u64 = 76561198040191316;
+
+    fn sent_offer(state: ETradeOfferState) -> TradeOffer_Trade {
+        trade_offer(1, state, serde_json::json!({}))
+    }
+
+    #[test]
+    fn per_partner_limit() {
+        let active = vec![
+            sent_offer(ETradeOfferState::Active),
+            sent_offer(ETradeOfferState::CreatedNeedsConfirmation),
+            sent_offer(ETradeOfferState::Accepted),
+        ];
+        let mut ongoing = OngoingOffers::from_offers(&active);
+        assert_eq!(ongoing.total, 2);
+
+        for _ in 2..TRADE_MAX_TRADES_PER_SINGLE_USER {
+            assert_eq!(ongoing.reserve(PARTNER), Ok(()));
+        }
+        assert_eq!(ongoing.reserve(PARTNER), Err(BatchLimit::TradesPerUser));
+
+        ongoing.release(PARTNER);
+        assert_eq!(ongoing.reserve(PARTNER), Ok(()));
+        assert_eq!(ongoing.reserve(1), Ok(()));
+    }
+
+    #[test]
+    fn ongoing_limit() {
+        let mut ongoing = OngoingOffers::default();
+        for partner in 0..u64::from(TRADE_MAX_ONGOING_TRADES) {
+            assert_eq!(ongoing.reserve(partner), Ok(()));
+        }
+        assert_eq!(ongoing.reserve(PARTNER), Err(BatchLimit::OngoingTrades));
+    }
+}
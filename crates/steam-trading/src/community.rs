@@ -0,0 +1,108 @@
+//! Requests to Steam Community endpoints, on the session of the authenticator.
+//!
+//! The Community Market and the gems endpoints answer with JSON that reports failures in its own way, so parsing is
+//! parameterised by a [`CommunityError`].
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use steam_mobile::HeaderMap;
+use steam_mobile::Method;
+
+use crate::errors::InternalError;
+use crate::SteamCompleteAuthenticator;
+use crate::TradeError;
+use crate::TryFutureExt;
+
+pub(crate) const COMMUNITY_PROFILES_BASE: &str = "https://steamcommunity.com/profiles/";
+
+/// Errors of a family of Steam Community endpoints.
+pub(crate) trait CommunityError: Into<TradeError> {
+    /// Throttled request, answered with a 429 or with `null`.
+    fn rate_limited() -> Self;
+
+    /// Failure without a known reason.
+    fn general_failure(reason: String) -> Self;
+
+    /// Failure reported by a JSON response, or `None` if it succeeded.
+    fn from_response(response: &Value, status: u16) -> Option<Self>;
+}
+
+/// Profile page of the account of `authenticator`, ending with a slash.
+pub(crate) fn profile_url(authenticator: &SteamCompleteAuthenticator) -> String {
+    format!("{}{}/", COMMUNITY_PROFILES_BASE, authenticator.steam_id().to_steam64())
+}
+
+/// Sends a request, returning the status and the body of the response.
+async fn send<F>(
+    authenticator: &SteamCompleteAuthenticator,
+    endpoint: String,
+    method: Method,
+    header: Option<HeaderMap>,
+    form: Option<F>,
+) -> Result<(u16, String), TradeError>
+where
+    F: Serialize + Send + Sync,
+{
+    let response = authenticator
+        .request_custom_endpoint(endpoint, method, header, form)
+        .err_into::<InternalError>()
+        .await?;
+    let status = response.status().as_u16();
+    let body = response.text().err_into::<InternalError>().await?;
+    Ok((status, body))
+}
+
+/// Gets `endpoint`, returning the status and the body of the response.
+pub(crate) async fn get(
+    authenticator: &SteamCompleteAuthenticator,
+    endpoint: String,
+) -> Result<(u16, String), TradeError> {
+    send(authenticator, endpoint, Method::GET, None, None::<&u8>).await
+}
+
+/// Posts `form` with `referer` as its Referer header.
+pub(crate) async fn send_form<E, F>(
+    authenticator: &SteamCompleteAuthenticator,
+    endpoint: String,
+    referer: &str,
+    form: F,
+) -> Result<(u16, String), TradeError>
+where
+    E: CommunityError,
+    F: Serialize + Send + Sync,
+{
+    let Ok(referer_value) = referer.parse() else {
+        return Err(E::general_failure(format!("Invalid referer: {}", referer)).into());
+    };
+    let mut header = HeaderMap::new();
+    header.insert("Referer", referer_value);
+
+    send(authenticator, endpoint, Method::POST, Some(header), Some(form)).await
+}
+
+/// Parses a response, mapping failures to typed errors.
+pub(crate) fn parse_response<T, E>(status: u16, body: &str) -> Result<T, TradeError>
+where
+    T: DeserializeOwned,
+    E: CommunityError,
+{
+    if status == 429 {
+        return Err(E::rate_limited().into());
+    }
+
+    let response = match serde_json::from_str::<Value>(body) {
+        // throttled requests are answered with `null`
+        Ok(Value::Null) => return Err(E::rate_limited().into()),
+        Ok(response) => response,
+        Err(_) => {
+            return Err(E::general_failure(format!("Unexpected response with status {}.", status)).into());
+        }
+    };
+
+    if let Some(error) = E::from_response(&response, status) {
+        return Err(error.into());
+    }
+
+    serde_json::from_value(response).map_err(|e| InternalError::from(e).into())
+}
@@ -1,9 +1,20 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use steam_language_gen::generated::enums::ECurrencyCode;
 use steam_language_gen::generated::enums::EResult;
 use steam_mobile::errors::AuthError;
 use steam_mobile::HttpError;
 use tappet::errors::SteamAPIError;
 use thiserror::Error;
 
+use crate::market::Price;
+use crate::types::trade_offer_web::TradeOfferGenericErrorResponse;
+
+lazy_static! {
+    static ref PAGE_ERROR_REGEX: Regex = Regex::new(r#"(?s)<div id="error_msg">\s*(?P<message>.*?)\s*</div>"#).unwrap();
+    static ref MESSAGE_ERESULT_REGEX: Regex = Regex::new(r"\((?P<eresult>\d+)\)\s*$").unwrap();
+}
+
 #[derive(Error, Debug)]
 #[non_exhaustive]
 pub enum TradeError {
@@ -25,6 +36,15 @@ pub enum TradeError {
     #[error(transparent)]
     AuthError(#[from] AuthError),
 
+    #[error(transparent)]
+    InventoryError(#[from] InventoryError),
+
+    #[error(transparent)]
+    MarketError(#[from] MarketError),
+
+    #[error(transparent)]
+    GemsError(#[from] GemsError),
+
     #[error(transparent)]
     InternalError(#[from] InternalError),
 }
@@ -36,6 +56,12 @@ pub enum InternalError {
 
     #[error(transparent)]
     MobileInternalError(#[from] steam_mobile::errors::InternalError),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    SerializationError(#[from] serde_json::Error),
 }
 
 #[derive(Error, Debug, PartialEq, Copy, Clone)]
@@ -52,6 +78,18 @@ pub enum OfferValidationError {
 
     #[error("`{0}`")]
     InvalidTrade(String),
+
+    #[error("Asset `{0}` can't be traded.")]
+    NotTradable(i64),
+
+    #[error("Asset `{0}` was added more than once to the same side of the trade.")]
+    DuplicateAsset(i64),
+
+    #[error("Invalid amount `{amount}` of asset `{assetid}`. Available: `{available}`.")]
+    InvalidAmount { assetid: i64, amount: u64, available: u64 },
+
+    #[error("Trade offer messages are limited to {max} characters, but this one has {length}.")]
+    MessageTooLong { length: usize, max: usize },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -75,22 +113,187 @@ pub enum OfferError {
     )]
     Revoked,
 
+    #[error("One or more of the items in this trade offer are no longer available to trade.")]
+    ItemUnavailable,
+
     #[error(
         "This suggests that the user receiving the trade offer recently activated his mobile SteamGuard and is under \
          the 7 day restriction period."
     )]
     SteamGuardRecentlyEnabled,
 
+    #[error(
+        "The items of this trade would be held by Steam. My escrow: {my_escrow_days} days. Their escrow: \
+         {their_escrow_days} days."
+    )]
+    TradeHold {
+        my_escrow_days: u32,
+        their_escrow_days: u32,
+    },
+
+    #[error("Trade `{0}` could not be found on the trade history.")]
+    TradeNotFound(i64),
+
+    #[error("`{0}` is not a friend of this account, so a trade token is needed to send offers to them.")]
+    NotFriend(u64),
+
+    #[error("The inventory of the partner is full, so it can't receive the items of this trade offer.")]
+    PartnerInventoryFull,
+
+    #[error("The partner has a limited account, which can't trade until it spends money on Steam.")]
+    PartnerLimitedAccount,
+
+    #[error("This account or the partner is trade banned.")]
+    TradeBan,
+
+    #[error("Too many trade offers were sent recently, or are still active. Wait a while, or cancel some of them.")]
+    TooManyOffers,
+
+    #[error(
+        "Neither the API Key nor the access token were accepted, so only the active offers of the trade offers pages \
+         can be listed. Offers updated since a time, or inactive ones, are unavailable."
+    )]
+    ActiveOffersOnly,
+
+    #[error("The session is no longer accepted by Steam, even with a refreshed sessionid. Log in again.")]
+    SessionExpired,
+
     #[error("General Failure: `{0}`")]
     GeneralFailure(String),
 }
 
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum InventoryError {
+    #[error("This inventory is private, or the account does not exist.")]
+    Private,
+
+    #[error("Too many inventory requests. Wait a while before trying again.")]
+    RateLimited,
+
+    #[error("General Failure: `{0}`")]
+    GeneralFailure(String),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MarketError {
+    #[error("This account can't use the Community Market: `{0}`")]
+    Restricted(String),
+
+    #[error("The item is no longer in the inventory, or can't be sold on the Community Market.")]
+    ItemUnavailable,
+
+    #[error("Could not find this item on the Community Market.")]
+    ItemNotFound,
+
+    #[error("Listing `{0}` could not be found. It may be already sold or removed.")]
+    ListingNotFound(u64),
+
+    #[error("This item already has a listing waiting for a confirmation. Confirm or remove it first.")]
+    PendingConfirmation,
+
+    #[error("There is already an active buy order for this item.")]
+    BuyOrderExists,
+
+    #[error("The wallet has {balance}, but {needed} are needed.")]
+    InsufficientFunds { needed: Price, balance: Price },
+
+    #[error("Prices are in `{found:?}`, but the wallet currency is `{wallet:?}`.")]
+    CurrencyMismatch {
+        wallet: ECurrencyCode,
+        found: ECurrencyCode,
+    },
+
+    #[error("Too many Community Market requests, or a previous action is still being processed. Wait a while.")]
+    RateLimited,
+
+    #[error("The total price is too large.")]
+    PriceOverflow,
+
+    #[error("General Failure: `{0}`")]
+    GeneralFailure(String),
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum GemsError {
+    #[error("{needed} gems are needed, but only {available} are available.")]
+    InsufficientGems { needed: u64, available: u64 },
+
+    #[error("Asset `{0}` can't be turned into gems.")]
+    NotGrindable(i64),
+
+    #[error("`{0}` sacks hold more gems than can be counted.")]
+    TooManySacks(u64),
+
+    #[error("Booster pack of app `{appid}` can't be created yet. Available at: `{available_at:?}`")]
+    BoosterUnavailable { appid: u32, available_at: Option<String> },
+
+    #[error("Too many requests, or a previous action is still being processed. Wait a while.")]
+    RateLimited,
+
+    #[error("General Failure: `{0}`")]
+    GeneralFailure(String),
+}
+
+/// Why an [`crate::events::EventSink`] could not deliver an event.
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum EventSinkError {
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    #[error("Webhook answered with status `{0}`.")]
+    Rejected(u16),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("The receiving side of the channel, or the writer thread of the file, is gone.")]
+    Closed,
+
+    #[error("The sink took longer than `{0:?}` to deliver the event.")]
+    Timeout(std::time::Duration),
+}
+
 #[derive(Error, Debug, Copy, Clone)]
 pub enum ConfirmationError {
     #[error("Could not find the requested confirmation.")]
     NotFound,
     #[error("Could not find the requested confirmation, but offer was created. Trade offer id: `{0}`")]
     NotFoundButTradeCreated(u64),
+    #[error("Refused to confirm the trade offer. {0}")]
+    Refused(GuardViolation),
+}
+
+/// Why a confirmation was refused. Check [`crate::guard`].
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GuardViolation {
+    #[error("Trade offer `{0}` was not created or accepted by this account.")]
+    UnknownOffer(u64),
+    #[error("Trade offer `{tradeoffer_id}` is with `{found}`, but it was created for `{expected}`.")]
+    PartnerMismatch {
+        tradeoffer_id: u64,
+        expected: u64,
+        found: u64,
+    },
+    #[error("Items of trade offer `{0}` are not the ones it was created with.")]
+    ItemsMismatch(u64),
+}
+
+impl GuardViolation {
+    /// Id of the refused trade offer.
+    pub fn tradeoffer_id(&self) -> u64 {
+        match *self {
+            Self::UnknownOffer(tradeoffer_id)
+            | Self::PartnerMismatch { tradeoffer_id, .. }
+            | Self::ItemsMismatch(tradeoffer_id) => tradeoffer_id,
+        }
+    }
 }
 
 pub fn tradeoffer_error_from_eresult(eresult: EResult) -> OfferError {
@@ -98,6 +301,11 @@ pub fn tradeoffer_error_from_eresult(eresult: EResult) -> OfferError {
         EResult::Revoked => OfferError::Revoked,
         EResult::InvalidState => OfferError::InvalidState,
         EResult::NoMatch => OfferError::NoMatch,
+        // LimitExceeded is also sent for a full partner inventory, so it is only classified by its message
+        EResult::RateLimitExceeded | EResult::TooManyPending => OfferError::TooManyOffers,
+        EResult::LimitedUserAccount => OfferError::PartnerLimitedAccount,
+        EResult::Banned => OfferError::TradeBan,
+        EResult::NotLoggedOn | EResult::LogonSessionReplaced => OfferError::SessionExpired,
         e => OfferError::GeneralFailure(format!(
             "{}{}",
             "Please check: https://steamerrors.com/",
@@ -106,21 +314,115 @@ pub fn tradeoffer_error_from_eresult(eresult: EResult) -> OfferError {
     }
 }
 
+/// Maps the EResult between parentheses at the end of `message`, as in `... Please try again later. (26)`.
 pub fn error_from_strmessage(message: &str) -> Option<OfferError> {
-    let index_start = message.find(|c: char| c == '(')?;
-    let index_end = message.find(|c: char| c == ')')?;
+    let captures = MESSAGE_ERESULT_REGEX.captures(message)?;
 
-    let number = message
-        .chars()
-        .skip(index_start + 1)
-        .take(index_end - index_start - 1)
-        .collect::<String>();
-
-    serde_json::from_str::<EResult>(&*number)
+    serde_json::from_str::<EResult>(&captures["eresult"])
         .map(tradeoffer_error_from_eresult)
         .ok()
 }
 
+/// Maps the `strError` of a failed trade offer response, or the error shown on a trade offer page.
+///
+/// Known messages are matched first, since Steam reuses the same EResult for different failures. Otherwise the EResult
+/// between parentheses is used, and unfamiliar messages are kept whole.
+pub fn offer_error_from_message(message: &str) -> OfferError {
+    let lowercase = message.to_lowercase();
+
+    if lowercase.contains("inventory is full") {
+        OfferError::PartnerInventoryFull
+    } else if lowercase.contains("trade ban") {
+        OfferError::TradeBan
+    } else if lowercase.contains("limited account") || lowercase.contains("limited user") {
+        OfferError::PartnerLimitedAccount
+    } else if lowercase.contains("too many trade offers") || lowercase.contains("too many active") {
+        OfferError::TooManyOffers
+    } else if lowercase.contains("is not available to trade") {
+        OfferError::SteamGuardRecentlyEnabled
+    } else if lowercase.contains("no longer available") || lowercase.contains("no longer in") {
+        OfferError::ItemUnavailable
+    } else if lowercase.contains("not logged in") || lowercase.contains("session has expired") {
+        OfferError::SessionExpired
+    } else {
+        error_from_strmessage(message).unwrap_or_else(|| OfferError::GeneralFailure(message.to_string()))
+    }
+}
+
+/// Classifies a failed response of the trade offer endpoints, from its HTTP `status` and `body`.
+///
+/// Steam answers with a JSON error, with the login page or an empty body if the session or its sessionid are no longer
+/// accepted, or with an error page. `None` if nothing in the response tells why it failed.
+pub(crate) fn offer_error_from_response(status: u16, body: &str) -> Option<OfferError> {
+    if let Some(error) = offer_error_from_json(body) {
+        return Some(error);
+    }
+
+    if status == 401 || status == 403 || body.contains("g_steamID = false") {
+        return Some(OfferError::SessionExpired);
+    }
+    PAGE_ERROR_REGEX
+        .captures(body)
+        .map(|captures| offer_error_from_message(&captures["message"]))
+}
+
+/// Error of a JSON response of the trade offer endpoints, with a `strError` or a `success` other than 1.
+///
+/// Successful responses have every field optional, so this must be checked before parsing them.
+pub(crate) fn offer_error_from_json(body: &str) -> Option<OfferError> {
+    let response = serde_json::from_str::<TradeOfferGenericErrorResponse>(body).ok()?;
+    if let Some(message) = response.error_message {
+        return Some(offer_error_from_message(&message));
+    }
+    response
+        .eresult
+        .filter(|eresult| *eresult != EResult::OK)
+        .map(tradeoffer_error_from_eresult)
+}
+
+/// Maps the `message` of a failed Community Market response.
+pub fn market_error_from_message(message: &str) -> MarketError {
+    let lowercase = message.to_lowercase();
+
+    if lowercase.contains("no longer in your inventory") || lowercase.contains("not allowed to be traded") {
+        MarketError::ItemUnavailable
+    } else if lowercase.contains("pending confirmation") {
+        MarketError::PendingConfirmation
+    } else if lowercase.contains("already have an active buy order") {
+        MarketError::BuyOrderExists
+    } else if lowercase.contains("previous action") || lowercase.contains("too many") {
+        MarketError::RateLimited
+    } else if lowercase.contains("community market")
+        && ["not allowed", "unable", "cannot", "can't"]
+            .iter()
+            .any(|restriction| lowercase.contains(restriction))
+    {
+        MarketError::Restricted(message.to_string())
+    } else {
+        MarketError::GeneralFailure(message.to_string())
+    }
+}
+
+/// Maps the `success` EResult of a failed gems, booster or badge response.
+///
+/// Steam doesn't tell how many gems were missing, so the amounts of [`GemsError::InsufficientGems`] are zero.
+pub fn gems_error_from_eresult(eresult: EResult) -> GemsError {
+    match eresult {
+        EResult::RateLimitExceeded | EResult::LimitExceeded | EResult::Busy | EResult::TooManyPending => {
+            GemsError::RateLimited
+        }
+        EResult::InsufficientFunds => GemsError::InsufficientGems {
+            needed: 0,
+            available: 0,
+        },
+        e => GemsError::GeneralFailure(format!(
+            "{}{}",
+            "Please check: https://steamerrors.com/",
+            &*serde_json::to_string(&e).unwrap()
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,6 +430,94 @@ mod tests {
     #[test]
     fn error_strmessage() {
         let error_message = "Something went wrong (26)";
-        assert_eq!(error_from_strmessage(error_message).unwrap(), OfferError::Revoked)
+        assert_eq!(error_from_strmessage(error_message).unwrap(), OfferError::Revoked);
+
+        // parentheses of persona names or emoticons, and non-ASCII text
+        assert_eq!(
+            error_from_strmessage("Bob :) can't trade right now (15)"),
+            Some(tradeoffer_error_from_eresult(EResult::AccessDenied))
+        );
+        assert_eq!(
+            error_from_strmessage("Ошибка (Боб) :( (11)"),
+            Some(OfferError::InvalidState)
+        );
+        assert_eq!(error_from_strmessage("Bob :) (no code)"), None);
+        assert_eq!(error_from_strmessage(") 26 ("), None);
+        assert_eq!(
+            offer_error_from_message("ボブ :) 取引できません"),
+            OfferError::GeneralFailure("ボブ :) 取引できません".to_string())
+        );
+    }
+
+    #[test]
+    fn offer_failures() {
+        assert_eq!(
+            offer_error_from_message("You cannot trade with Foo because they have a trade ban."),
+            OfferError::TradeBan
+        );
+        assert_eq!(
+            offer_error_from_message("You have sent too many trade offers, or have too many outstanding trade offers."),
+            OfferError::TooManyOffers
+        );
+        assert_eq!(
+            offer_error_from_message("There was an error sending your trade offer.  Please try again later. (26)"),
+            OfferError::Revoked
+        );
+        assert_eq!(
+            offer_error_from_message("One or more of the items in this trade offer is no longer available. (26)"),
+            OfferError::ItemUnavailable
+        );
+        assert!(matches!(
+            offer_error_from_message("Something unexpected happened."),
+            OfferError::GeneralFailure(_)
+        ));
+
+        assert_eq!(
+            offer_error_from_response(
+                200,
+                r#"{"strError":"Bar's inventory is full. Bar needs to make room for these items. (25)"}"#
+            ),
+            Some(OfferError::PartnerInventoryFull)
+        );
+        assert_eq!(
+            offer_error_from_response(200, r#"{"success":112}"#),
+            Some(OfferError::PartnerLimitedAccount)
+        );
+        assert!(matches!(
+            offer_error_from_response(
+                200,
+                r#"{"strError":"There was an error sending your trade offer. (25)"}"#
+            ),
+            Some(OfferError::GeneralFailure(_))
+        ));
+        assert_eq!(offer_error_from_response(401, ""), Some(OfferError::SessionExpired));
+        assert_eq!(
+            offer_error_from_response(
+                200,
+                "<html><div id=\"error_msg\">\n\tFoo is not available to trade. More information will be shown to \
+                 Foo if you invite them to trade.\n</div></html>"
+            ),
+            Some(OfferError::SteamGuardRecentlyEnabled)
+        );
+        assert_eq!(offer_error_from_response(502, "<html>Bad Gateway</html>"), None);
+    }
+
+    #[test]
+    fn market_messages() {
+        assert_eq!(
+            market_error_from_message(
+                "The item specified is no longer in your inventory or is not allowed to be traded on the Community \
+                 Market."
+            ),
+            MarketError::ItemUnavailable
+        );
+        assert_eq!(
+            market_error_from_message("You cannot sell any items until your previous action completes."),
+            MarketError::RateLimited
+        );
+        assert!(matches!(
+            market_error_from_message("You are not allowed to use the Community Market."),
+            MarketError::Restricted(_)
+        ));
     }
 }
@@ -0,0 +1,336 @@
+//! Trade offer lifecycle events, delivered to pluggable sinks.
+//!
+//! Sinks are registered with [`crate::SteamTradeManager::add_event_sink`], and receive an [`OfferEvent`] whenever the
+//! manager creates, confirms, accepts, declines or cancels an offer. A [`crate::poller::TradeOfferPoller`] of the
+//! manager also reports the offers accepted, declined or held by the partner, and the received offers accepted by the
+//! manager that were still waiting for a confirmation.
+//!
+//! Sinks are called one after the other, in the order they were added. A failing sink is logged, and never fails the
+//! operation that emitted the event. Sinks that take longer than [`SINK_TIMEOUT`] are given up on, so a hung endpoint
+//! doesn't stall trading.
+
+use std::fmt::Debug;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc as std_mpsc;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use futures::channel::mpsc;
+use futures::channel::mpsc::Receiver;
+use futures::channel::mpsc::Sender;
+use futures::channel::oneshot;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use hmac::Hmac;
+use hmac::Mac;
+use parking_lot::Mutex;
+use reqwest::Client;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+use steam_mobile::Url;
+use tracing::warn;
+
+use crate::errors::EventSinkError;
+
+/// Header of the webhook requests with the signature of the body.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// How long a sink may take to deliver an event.
+pub const SINK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How many events a [`ChannelSink`] holds until they are received, unless given with [`ChannelSink::with_capacity`].
+pub const CHANNEL_SINK_CAPACITY: usize = 1024;
+
+/// A line to append, and where to report whether it was written.
+type WriteRequest = (Vec<u8>, oneshot::Sender<std::io::Result<()>>);
+
+/// What happened to a trade offer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferEventKind {
+    /// Offer was sent by this account, either a new one or a counter offer.
+    Created,
+    /// Offer was confirmed with the mobile authenticator.
+    Confirmed,
+    /// Offer was accepted, and the items were exchanged.
+    Accepted,
+    /// Offer was declined, by this account or by the partner.
+    Declined,
+    /// Offer sent by this account was canceled with [`crate::SteamTradeManager::cancel_offer`].
+    Canceled,
+    /// Offer was accepted, but the items are held by Steam for a while.
+    Escrowed,
+}
+
+/// A change of a trade offer, as delivered to an [`EventSink`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfferEvent {
+    #[allow(missing_docs)]
+    pub kind: OfferEventKind,
+    #[allow(missing_docs)]
+    pub tradeoffer_id: u64,
+    /// SteamID64 of the partner, if known.
+    pub partner: Option<u64>,
+    /// SteamID64 of this account.
+    pub account: u64,
+    /// Unix timestamp of when the event was emitted.
+    pub time: u64,
+}
+
+impl OfferEvent {
+    #[allow(missing_docs)]
+    pub fn new(kind: OfferEventKind, tradeoffer_id: u64, partner: Option<u64>, account: u64) -> Self {
+        Self {
+            kind,
+            tradeoffer_id,
+            partner,
+            account,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Receives the trade offer events of a [`crate::SteamTradeManager`].
+pub trait EventSink: Debug + Send + Sync {
+    /// Delivers `event`. Errors are logged by the manager.
+    fn deliver(&self, event: &OfferEvent) -> BoxFuture<'_, Result<(), EventSinkError>>;
+}
+
+/// Posts every event as JSON to an HTTP endpoint.
+///
+/// The body is signed with HMAC-SHA256 and the shared secret, and the hex encoded signature is sent in the
+/// [`SIGNATURE_HEADER`] header as `sha256=<signature>`. Receivers can check it with [`WebhookSink::signature`].
+#[derive(Debug, Clone)]
+pub struct WebhookSink {
+    client: Client,
+    url: Url,
+    secret: Vec<u8>,
+}
+
+impl WebhookSink {
+    /// Sink posting to `url`, with requests that time out after [`SINK_TIMEOUT`].
+    pub fn new(url: Url, secret: impl Into<Vec<u8>>) -> Self {
+        let client = Client::builder()
+            .timeout(SINK_TIMEOUT)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Self::with_client(client, url, secret)
+    }
+
+    /// Same as [`Self::new`], sending the requests with `client`, such as one with a proxy or timeouts.
+    pub fn with_client(client: Client, url: Url, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            client,
+            url,
+            secret: secret.into(),
+        }
+    }
+
+    /// Hex encoded HMAC-SHA256 of `body` with `secret`.
+    pub fn signature(secret: &[u8], body: &[u8]) -> String {
+        // safe to unwrap, HMAC accepts keys of any size
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    async fn post(&self, event: OfferEvent) -> Result<(), EventSinkError> {
+        let body = serde_json::to_vec(&event)?;
+        let signature = Self::signature(&self.secret, &body);
+
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header("Content-Type", "application/json")
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(EventSinkError::Rejected(status.as_u16()));
+        }
+        Ok(())
+    }
+}
+
+impl EventSink for WebhookSink {
+    fn deliver(&self, event: &OfferEvent) -> BoxFuture<'_, Result<(), EventSinkError>> {
+        self.post(*event).boxed()
+    }
+}
+
+/// Appends every event to a file, as a line of JSON.
+///
+/// Lines are written one at a time by a thread of the sink, so the file I/O never blocks the async executor. The
+/// thread stops once the sink is dropped.
+#[derive(Debug)]
+pub struct JsonlFileSink {
+    path: PathBuf,
+    writer: std_mpsc::Sender<WriteRequest>,
+}
+
+impl JsonlFileSink {
+    /// Sink appending to the file at `path`, which is created if needed.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let (writer, requests) = std_mpsc::channel::<WriteRequest>();
+
+        let writer_path = path.clone();
+        thread::spawn(move || {
+            for (line, written) in requests {
+                let result = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&writer_path)
+                    .and_then(|mut file| file.write_all(&line));
+                let _ = written.send(result);
+            }
+        });
+
+        Self { path, writer }
+    }
+
+    #[allow(missing_docs)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn append(&self, event: OfferEvent) -> Result<(), EventSinkError> {
+        let mut line = serde_json::to_vec(&event)?;
+        line.push(b'\n');
+
+        let (written, result) = oneshot::channel();
+        self.writer.send((line, written)).map_err(|_| EventSinkError::Closed)?;
+        result.await.map_err(|_| EventSinkError::Closed)??;
+        Ok(())
+    }
+}
+
+impl EventSink for JsonlFileSink {
+    fn deliver(&self, event: &OfferEvent) -> BoxFuture<'_, Result<(), EventSinkError>> {
+        self.append(*event).boxed()
+    }
+}
+
+/// Sends every event to an in-memory channel, such as one read by another task of the bot.
+///
+/// The channel is bounded, so a receiver that stopped reading doesn't grow it forever. Events that don't fit are
+/// dropped with a warning.
+#[derive(Debug)]
+pub struct ChannelSink {
+    sender: Mutex<Sender<OfferEvent>>,
+}
+
+impl ChannelSink {
+    /// Returns the sink, and the receiving side of its channel, holding up to [`CHANNEL_SINK_CAPACITY`] events.
+    pub fn new() -> (Self, Receiver<OfferEvent>) {
+        Self::with_capacity(CHANNEL_SINK_CAPACITY)
+    }
+
+    /// Same as [`Self::new`], holding up to `capacity` events, and at least one.
+    ///
+    /// Each clone of the sink may add one more.
+    pub fn with_capacity(capacity: usize) -> (Self, Receiver<OfferEvent>) {
+        // every sender has a slot of its own, besides the shared buffer
+        let (sender, receiver) = mpsc::channel(capacity.saturating_sub(1));
+        let sink = Self {
+            sender: Mutex::new(sender),
+        };
+        (sink, receiver)
+    }
+}
+
+impl Clone for ChannelSink {
+    fn clone(&self) -> Self {
+        Self {
+            sender: Mutex::new(self.sender.lock().clone()),
+        }
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn deliver(&self, event: &OfferEvent) -> BoxFuture<'_, Result<(), EventSinkError>> {
+        let sent = match self.sender.lock().try_send(*event) {
+            Ok(()) => Ok(()),
+            Err(e) if e.is_full() => {
+                warn!(
+                    "Channel of trade offer events is full. Dropped {:?} of trade offer {}.",
+                    event.kind, event.tradeoffer_id
+                );
+                Ok(())
+            }
+            Err(_) => Err(EventSinkError::Closed),
+        };
+        future::ready(sent).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use futures::StreamExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn sinks() {
+        let event = OfferEvent::new(
+            OfferEventKind::Created,
+            4127395150,
+            Some(76561198040191316),
+            76561198017653157,
+        );
+
+        let (sink, mut receiver) = ChannelSink::new();
+        sink.deliver(&event).await.unwrap();
+        assert_eq!(receiver.next().await, Some(event));
+        drop(receiver);
+        assert!(matches!(sink.deliver(&event).await, Err(EventSinkError::Closed)));
+
+        // events beyond the capacity are dropped, until the receiver catches up
+        let (sink, mut receiver) = ChannelSink::with_capacity(2);
+        for _ in 0..3 {
+            sink.deliver(&event).await.unwrap();
+        }
+        assert_eq!(receiver.try_next().unwrap(), Some(event));
+        assert_eq!(receiver.try_next().unwrap(), Some(event));
+        assert!(receiver.try_next().is_err());
+        sink.deliver(&event).await.unwrap();
+        assert_eq!(receiver.try_next().unwrap(), Some(event));
+
+        let path = env::temp_dir().join(format!("steam-trading-events-{}.jsonl", std::process::id()));
+        let sink = JsonlFileSink::new(&path);
+        sink.deliver(&event).await.unwrap();
+        sink.deliver(&event).await.unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(serde_json::from_str::<OfferEvent>(lines[1]).unwrap(), event);
+        assert!(lines[0].contains(r#""kind":"created""#));
+    }
+
+    #[test]
+    fn webhook_signature() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            WebhookSink::signature(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
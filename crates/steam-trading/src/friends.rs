@@ -0,0 +1,179 @@
+//! Friends list of the account, so offers can be sent to friends without a trade token.
+//!
+//! Steam lets friends trade with just their SteamID, as with [`crate::Tradelink::friend`]. Bots of the same owner can
+//! become friends with [`crate::SteamTradeManager::befriend`], or accept each other requests with
+//! [`crate::SteamTradeManager::accept_friend_requests`], so their trade URLs don't need to be stored.
+
+use std::collections::HashMap;
+
+use num_traits::FromPrimitive;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use serde_with::serde_as;
+use serde_with::DisplayFromStr;
+use steam_language_gen::generated::enums::EFriendRelationship;
+use steam_mobile::HeaderMap;
+use steam_mobile::Method;
+use tracing::debug;
+
+use crate::errors::InternalError;
+use crate::keyless::call_service_with_access_token;
+use crate::SteamTradeManager;
+use crate::TradeError;
+use crate::TryFutureExt;
+
+const ADD_FRIEND_URL: &str = "https://steamcommunity.com/actions/AddFriendAjax";
+
+#[derive(Debug, Deserialize)]
+struct FriendsListResponse {
+    response: FriendsListBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendsListBody {
+    friendslist: Option<FriendsList>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FriendsList {
+    #[serde(default)]
+    friends: Vec<RawFriend>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RawFriend {
+    #[serde_as(as = "DisplayFromStr")]
+    ulfriendid: u64,
+    efriendrelationship: i32,
+}
+
+#[derive(Debug, Serialize)]
+struct AddFriendRequest {
+    #[serde(rename = "sessionID")]
+    sessionid: String,
+    steamid: u64,
+    accept_invite: u8,
+}
+
+impl FriendsListResponse {
+    fn relationships(self) -> HashMap<u64, EFriendRelationship> {
+        self.response
+            .friendslist
+            .map(|list| list.friends)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|friend| {
+                EFriendRelationship::from_i32(friend.efriendrelationship)
+                    .map(|relationship| (friend.ulfriendid, relationship))
+            })
+            .collect()
+    }
+}
+
+/// Relationship with every account of the friends list, including pending requests, by SteamID64.
+pub(crate) async fn relationships(
+    manager: &SteamTradeManager<'_>,
+) -> Result<HashMap<u64, EFriendRelationship>, TradeError> {
+    let parameters = [("steamid", manager.authenticator.steam_id().to_steam64().to_string())];
+    call_service_with_access_token::<FriendsListResponse>(
+        &manager.authenticator,
+        "IFriendsListService",
+        "GetFriendsList",
+        &parameters,
+    )
+    .await
+    .map(FriendsListResponse::relationships)
+}
+
+/// Sends a friend request to `steamid`, or accepts its pending request if `accept` is set.
+pub(crate) async fn add_friend(manager: &SteamTradeManager<'_>, steamid: u64, accept: bool) -> Result<(), TradeError> {
+    let form = AddFriendRequest {
+        sessionid: manager.session_id()?,
+        steamid,
+        accept_invite: accept as u8,
+    };
+    let mut header = HeaderMap::new();
+    // safe to unwrap
+    header.insert(
+        "Referer",
+        format!("https://steamcommunity.com/profiles/{}/", steamid)
+            .parse()
+            .unwrap(),
+    );
+
+    let response = manager
+        .authenticator
+        .request_custom_endpoint(ADD_FRIEND_URL.to_string(), Method::POST, Some(header), Some(form))
+        .err_into::<InternalError>()
+        .await?;
+    let status = response.status().as_u16();
+    let text = response.text().err_into::<InternalError>().await?;
+
+    if !friend_request_succeeded(status, &text) {
+        return Err(TradeError::GeneralError(format!(
+            "Friend request with {} failed with status {}: {}",
+            steamid, status, text
+        )));
+    }
+    debug!(
+        "Friend request with {} {}.",
+        steamid,
+        if accept { "accepted" } else { "sent" }
+    );
+    Ok(())
+}
+
+fn friend_request_succeeded(status: u16, body: &str) -> bool {
+    let Ok(response) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+    let success = match response.get("success") {
+        Some(Value::Bool(success)) => *success,
+        Some(Value::Number(success)) => success.as_u64() == Some(1),
+        _ => false,
+    };
+    let failed_invites = response
+        .get("failed_invites")
+        .and_then(Value::as_array)
+        .is_some_and(|failed| !failed.is_empty());
+
+    status == 200 && success && !failed_invites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn friends_list() {
+        let response: FriendsListResponse = serde_json::from_str(
+            r#"{"response": {"friendslist": {"bincremental": false, "friends": [
+                {"ulfriendid": "76561198040191316", "efriendrelationship": 3, "time_friend_since": 1690000000},
+                {"ulfriendid": "76561197984835396", "efriendrelationship": 2, "time_friend_since": 0}
+            ]}}}"#,
+        )
+        .unwrap();
+        let relationships = response.relationships();
+
+        assert_eq!(relationships[&76561198040191316], EFriendRelationship::Friend);
+        assert_eq!(relationships[&76561197984835396], EFriendRelationship::RequestRecipient);
+
+        let empty: FriendsListResponse = serde_json::from_str(r#"{"response": {}}"#).unwrap();
+        assert!(empty.relationships().is_empty());
+    }
+
+    #[test]
+    fn friend_requests() {
+        assert!(friend_request_succeeded(
+            200,
+            r#"{"invited":["76561198040191316"],"success":1}"#
+        ));
+        assert!(!friend_request_succeeded(
+            200,
+            r#"{"failed_invites":["76561198040191316"],"failed_invites_result":[25],"success":1}"#
+        ));
+        assert!(!friend_request_succeeded(400, r#"{"success":2}"#));
+    }
+}
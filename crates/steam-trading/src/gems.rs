@@ -0,0 +1,600 @@
+//! Steam Gems, booster packs and badge crafting, on the session of the authenticator.
+//!
+//! Duplicate Steam Community items, such as trading cards, backgrounds and emoticons, can be turned into gems. Gems
+//! are packed into sacks of 1000 to trade them, and spent on booster packs of the games the account owns. Check
+//! [`crate::SteamTradeManager::gems`].
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use serde_with::serde_as;
+use serde_with::DisplayFromStr;
+use serde_with::PickFirst;
+use steam_language_gen::generated::enums::EResult;
+use tracing::debug;
+
+use crate::community;
+use crate::community::CommunityError;
+use crate::errors::gems_error_from_eresult;
+use crate::errors::GemsError;
+use crate::errors::InternalError;
+use crate::inventory::InventoryItem;
+use crate::SteamTradeManager;
+use crate::TradeError;
+
+const BOOSTER_CREATOR_URL: &str = "https://steamcommunity.com/tradingcards/boostercreator/";
+const CREATE_BOOSTER_URL: &str = "https://steamcommunity.com/tradingcards/ajaxcreatebooster/";
+
+/// App and context of the Steam Community inventory, where gems, cards and booster packs are.
+pub const COMMUNITY_APPID: u32 = 753;
+#[allow(missing_docs)]
+pub const COMMUNITY_CONTEXTID: u32 = 6;
+
+/// Gems in a Sack of Gems.
+pub const GEMS_PER_SACK: u64 = 1000;
+
+const GEMS_HASH_NAME: &str = "753-Gems";
+const SACK_OF_GEMS_HASH_NAME: &str = "753-Sack of Gems";
+
+lazy_static! {
+    static ref BOOSTER_CATALOG_REGEX: Regex = Regex::new(
+        r"CBoosterCreatorPage\.Init\(\s*(?P<catalog>\[[^\n]+\]),\s*(?P<total>\d+),\s*(?P<tradable>\d+),\s*(?P<untradable>\d+)"
+    )
+    .unwrap();
+}
+
+/// Which gems are spent on a booster pack. Booster packs made with untradable gems are untradable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(into = "u8")]
+pub enum GemTradability {
+    /// Untradable gems first, then tradable ones.
+    PreferUntradable = 1,
+    #[allow(missing_docs)]
+    TradableOnly = 2,
+    #[allow(missing_docs)]
+    UntradableOnly = 3,
+}
+
+impl From<GemTradability> for u8 {
+    fn from(tradability: GemTradability) -> Self {
+        tradability as u8
+    }
+}
+
+/// Booster packs this account can create, and the gems it has.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoosterCatalog {
+    #[allow(missing_docs)]
+    pub boosters: Vec<BoosterPack>,
+    #[allow(missing_docs)]
+    pub gems: GemBalance,
+}
+
+impl BoosterCatalog {
+    /// Booster pack of `appid`, if this account can create it.
+    pub fn booster(&self, appid: u32) -> Option<&BoosterPack> {
+        self.boosters.iter().find(|booster| booster.appid == appid)
+    }
+}
+
+/// Gems of this account.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct GemBalance {
+    #[allow(missing_docs)]
+    pub total: u64,
+    #[allow(missing_docs)]
+    pub tradable: u64,
+    #[allow(missing_docs)]
+    pub untradable: u64,
+}
+
+impl GemBalance {
+    /// Gems that can be spent with `tradability`.
+    pub fn spendable(&self, tradability: GemTradability) -> u64 {
+        match tradability {
+            GemTradability::PreferUntradable => self.total,
+            GemTradability::TradableOnly => self.tradable,
+            GemTradability::UntradableOnly => self.untradable,
+        }
+    }
+}
+
+/// A booster pack that can be created with gems.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BoosterPack {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    /// Name of the game.
+    pub name: String,
+    #[allow(missing_docs)]
+    pub series: u32,
+    /// Gems it costs.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub price: u64,
+    /// `true` if it was created recently, and can't be created again until `available_at_time`.
+    #[serde(default)]
+    pub unavailable: bool,
+    /// When it can be created again, such as "Oct 19 @ 2:15pm".
+    pub available_at_time: Option<String>,
+}
+
+/// A booster pack created by [`Gems::create_booster_pack`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CreatedBooster {
+    /// Asset id of the booster pack in the Steam Community inventory.
+    pub assetid: i64,
+    /// Gems left.
+    pub gems: GemBalance,
+}
+
+/// Gems received for an item turned into gems.
+#[serde_as]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub struct GrindOutcome {
+    /// Steam sends this key with a trailing space.
+    #[serde(rename = "goo_value_received ", alias = "goo_value_received")]
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub received: u64,
+    /// Gems of the account after grinding.
+    #[serde(rename = "goo_value_total")]
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub total: u64,
+}
+
+/// Badge crafted by [`Gems::craft_badge`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CraftedBadge {
+    /// Items dropped by crafting, such as a background, an emoticon and a coupon.
+    #[serde(rename = "rgDroppedItems", default)]
+    pub dropped_items: Vec<Value>,
+    /// Badge level and experience after crafting.
+    #[serde(rename = "Badge")]
+    pub badge: Option<Value>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct GemValueResponse {
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    goo_value: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct GrindRequest {
+    sessionid: String,
+    appid: u32,
+    assetid: i64,
+    contextid: u32,
+    goo_value_expected: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct ExchangeGemsRequest {
+    sessionid: String,
+    appid: u32,
+    assetid: i64,
+    goo_denomination_in: u64,
+    goo_amount_in: u64,
+    goo_denomination_out: u64,
+    goo_amount_out_expected: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateBoosterRequest {
+    sessionid: String,
+    appid: u32,
+    series: u32,
+    tradability_preference: GemTradability,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct CreateBoosterResponse {
+    purchase_result: Option<BoosterPurchaseResult>,
+    purchase_eresult: Option<EResult>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    goo_amount: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    tradable_goo_amount: Option<u64>,
+    #[serde_as(as = "Option<PickFirst<(_, DisplayFromStr)>>")]
+    #[serde(default)]
+    untradable_goo_amount: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct BoosterPurchaseResult {
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    communityitemid: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct CraftBadgeRequest {
+    sessionid: String,
+    appid: u32,
+    series: u32,
+    border_color: u8,
+    levels: u32,
+}
+
+/// Gems, booster pack and badge client of a [`SteamTradeManager`].
+#[derive(Debug, Clone)]
+pub struct Gems<'a> {
+    manager: SteamTradeManager<'a>,
+}
+
+impl<'a> Gems<'a> {
+    pub(crate) fn new(manager: SteamTradeManager<'a>) -> Self {
+        Self { manager }
+    }
+
+    /// Stacks of gems and sacks of gems of the Steam Community inventory.
+    pub async fn gem_stacks(&self) -> Result<Vec<InventoryItem>, TradeError> {
+        let inventory = self.manager.get_inventory(COMMUNITY_APPID, COMMUNITY_CONTEXTID).await?;
+        Ok(inventory
+            .into_iter()
+            .filter(|item| is_gems(item) || is_sack_of_gems(item))
+            .collect())
+    }
+
+    /// Gems received for turning `item` into gems. It is not always the same for items of the same game.
+    ///
+    /// Returns [`GemsError::NotGrindable`] if the item can't be turned into gems.
+    pub async fn gem_value(&self, item: &InventoryItem) -> Result<u64, TradeError> {
+        let endpoint = format!(
+            "{}ajaxgetgoovalue/?sessionid={}&appid={}&assetid={}&contextid={}",
+            self.profile_url(),
+            self.manager.session_id()?,
+            game_appid(item)?,
+            item.assetid,
+            item.contextid
+        );
+        let (status, body) = community::get(&self.manager.authenticator, endpoint).await?;
+        let response: GemValueResponse = parse_response(status, &body)?;
+
+        if response.goo_value == 0 {
+            return Err(GemsError::NotGrindable(item.assetid).into());
+        }
+        Ok(response.goo_value)
+    }
+
+    /// Turns `item` into gems, expecting to receive `expected_gems`, as returned by [`Self::gem_value`].
+    pub async fn grind_into_gems(&self, item: &InventoryItem, expected_gems: u64) -> Result<GrindOutcome, TradeError> {
+        let form = GrindRequest {
+            sessionid: self.manager.session_id()?,
+            appid: game_appid(item)?,
+            assetid: item.assetid,
+            contextid: item.contextid,
+            goo_value_expected: expected_gems,
+        };
+        let outcome: GrindOutcome = self
+            .post(format!("{}ajaxgrindintogoo/", self.profile_url()), form)
+            .await?;
+
+        debug!("Asset {} turned into {} gems.", item.assetid, outcome.received);
+        Ok(outcome)
+    }
+
+    /// Packs `sacks` sacks of gems, with gems of the `gems` stack.
+    pub async fn pack_gem_sacks(&self, gems: &InventoryItem, sacks: u64) -> Result<(), TradeError> {
+        let needed = sacks_to_gems(sacks)?;
+        if !is_gems(gems) || gems.amount < needed {
+            return Err(GemsError::InsufficientGems {
+                needed,
+                available: if is_gems(gems) { gems.amount } else { 0 },
+            }
+            .into());
+        }
+
+        self.exchange(gems.assetid, 1, needed, GEMS_PER_SACK, sacks)
+            .await
+            .map_err(|e| with_gem_amounts(e, needed, gems.amount))
+    }
+
+    /// Unpacks `sacks` sacks of gems of the `stack` of sacks into gems.
+    pub async fn unpack_gem_sacks(&self, stack: &InventoryItem, sacks: u64) -> Result<(), TradeError> {
+        let gems = sacks_to_gems(sacks)?;
+        let available = if is_sack_of_gems(stack) {
+            stack.amount.saturating_mul(GEMS_PER_SACK)
+        } else {
+            0
+        };
+        if !is_sack_of_gems(stack) || stack.amount < sacks {
+            return Err(GemsError::InsufficientGems {
+                needed: gems,
+                available,
+            }
+            .into());
+        }
+
+        self.exchange(stack.assetid, GEMS_PER_SACK, sacks, 1, gems)
+            .await
+            .map_err(|e| with_gem_amounts(e, gems, available))
+    }
+
+    /// Booster packs this account can create, with their gem cost, and the gems it has.
+    pub async fn booster_catalog(&self) -> Result<BoosterCatalog, TradeError> {
+        let (status, page) = community::get(&self.manager.authenticator, BOOSTER_CREATOR_URL.to_string()).await?;
+        if status == 429 {
+            return Err(GemsError::RateLimited.into());
+        }
+        parse_booster_catalog(&page)
+    }
+
+    /// Creates a booster pack, spending gems as chosen with `tradability`.
+    ///
+    /// Fails with [`GemsError::BoosterUnavailable`] if the booster pack was created recently, and with
+    /// [`GemsError::InsufficientGems`] if there aren't enough gems, without asking Steam.
+    pub async fn create_booster_pack(
+        &self,
+        appid: u32,
+        tradability: GemTradability,
+    ) -> Result<CreatedBooster, TradeError> {
+        let catalog = self.booster_catalog().await?;
+        let booster = catalog.booster(appid).ok_or(GemsError::BoosterUnavailable {
+            appid,
+            available_at: None,
+        })?;
+
+        if booster.unavailable {
+            return Err(GemsError::BoosterUnavailable {
+                appid,
+                available_at: booster.available_at_time.clone(),
+            }
+            .into());
+        }
+        let available = catalog.gems.spendable(tradability);
+        if available < booster.price {
+            return Err(GemsError::InsufficientGems {
+                needed: booster.price,
+                available,
+            }
+            .into());
+        }
+
+        let form = CreateBoosterRequest {
+            sessionid: self.manager.session_id()?,
+            appid,
+            series: booster.series,
+            tradability_preference: tradability,
+        };
+        let created = self
+            .post(CREATE_BOOSTER_URL.to_string(), form)
+            .await
+            .and_then(created_booster)
+            .map_err(|e| with_gem_amounts(e, booster.price, available))?;
+
+        debug!("Booster pack of app {} created: {}.", appid, created.assetid);
+        Ok(created)
+    }
+
+    /// Crafts a level of the badge of `appid`, consuming a full set of its trading cards.
+    ///
+    /// `foil` crafts the foil badge, with foil cards.
+    pub async fn craft_badge(&self, appid: u32, foil: bool) -> Result<CraftedBadge, TradeError> {
+        let form = CraftBadgeRequest {
+            sessionid: self.manager.session_id()?,
+            appid,
+            series: 1,
+            border_color: foil as u8,
+            levels: 1,
+        };
+        self.post(format!("{}ajaxcraftbadge/", self.profile_url()), form).await
+    }
+
+    async fn exchange(
+        &self,
+        assetid: i64,
+        denomination_in: u64,
+        amount_in: u64,
+        denomination_out: u64,
+        amount_out: u64,
+    ) -> Result<(), TradeError> {
+        let form = ExchangeGemsRequest {
+            sessionid: self.manager.session_id()?,
+            appid: COMMUNITY_APPID,
+            assetid,
+            goo_denomination_in: denomination_in,
+            goo_amount_in: amount_in,
+            goo_denomination_out: denomination_out,
+            goo_amount_out_expected: amount_out,
+        };
+        self.post::<IgnoredAny, _>(format!("{}ajaxexchangegoo/", self.profile_url()), form)
+            .await?;
+        Ok(())
+    }
+
+    fn profile_url(&self) -> String {
+        community::profile_url(&self.manager.authenticator)
+    }
+
+    async fn post<T, F>(&self, endpoint: String, form: F) -> Result<T, TradeError>
+    where
+        T: DeserializeOwned,
+        F: Serialize + Send + Sync,
+    {
+        let referer = format!("{}gamecards/", self.profile_url());
+        let (status, body) =
+            community::send_form::<GemsError, _>(&self.manager.authenticator, endpoint, &referer, form).await?;
+        parse_response(status, &body)
+    }
+}
+
+fn is_gems(item: &InventoryItem) -> bool {
+    item.appid == COMMUNITY_APPID && item.market_hash_name() == Some(GEMS_HASH_NAME)
+}
+
+fn is_sack_of_gems(item: &InventoryItem) -> bool {
+    item.appid == COMMUNITY_APPID && item.market_hash_name() == Some(SACK_OF_GEMS_HASH_NAME)
+}
+
+/// Gems held by `sacks` sacks of gems.
+fn sacks_to_gems(sacks: u64) -> Result<u64, TradeError> {
+    sacks
+        .checked_mul(GEMS_PER_SACK)
+        .ok_or_else(|| GemsError::TooManySacks(sacks).into())
+}
+
+/// Fills in the amounts of a [`GemsError::InsufficientGems`] reported by Steam, which doesn't tell them.
+fn with_gem_amounts(error: TradeError, needed: u64, available: u64) -> TradeError {
+    match error {
+        TradeError::GemsError(GemsError::InsufficientGems { .. }) => {
+            GemsError::InsufficientGems { needed, available }.into()
+        }
+        error => error,
+    }
+}
+
+/// Game `item` belongs to, needed to turn it into gems.
+fn game_appid(item: &InventoryItem) -> Result<u32, TradeError> {
+    item.description
+        .as_ref()
+        .and_then(|description| description.market_fee_app)
+        .ok_or_else(|| GemsError::NotGrindable(item.assetid).into())
+}
+
+/// Parses the booster packs and gems from the booster creator page.
+fn parse_booster_catalog(page: &str) -> Result<BoosterCatalog, TradeError> {
+    let captures = BOOSTER_CATALOG_REGEX
+        .captures(page)
+        .ok_or_else(|| GemsError::GeneralFailure("Booster packs not found on the booster creator page.".to_string()))?;
+    let gems = |name: &str| captures[name].parse::<u64>().unwrap_or_default();
+
+    Ok(BoosterCatalog {
+        boosters: serde_json::from_str(&captures["catalog"]).map_err(InternalError::from)?,
+        gems: GemBalance {
+            total: gems("total"),
+            tradable: gems("tradable"),
+            untradable: gems("untradable"),
+        },
+    })
+}
+
+fn created_booster(response: CreateBoosterResponse) -> Result<CreatedBooster, TradeError> {
+    let Some(purchase_result) = response.purchase_result else {
+        let eresult = response.purchase_eresult.unwrap_or(EResult::Fail);
+        return Err(gems_error_from_eresult(eresult).into());
+    };
+
+    Ok(CreatedBooster {
+        assetid: purchase_result.communityitemid,
+        gems: GemBalance {
+            total: response.goo_amount.unwrap_or_default(),
+            tradable: response.tradable_goo_amount.unwrap_or_default(),
+            untradable: response.untradable_goo_amount.unwrap_or_default(),
+        },
+    })
+}
+
+/// Parses a response, mapping failures to typed errors.
+fn parse_response<T>(status: u16, body: &str) -> Result<T, TradeError>
+where
+    T: DeserializeOwned,
+{
+    community::parse_response::<T, GemsError>(status, body)
+}
+
+impl CommunityError for GemsError {
+    fn rate_limited() -> Self {
+        Self::RateLimited
+    }
+
+    fn general_failure(reason: String) -> Self {
+        Self::GeneralFailure(reason)
+    }
+
+    fn from_response(response: &Value, _status: u16) -> Option<Self> {
+        let eresult = match response.get("success") {
+            Some(Value::Bool(true)) | None => EResult::OK,
+            Some(success) => serde_json::from_value(success.clone()).unwrap_or(EResult::Fail),
+        };
+        (eresult != EResult::OK).then(|| gems_error_from_eresult(eresult))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn booster_catalog() {
+        let page = r#"<script>
+            CBoosterCreatorPage.Init( [{"appid":730,"name":"Counter-Strike 2","series":1,"price":"600"},{"appid":570,"name":"Dota 2","series":1,"price":"400","unavailable":true,"available_at_time":"Oct 19 @ 2:15pm"}], 1450, 450, 1000, "https://steamcommunity.com/tradingcards/boostercreator/" );
+        </script>"#;
+        let catalog = parse_booster_catalog(page).unwrap();
+
+        assert_eq!(catalog.boosters.len(), 2);
+        assert_eq!(catalog.booster(730).unwrap().price, 600);
+        assert_eq!(
+            catalog.booster(570).unwrap().available_at_time.as_deref(),
+            Some("Oct 19 @ 2:15pm")
+        );
+        assert_eq!(catalog.gems.spendable(GemTradability::TradableOnly), 450);
+        assert_eq!(catalog.gems.spendable(GemTradability::PreferUntradable), 1450);
+    }
+
+    #[test]
+    fn responses() {
+        let outcome: GrindOutcome = parse_response(
+            200,
+            r#"{"success":1,"goo_value_received ":"40","goo_value_total":"1490"}"#,
+        )
+        .unwrap();
+        assert_eq!(outcome.received, 40);
+        assert_eq!(outcome.total, 1490);
+
+        assert!(matches!(
+            parse_response::<GrindOutcome>(200, r#"{"success":84}"#),
+            Err(TradeError::GemsError(GemsError::RateLimited))
+        ));
+        assert!(matches!(
+            parse_response::<GrindOutcome>(429, ""),
+            Err(TradeError::GemsError(GemsError::RateLimited))
+        ));
+
+        let response: CreateBoosterResponse = parse_response(
+            200,
+            r#"{"purchase_result":{"communityitemid":"27648561738","appid":730,"item_type":29,"purchaseid":"12","success":1,"rwgrsn":-2},"goo_amount":"850","tradable_goo_amount":"450","untradable_goo_amount":"400"}"#,
+        )
+        .unwrap();
+        let created = created_booster(response).unwrap();
+        assert_eq!(created.assetid, 27648561738);
+        assert_eq!(created.gems.untradable, 400);
+
+        let response: CreateBoosterResponse = parse_response(200, r#"{"purchase_eresult":25}"#).unwrap();
+        assert!(matches!(
+            created_booster(response),
+            Err(TradeError::GemsError(GemsError::RateLimited))
+        ));
+
+        // not enough gems, with the amounts known by the caller
+        let response: CreateBoosterResponse = parse_response(200, r#"{"purchase_eresult":107}"#).unwrap();
+        assert!(matches!(
+            created_booster(response).map_err(|e| with_gem_amounts(e, 600, 450)),
+            Err(TradeError::GemsError(GemsError::InsufficientGems {
+                needed: 600,
+                available: 450
+            }))
+        ));
+        assert!(matches!(
+            parse_response::<IgnoredAny>(200, r#"{"success":107}"#),
+            Err(TradeError::GemsError(GemsError::InsufficientGems { .. }))
+        ));
+    }
+
+    #[test]
+    fn sacks() {
+        assert_eq!(sacks_to_gems(3).unwrap(), 3000);
+        assert!(matches!(
+            sacks_to_gems(u64::MAX),
+            Err(TradeError::GemsError(GemsError::TooManySacks(u64::MAX)))
+        ));
+    }
+}
@@ -0,0 +1,316 @@
+//! Protection against stolen API Keys.
+//!
+//! A known scam uses a stolen API Key to cancel the offers sent by an account, and right away sends look-alike offers
+//! to an account of the scammer, hoping they get confirmed instead. [`crate::SteamTradeManager`] remembers the partner
+//! and items of every offer it creates or accepts, and refuses to confirm anything else, failing with
+//! [`ConfirmationError::Refused`](crate::ConfirmationError::Refused).
+//!
+//! Offers created by the manager that are canceled by someone else are reported by the
+//! [`TradeOfferPoller`](crate::poller::TradeOfferPoller) as [`TradeOfferEvent::CanceledByOthers`]. Offers are only
+//! remembered in memory, so offers created before a restart, or by another process, are refused unless registered
+//! with [`crate::SteamTradeManager::expect_offer`]. [`crate::SteamTradeManager::disable_offer_guard`] turns the guard
+//! off altogether.
+//!
+//! Offers parsed from the trade offers pages, for accounts without a working API Key or access token, don't list their
+//! items. Only their partner is checked.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use steam_language_gen::generated::enums::ETradeOfferState;
+use steamid_parser::SteamID;
+use tappet::response_types::CEcon_Asset;
+use tappet::response_types::TradeOffer_Trade;
+use tracing::warn;
+
+use crate::ledger::AssetKey;
+use crate::poller::is_final_state;
+use crate::poller::TradeOfferEvent;
+use crate::AssetCollection;
+use crate::GuardViolation;
+use crate::TradeOffer;
+
+/// Amount of each asset on one side of an offer.
+type ItemSet = HashMap<AssetKey, i64>;
+
+/// Partner and items of an offer created or accepted by this account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExpectedOffer {
+    /// SteamID64 of the partner.
+    partner: u64,
+    items_to_give: ItemSet,
+    items_to_receive: ItemSet,
+}
+
+impl ExpectedOffer {
+    pub(crate) fn new(
+        partner: u64,
+        my_assets: Option<&AssetCollection>,
+        their_assets: Option<&AssetCollection>,
+    ) -> Self {
+        Self {
+            partner,
+            items_to_give: items_of_collection(my_assets),
+            items_to_receive: items_of_collection(their_assets),
+        }
+    }
+
+    pub(crate) fn from_offer(offer: &TradeOffer) -> Self {
+        Self::new(
+            offer.their_tradelink.partner_id.to_steam64(),
+            offer.my_assets.as_ref(),
+            offer.their_assets.as_ref(),
+        )
+    }
+
+    pub(crate) fn from_trade(offer: &TradeOffer_Trade) -> Self {
+        Self {
+            partner: partner_of(offer),
+            items_to_give: items_of_trade(offer.items_to_give.as_deref()),
+            items_to_receive: items_of_trade(offer.items_to_receive.as_deref()),
+        }
+    }
+}
+
+/// SteamID64 of the partner of `offer`.
+pub(crate) fn partner_of(offer: &TradeOffer_Trade) -> u64 {
+    SteamID::from_steam3(offer.accountid_other as u32, None, None).to_steam64()
+}
+
+fn items_of_collection(assets: Option<&AssetCollection>) -> ItemSet {
+    let mut items = ItemSet::new();
+    for asset in assets.into_iter().flat_map(|collection| collection.0.iter()) {
+        let key = AssetKey::new(
+            asset.appid,
+            asset.contextid.parse().unwrap_or_default(),
+            asset.assetid.parse().unwrap_or_default(),
+        );
+        *items.entry(key).or_default() += asset.amount;
+    }
+    items
+}
+
+fn items_of_trade(assets: Option<&[CEcon_Asset]>) -> ItemSet {
+    let mut items = ItemSet::new();
+    for asset in assets.unwrap_or_default() {
+        let key = AssetKey::new(asset.appid as u32, asset.contextid as u32, asset.assetid);
+        *items.entry(key).or_default() += asset.amount;
+    }
+    items
+}
+
+/// Offers created or accepted by this account, shared by every clone of a [`crate::SteamTradeManager`].
+#[derive(Debug, Default)]
+pub(crate) struct OfferGuard {
+    expected: HashMap<u64, ExpectedOffer>,
+    canceled_by_us: HashSet<u64>,
+    /// Every offer is confirmed, whatever it contains.
+    disabled: bool,
+    /// Received offers accepted by this account, whose acceptance is waiting for a confirmation.
+    pending_acceptances: HashSet<u64>,
+}
+
+impl OfferGuard {
+    /// Remembers an offer that may be confirmed later.
+    pub(crate) fn expect(&mut self, tradeoffer_id: u64, offer: ExpectedOffer) {
+        self.expected.insert(tradeoffer_id, offer);
+    }
+
+    /// Stops refusing confirmations. Offers are still remembered, to report the ones canceled by others.
+    pub(crate) fn disable(&mut self) {
+        self.disabled = true;
+    }
+
+    /// Remembers a received offer accepted by this account, that Steam didn't complete yet.
+    pub(crate) fn await_acceptance(&mut self, tradeoffer_id: u64) {
+        self.pending_acceptances.insert(tradeoffer_id);
+    }
+
+    /// Whether `event` completes the acceptance of a received offer remembered with [`Self::await_acceptance`].
+    ///
+    /// Offers are forgotten once held by Steam or once they can't change anymore.
+    pub(crate) fn settles_acceptance(&mut self, event: &TradeOfferEvent) -> bool {
+        let offer = event.offer();
+        let pending = if is_final_state(offer.state) || offer.state == ETradeOfferState::InEscrow {
+            self.pending_acceptances.remove(&offer.tradeofferid)
+        } else {
+            self.pending_acceptances.contains(&offer.tradeofferid)
+        };
+        pending && matches!(event, TradeOfferEvent::Accepted(_) | TradeOfferEvent::EscrowStarted(_))
+    }
+
+    /// Remembers that this account is canceling `tradeoffer_id`, so it isn't reported.
+    pub(crate) fn cancel(&mut self, tradeoffer_id: u64) {
+        self.canceled_by_us.insert(tradeoffer_id);
+    }
+
+    /// Checks that `offer` has the partner and items it was created or accepted with.
+    pub(crate) fn verify(&self, offer: &TradeOffer_Trade) -> Result<(), GuardViolation> {
+        if self.disabled {
+            return Ok(());
+        }
+
+        let tradeoffer_id = offer.tradeofferid;
+        let expected = self
+            .expected
+            .get(&tradeoffer_id)
+            .ok_or(GuardViolation::UnknownOffer(tradeoffer_id))?;
+        let found = ExpectedOffer::from_trade(offer);
+
+        if found.partner != expected.partner {
+            return Err(GuardViolation::PartnerMismatch {
+                tradeoffer_id,
+                expected: expected.partner,
+                found: found.partner,
+            });
+        }
+        if offer.items_to_give.is_none() && offer.items_to_receive.is_none() {
+            warn!(
+                "Trade offer {} was fetched without its items, so only its partner was checked.",
+                tradeoffer_id
+            );
+            return Ok(());
+        }
+        if found.items_to_give != expected.items_to_give || found.items_to_receive != expected.items_to_receive {
+            return Err(GuardViolation::ItemsMismatch(tradeoffer_id));
+        }
+        Ok(())
+    }
+
+    /// Turns the cancellation of an offer created by this account, that it didn't cancel itself, into
+    /// [`TradeOfferEvent::CanceledByOthers`].
+    ///
+    /// Offers that can't change anymore are forgotten.
+    pub(crate) fn screen(&mut self, event: TradeOfferEvent) -> TradeOfferEvent {
+        let tradeofferid = event.offer().tradeofferid;
+        let created_by_us = self.expected.contains_key(&tradeofferid) && event.offer().is_our_offer;
+        let canceled_by_us = self.canceled_by_us.contains(&tradeofferid);
+
+        if is_final_state(event.offer().state) {
+            self.expected.remove(&tradeofferid);
+            self.canceled_by_us.remove(&tradeofferid);
+        }
+
+        match event {
+            TradeOfferEvent::StateChanged { offer, .. }
+                if offer.state == ETradeOfferState::Canceled && created_by_us && !canceled_by_us =>
+            {
+                TradeOfferEvent::CanceledByOthers(offer)
+            }
+            event => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::trade_offer;
+
+    const TRADEOFFER_ID: u64 = 4127395150;
+    const PARTNER: u64 = 76561198040191316;
+
+    fn trade(accountid_other: u64, assetid: i64, state: ETradeOfferState) -> TradeOffer_Trade {
+        trade_offer(
+            TRADEOFFER_ID,
+            state,
+            serde_json::json!({
+                "accountid_other": accountid_other,
+                "items_to_give": [{
+                    "appid": 730,
+                    "contextid": "2",
+                    "assetid": assetid.to_string(),
+                    "classid": "1",
+                    "instanceid": "0",
+                    "amount": "1",
+                    "missing": false,
+                    "est_usd": "0"
+                }]
+            }),
+        )
+    }
+
+    fn expecting_offer() -> OfferGuard {
+        let mut my_assets = AssetCollection::default();
+        my_assets.add(730, 2, 18465222145);
+
+        let mut guard = OfferGuard::default();
+        guard.expect(TRADEOFFER_ID, ExpectedOffer::new(PARTNER, Some(&my_assets), None));
+        guard
+    }
+
+    #[test]
+    fn verify_offers() {
+        let guard = expecting_offer();
+        assert_eq!(
+            guard.verify(&trade(79925588, 18465222145, ETradeOfferState::Active)),
+            Ok(())
+        );
+        assert_eq!(
+            guard.verify(&trade(24569668, 18465222145, ETradeOfferState::Active)),
+            Err(GuardViolation::PartnerMismatch {
+                tradeoffer_id: TRADEOFFER_ID,
+                expected: PARTNER,
+                found: 76561197984835396,
+            })
+        );
+        assert_eq!(
+            guard.verify(&trade(79925588, 17034419698, ETradeOfferState::Active)),
+            Err(GuardViolation::ItemsMismatch(TRADEOFFER_ID))
+        );
+        assert_eq!(
+            OfferGuard::default().verify(&trade(79925588, 18465222145, ETradeOfferState::Active)),
+            Err(GuardViolation::UnknownOffer(TRADEOFFER_ID))
+        );
+
+        // parsed from the trade offers pages, without items
+        let mut without_items = trade(79925588, 17034419698, ETradeOfferState::Active);
+        without_items.items_to_give = None;
+        assert_eq!(guard.verify(&without_items), Ok(()));
+        without_items.accountid_other = 24569668;
+        assert!(matches!(
+            guard.verify(&without_items),
+            Err(GuardViolation::PartnerMismatch { .. })
+        ));
+
+        let mut disabled = OfferGuard::default();
+        disabled.disable();
+        assert_eq!(
+            disabled.verify(&trade(79925588, 18465222145, ETradeOfferState::Active)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn cancellations() {
+        let canceled = || TradeOfferEvent::StateChanged {
+            offer: trade(79925588, 18465222145, ETradeOfferState::Canceled),
+            previous: Some(ETradeOfferState::Active),
+        };
+
+        let mut guard = expecting_offer();
+        assert!(matches!(guard.screen(canceled()), TradeOfferEvent::CanceledByOthers(_)));
+        // forgotten once canceled
+        assert!(matches!(guard.screen(canceled()), TradeOfferEvent::StateChanged { .. }));
+
+        let mut guard = expecting_offer();
+        guard.cancel(TRADEOFFER_ID);
+        assert!(matches!(guard.screen(canceled()), TradeOfferEvent::StateChanged { .. }));
+    }
+
+    #[test]
+    fn pending_acceptances() {
+        let accepted = || {
+            let mut offer = trade(79925588, 18465222145, ETradeOfferState::Accepted);
+            offer.is_our_offer = false;
+            TradeOfferEvent::Accepted(offer)
+        };
+
+        let mut guard = OfferGuard::default();
+        assert!(!guard.settles_acceptance(&accepted()));
+        guard.await_acceptance(TRADEOFFER_ID);
+        assert!(guard.settles_acceptance(&accepted()));
+        // reported only once
+        assert!(!guard.settles_acceptance(&accepted()));
+    }
+}
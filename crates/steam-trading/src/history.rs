@@ -0,0 +1,191 @@
+//! Complete trade history, from `IEconService/GetTradeHistory`.
+//!
+//! Steam returns the history newest first, a page at a time. The cursor of the next page is the time and id of the
+//! last trade of the current one, and is followed automatically by [`crate::SteamTradeManager::trade_history`].
+
+use std::time::Duration;
+
+use futures::stream;
+use futures::Stream;
+use futures::TryStreamExt;
+use futures_timer::Delay;
+use tappet::response_types::CEcon_GetTradeHistory_Response_Trade_Intermediate;
+use tappet::response_types::GetTradeHistoryResponse;
+use tappet::response_types::TradeHistory_Trade;
+use tappet::ExecutorResponse;
+use tracing::debug;
+use tracing::warn;
+
+use crate::keyless::call_with_access_token;
+use crate::keyless::is_unauthorized;
+use crate::SteamTradeManager;
+use crate::TradeError;
+use crate::TryFutureExt;
+use crate::STANDARD_DELAY;
+
+/// Maximum number of trades Steam returns on a single page.
+const TRADE_HISTORY_PAGE_SIZE: u32 = 500;
+
+/// A page of the trade history.
+pub type TradeHistoryPage = CEcon_GetTradeHistory_Response_Trade_Intermediate;
+
+/// What to include when streaming the trade history.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TradeHistoryOptions {
+    /// Include failed and rolled back trades.
+    pub include_failed: bool,
+    /// Include the descriptions of the traded items, on [`TradeHistoryPage::descriptions`].
+    pub get_descriptions: bool,
+    /// Trades fetched by request, up to 500.
+    pub page_size: u32,
+}
+
+impl Default for TradeHistoryOptions {
+    fn default() -> Self {
+        Self {
+            include_failed: false,
+            get_descriptions: false,
+            page_size: TRADE_HISTORY_PAGE_SIZE,
+        }
+    }
+}
+
+/// Position of the next page on the trade history.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct HistoryCursor {
+    start_after_time: u32,
+    start_after_tradeid: i64,
+}
+
+impl HistoryCursor {
+    /// Cursor of the page after `page`, or `None` if it is the last one.
+    fn after(page: &TradeHistoryPage) -> Option<Self> {
+        if !page.more {
+            return None;
+        }
+
+        page.trades.last().map(|trade| Self {
+            start_after_time: trade.time_init as u32,
+            start_after_tradeid: trade.tradeid,
+        })
+    }
+}
+
+/// Fetches a page with the API Key, or with the access token if there is no key or it was refused.
+async fn fetch_page(
+    manager: &SteamTradeManager<'_>,
+    options: TradeHistoryOptions,
+    cursor: Option<HistoryCursor>,
+) -> Result<TradeHistoryPage, TradeError> {
+    let max_trades = options.page_size.min(TRADE_HISTORY_PAGE_SIZE);
+
+    if let Some(api_client) = &manager.api_client {
+        let api_response = api_client
+            .get()
+            .IEconService()
+            .GetTradeHistory(
+                max_trades,
+                options.include_failed,
+                false,
+                cursor.map(|c| c.start_after_time),
+                cursor.map(|c| c.start_after_tradeid),
+                None,
+                Some(options.get_descriptions),
+                None,
+            )
+            .execute_with_response()
+            .await;
+
+        match api_response {
+            Ok(response) => return Ok(response.response),
+            Err(e) if is_unauthorized(&e) => {
+                warn!("GetTradeHistory refused the API Key: {}. Trying the access token.", e)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut parameters = vec![
+        ("max_trades", max_trades.to_string()),
+        ("include_failed", options.include_failed.to_string()),
+        ("get_descriptions", options.get_descriptions.to_string()),
+    ];
+    if let Some(cursor) = cursor {
+        parameters.push(("start_after_time", cursor.start_after_time.to_string()));
+        parameters.push(("start_after_tradeid", cursor.start_after_tradeid.to_string()));
+    }
+
+    call_with_access_token::<GetTradeHistoryResponse>(&manager.authenticator, "GetTradeHistory", &parameters)
+        .map_ok(|response| response.response)
+        .await
+}
+
+/// Streams every page of the trade history, newest first.
+pub(crate) fn history_pages<'m>(
+    manager: &'m SteamTradeManager<'_>,
+    options: TradeHistoryOptions,
+) -> impl Stream<Item = Result<TradeHistoryPage, TradeError>> + 'm {
+    // `None` once the last page was yielded
+    let initial_state = Some((None, true));
+
+    stream::unfold(
+        initial_state,
+        move |state: Option<(Option<HistoryCursor>, bool)>| async move {
+            let (cursor, first_page) = state?;
+            if !first_page {
+                Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+            }
+
+            match fetch_page(manager, options, cursor).await {
+                Ok(page) => {
+                    debug!("Trade history page fetched with {} trades.", page.trades.len());
+                    let next = HistoryCursor::after(&page).map(|cursor| (Some(cursor), false));
+                    Some((Ok(page), next))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        },
+    )
+}
+
+/// Streams every trade of the history, newest first.
+pub(crate) fn history_trades<'m>(
+    manager: &'m SteamTradeManager<'_>,
+    options: TradeHistoryOptions,
+) -> impl Stream<Item = Result<TradeHistory_Trade, TradeError>> + 'm {
+    history_pages(manager, options)
+        .map_ok(|page| stream::iter(page.trades.into_iter().map(Ok)))
+        .try_flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(more: bool) -> TradeHistoryPage {
+        serde_json::from_value(serde_json::json!({
+            "more": more,
+            "trades": [
+                {"tradeid": "3622543526924228084", "steamid_other": "76561198040191316", "time_init": 1603998438, "status": 3},
+                {"tradeid": "3151905948742966439", "steamid_other": "76561198040191316", "time_init": 1594190957, "status": 3}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn cursor_follows_last_trade() {
+        assert_eq!(
+            HistoryCursor::after(&page(true)),
+            Some(HistoryCursor {
+                start_after_time: 1594190957,
+                start_after_tradeid: 3151905948742966439,
+            })
+        );
+        assert_eq!(HistoryCursor::after(&page(false)), None);
+
+        let mut empty = page(true);
+        empty.trades.clear();
+        assert_eq!(HistoryCursor::after(&empty), None);
+    }
+}
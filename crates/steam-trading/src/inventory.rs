@@ -0,0 +1,392 @@
+//! Community inventory loading.
+//!
+//! Inventories are fetched from `https://steamcommunity.com/inventory/{steamid}/{appid}/{contextid}`, a page at a time,
+//! and every asset is merged with its description.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures_timer::Delay;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_with::serde_as;
+use serde_with::BoolFromInt;
+use serde_with::DisplayFromStr;
+use steam_mobile::Method;
+use steamid_parser::SteamID;
+use tracing::debug;
+
+use crate::errors::InternalError;
+use crate::errors::InventoryError;
+use crate::AssetCollection;
+use crate::SteamCompleteAuthenticator;
+use crate::TradeError;
+use crate::TryFutureExt;
+use crate::STANDARD_DELAY;
+
+const INVENTORY_BASE: &str = "https://steamcommunity.com/inventory/";
+
+/// Maximum number of assets Steam returns on a single page.
+const INVENTORY_PAGE_SIZE: u32 = 2000;
+
+/// An asset of an inventory, with its description.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InventoryItem {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    #[allow(missing_docs)]
+    pub contextid: u32,
+    #[allow(missing_docs)]
+    pub assetid: i64,
+    #[allow(missing_docs)]
+    pub classid: u64,
+    #[allow(missing_docs)]
+    pub instanceid: u64,
+    /// How many of this asset there are. Always 1, except for stackable items, such as gems.
+    pub amount: u64,
+    /// `None` if Steam didn't send a description for the asset class.
+    pub description: Option<ItemDescription>,
+}
+
+impl InventoryItem {
+    /// Whether the item can be traded right now.
+    pub fn is_tradable(&self) -> bool {
+        self.description.as_ref().is_some_and(|d| d.tradable)
+    }
+
+    /// Whether the item can be listed on the Community Market.
+    pub fn is_marketable(&self) -> bool {
+        self.description.as_ref().is_some_and(|d| d.marketable)
+    }
+
+    /// Name used on the Community Market.
+    pub fn market_hash_name(&self) -> Option<&str> {
+        self.description.as_ref().map(|d| &*d.market_hash_name)
+    }
+}
+
+impl<'a> FromIterator<&'a InventoryItem> for AssetCollection {
+    fn from_iter<T: IntoIterator<Item = &'a InventoryItem>>(iter: T) -> Self {
+        let mut collection = Self::default();
+        iter.into_iter()
+            .for_each(|item| collection.add(item.appid, item.contextid, item.assetid));
+        collection
+    }
+}
+
+/// Description shared by every asset of the same class and instance.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemDescription {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub classid: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub instanceid: u64,
+    #[allow(missing_docs)]
+    pub name: String,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub market_name: String,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub market_hash_name: String,
+    /// Type of the item, as shown below its name. E.g. "Mil-Spec Grade Rifle".
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub icon_url: String,
+    #[serde_as(as = "BoolFromInt")]
+    #[allow(missing_docs)]
+    pub tradable: bool,
+    #[serde_as(as = "BoolFromInt")]
+    #[allow(missing_docs)]
+    pub marketable: bool,
+    /// Whether identical items are interchangeable on the market, such as cases and keys.
+    #[serde_as(as = "BoolFromInt")]
+    #[serde(default)]
+    pub commodity: bool,
+    /// Days the item stays untradable after being bought on the market.
+    pub market_tradable_restriction: Option<u32>,
+    /// App the item belongs to, for Steam Community items such as trading cards.
+    pub market_fee_app: Option<u32>,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub descriptions: Vec<DescriptionLine>,
+    /// Lines only shown to the owner, such as the date the item becomes tradable.
+    #[serde(default)]
+    pub owner_descriptions: Vec<DescriptionLine>,
+    /// When the item becomes tradable again, in RFC 3339, such as `2020-11-10T07:00:00Z`. Only sent for items on
+    /// trade hold.
+    pub cache_expiration: Option<String>,
+    /// Links shown below the item, such as "Inspect in Game...".
+    #[serde(default)]
+    pub actions: Vec<ItemAction>,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub market_actions: Vec<ItemAction>,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub tags: Vec<ItemTag>,
+}
+
+/// A line of an item description.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DescriptionLine {
+    /// Either `html` or `text`.
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[allow(missing_docs)]
+    pub value: String,
+    #[allow(missing_docs)]
+    pub color: Option<String>,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemAction {
+    pub link: String,
+    pub name: String,
+}
+
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemTag {
+    pub category: String,
+    pub internal_name: String,
+    #[serde(default)]
+    pub localized_category_name: String,
+    #[serde(default)]
+    pub localized_tag_name: String,
+    pub color: Option<String>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct RawAsset {
+    appid: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    contextid: u32,
+    #[serde_as(as = "DisplayFromStr")]
+    assetid: i64,
+    #[serde_as(as = "DisplayFromStr")]
+    classid: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    instanceid: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    amount: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct InventoryPage {
+    #[serde(default)]
+    assets: Vec<RawAsset>,
+    #[serde(default)]
+    descriptions: Vec<ItemDescription>,
+    #[serde(default)]
+    more_items: u8,
+    last_assetid: Option<String>,
+    error: Option<String>,
+}
+
+impl InventoryPage {
+    /// Parses a page, mapping the known error responses to typed errors.
+    fn parse(status: u16, body: &str) -> Result<Self, TradeError> {
+        match status {
+            403 => return Err(InventoryError::Private.into()),
+            429 => return Err(InventoryError::RateLimited.into()),
+            _ => {}
+        }
+
+        // private or non existent inventories may also answer with an empty body
+        if body.trim().is_empty() || body.trim() == "null" {
+            return Err(InventoryError::Private.into());
+        }
+
+        let mut page = serde_json::from_str::<Self>(body).map_err(InternalError::from)?;
+        if let Some(error) = page.error.take() {
+            return Err(InventoryError::GeneralFailure(error).into());
+        }
+        Ok(page)
+    }
+
+    /// Where the next page starts, if there is one.
+    fn next_start(&self) -> Option<&str> {
+        self.last_assetid.as_deref().filter(|_| self.more_items == 1)
+    }
+}
+
+/// Pairs every asset with its description.
+fn merge_descriptions(
+    assets: Vec<RawAsset>,
+    descriptions: &HashMap<(u64, u64), ItemDescription>,
+) -> Vec<InventoryItem> {
+    assets
+        .into_iter()
+        .map(|asset| InventoryItem {
+            description: descriptions.get(&(asset.classid, asset.instanceid)).cloned(),
+            appid: asset.appid,
+            contextid: asset.contextid,
+            assetid: asset.assetid,
+            classid: asset.classid,
+            instanceid: asset.instanceid,
+            amount: asset.amount,
+        })
+        .collect()
+}
+
+/// Fetches every page of the inventory of `steamid`.
+pub(crate) async fn fetch_inventory(
+    authenticator: &SteamCompleteAuthenticator,
+    steamid: &SteamID,
+    appid: u32,
+    contextid: u32,
+) -> Result<Vec<InventoryItem>, TradeError> {
+    let base_endpoint = format!(
+        "{}{}/{}/{}?l=english&count={}",
+        INVENTORY_BASE,
+        steamid.to_steam64(),
+        appid,
+        contextid,
+        INVENTORY_PAGE_SIZE
+    );
+
+    let mut assets = vec![];
+    let mut descriptions = HashMap::new();
+    let mut start_assetid: Option<String> = None;
+
+    loop {
+        let endpoint = match &start_assetid {
+            None => base_endpoint.clone(),
+            Some(start) => format!("{}&start_assetid={}", base_endpoint, start),
+        };
+
+        let response = authenticator
+            .request_custom_endpoint(endpoint, Method::GET, None, None::<&u8>)
+            .err_into::<InternalError>()
+            .await?;
+        let status = response.status().as_u16();
+        let body = response.text().err_into::<InternalError>().await?;
+
+        let mut page = InventoryPage::parse(status, &body)?;
+        start_assetid = page.next_start().map(ToString::to_string);
+
+        assets.append(&mut page.assets);
+        for description in page.descriptions {
+            descriptions.insert((description.classid, description.instanceid), description);
+        }
+
+        if start_assetid.is_none() {
+            break;
+        }
+        Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+    }
+
+    debug!("Fetched {} assets of {}/{}.", assets.len(), appid, contextid);
+    Ok(merge_descriptions(assets, &descriptions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_inventory_page() -> &'static str {
+        r#"{
+  "assets": [
+    {"appid": 730, "contextid": "2", "assetid": "19793871926", "classid": "3035569977", "instanceid": "302028390", "amount": "1"},
+    {"appid": 730, "contextid": "2", "assetid": "19034292089", "classid": "1989330488", "instanceid": "0", "amount": "1"}
+  ],
+  "descriptions": [
+    {
+      "appid": 730,
+      "classid": "3035569977",
+      "instanceid": "302028390",
+      "currency": 0,
+      "icon_url": "-9a81dlWLwJ2UUGcVs_nsVtzdOEdtWwKGZZLQHTxDZ7I56KU0Zwwo4NUX4oFJZEHLbXH5ApeO4YmlhxYQknCRvCo04DEVlxkKgpot7HxfDhjxszJemkV09-5lpKKqPrxN7LEmyVQ7MEpiLuSrYmnjQO3-UdsZGHyd4_Bd1RvNQ7T_FDrw-_ng5Pu75iY1zI97bhLsvQz",
+      "descriptions": [{"type": "html", "value": "Exterior: Field-Tested"}],
+      "tradable": 1,
+      "actions": [{"link": "steam://rungame/730/76561202255233023/+csgo_econ_action_preview%20S%owner_steamid%A%assetid%D1", "name": "Inspect in Game..."}],
+      "name": "AK-47 | Redline",
+      "market_hash_name": "AK-47 | Redline (Field-Tested)",
+      "market_name": "AK-47 | Redline (Field-Tested)",
+      "type": "Classified Rifle",
+      "marketable": 1,
+      "commodity": 0,
+      "market_tradable_restriction": 7,
+      "tags": [{"category": "Type", "internal_name": "CSGO_Type_Rifle", "localized_category_name": "Type", "localized_tag_name": "Rifle"}]
+    },
+    {
+      "appid": 730,
+      "classid": "1989330488",
+      "instanceid": "0",
+      "name": "Operation Breakout Weapon Case",
+      "market_hash_name": "Operation Breakout Weapon Case",
+      "tradable": 0,
+      "marketable": 1,
+      "commodity": 1,
+      "owner_descriptions": [{"type": "html", "value": "Tradable After Nov 10, 2020 (7:00:00) GMT", "color": "ff4040"}]
+    }
+  ],
+  "more_items": 1,
+  "last_assetid": "19034292089",
+  "total_inventory_count": 120,
+  "success": 1,
+  "rwgrsn": -2
+}"#
+    }
+
+    #[test]
+    fn parse_and_merge() {
+        let page = InventoryPage::parse(200, sample_inventory_page()).unwrap();
+        assert_eq!(page.next_start(), Some("19034292089"));
+
+        let descriptions = page
+            .descriptions
+            .into_iter()
+            .map(|d| ((d.classid, d.instanceid), d))
+            .collect::<HashMap<_, _>>();
+        let items = merge_descriptions(page.assets, &descriptions);
+
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].assetid, 19_793_871_926);
+        assert_eq!(items[0].market_hash_name(), Some("AK-47 | Redline (Field-Tested)"));
+        assert!(items[0].is_tradable());
+        assert_eq!(
+            items[0].description.as_ref().unwrap().tags[0].localized_tag_name,
+            "Rifle"
+        );
+        assert!(!items[1].is_tradable());
+        assert!(items[1].description.as_ref().unwrap().commodity);
+        assert_eq!(items[1].description.as_ref().unwrap().owner_descriptions.len(), 1);
+
+        let collection = items.iter().collect::<AssetCollection>();
+        assert_eq!(collection.0.len(), 2);
+    }
+
+    #[test]
+    fn last_page_and_errors() {
+        let page = InventoryPage::parse(200, r#"{"total_inventory_count":0,"success":1,"rwgrsn":-2}"#).unwrap();
+        assert!(page.assets.is_empty());
+        assert_eq!(page.next_start(), None);
+
+        assert!(matches!(
+            InventoryPage::parse(403, "null"),
+            Err(TradeError::InventoryError(InventoryError::Private))
+        ));
+        assert!(matches!(
+            InventoryPage::parse(429, "null"),
+            Err(TradeError::InventoryError(InventoryError::RateLimited))
+        ));
+        assert!(matches!(
+            InventoryPage::parse(
+                500,
+                r#"{"error":"EYldRefreshAppIfNecessary failed with EResult 55","success":false}"#
+            ),
+            Err(TradeError::InventoryError(InventoryError::GeneralFailure(_)))
+        ));
+    }
+}
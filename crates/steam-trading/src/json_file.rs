@@ -0,0 +1,76 @@
+//! State persisted as JSON files.
+//!
+//! Files are written next to their destination first and then renamed, so a crash never leaves them truncated.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::InternalError;
+use crate::TradeError;
+
+/// Reads the JSON file at `path`. A missing file is read as the default value.
+pub(crate) fn load<T>(path: &Path) -> Result<T, TradeError>
+where
+    T: DeserializeOwned + Default,
+{
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| InternalError::from(e).into()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(InternalError::from(e).into()),
+    }
+}
+
+/// Replaces the file at `path` with `value` as JSON.
+pub(crate) fn save<T>(path: &Path, value: &T) -> Result<(), TradeError>
+where
+    T: Serialize,
+{
+    let temporary_path = temporary_path(path);
+
+    let content = serde_json::to_string(value).map_err(InternalError::from)?;
+    fs::write(&temporary_path, content).map_err(InternalError::from)?;
+    fs::rename(&temporary_path, path).map_err(InternalError::from)?;
+    Ok(())
+}
+
+/// `path` with `.tmp` appended to its file name, so files that only differ by extension don't share it.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn temporary_file_names() {
+        assert_eq!(
+            temporary_path(Path::new("state/poll.json")),
+            Path::new("state/poll.json.tmp")
+        );
+        assert_eq!(temporary_path(Path::new("ledger")), Path::new("ledger.tmp"));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let path = std::env::temp_dir().join(format!("steam-trading-json-{}.json", std::process::id()));
+        let value = BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        save(&path, &value).unwrap();
+        assert_eq!(load::<BTreeMap<String, u32>>(&path).unwrap(), value);
+        assert!(!temporary_path(&path).exists());
+        fs::remove_file(&path).unwrap();
+
+        assert!(load::<BTreeMap<String, u32>>(&path).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+//! Trade offers for accounts without a Web API Key.
+//!
+//! `IEconService` also accepts the access token of the session in place of the key. If the token is refused too, the
+//! offers are parsed from the `/my/tradeoffers/` and `/my/tradeoffers/sent/` pages, which only list active offers, and
+//! without their items. Requests that need more than that fail with [`OfferError::ActiveOffersOnly`].
+//!
+//! Only a refused key or token moves on to the next way. Other failures, such as throttled requests, are returned.
+
+use scraper::ElementRef;
+use scraper::Html;
+use scraper::Selector;
+use serde::de::DeserializeOwned;
+use steam_language_gen::generated::enums::EResult;
+use steam_language_gen::generated::enums::ETradeOfferState;
+use steam_mobile::errors::InternalError as MobileInternalError;
+use steam_mobile::Method;
+use steam_mobile::Url;
+use tappet::errors::SteamAPIError;
+use tappet::response_types::CEcon_GetTradeOffers_Response;
+use tappet::response_types::GetTradeOffersResponse;
+use tappet::response_types::TradeOffer_Trade;
+
+use crate::errors::InternalError;
+use crate::OfferError;
+use crate::SteamCompleteAuthenticator;
+use crate::TradeError;
+use crate::TryFutureExt;
+
+const WEB_API_BASE: &str = "https://api.steampowered.com/";
+const TRADEOFFERS_PAGE: &str = "https://steamcommunity.com/my/tradeoffers/";
+const SENT_TRADEOFFERS_PAGE: &str = "https://steamcommunity.com/my/tradeoffers/sent/";
+
+/// Whether the Web API refused the API Key or the access token, as opposed to failing for a while.
+pub(crate) fn is_unauthorized(error: &SteamAPIError) -> bool {
+    match error {
+        SteamAPIError::SteamHttpError(status) => status == "401" || status == "403",
+        SteamAPIError::EResult(eresult, _) => *eresult == EResult::AccessDenied,
+        _ => false,
+    }
+}
+
+/// Calls `method` of `IEconService` with the access token of the session, instead of the API Key.
+pub(crate) async fn call_with_access_token<T>(
+    authenticator: &SteamCompleteAuthenticator,
+    method: &str,
+    parameters: &[(&str, String)],
+) -> Result<T, TradeError>
+where
+    T: DeserializeOwned,
+{
+    call_service_with_access_token(authenticator, "IEconService", method, parameters).await
+}
+
+/// Calls `method` of the Web API `interface` with the access token of the session.
+///
+/// The token goes in the query, so request errors are returned without their URL.
+pub(crate) async fn call_service_with_access_token<T>(
+    authenticator: &SteamCompleteAuthenticator,
+    interface: &str,
+    method: &str,
+    parameters: &[(&str, String)],
+) -> Result<T, TradeError>
+where
+    T: DeserializeOwned,
+{
+    let access_token = authenticator.access_token();
+    let query = std::iter::once(("access_token", access_token.as_str()))
+        .chain(parameters.iter().map(|(name, value)| (*name, value.as_str())));
+    let endpoint = Url::parse_with_params(&format!("{}{}/{}/v1/", WEB_API_BASE, interface, method), query)
+        .map_err(|e| OfferError::GeneralFailure(e.to_string()))?;
+
+    let response = authenticator
+        .request_custom_endpoint(endpoint.to_string(), Method::GET, None, None::<&u8>)
+        .map_err(without_url)
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(SteamAPIError::SteamHttpError(status.as_u16().to_string()).into());
+    }
+    let text = response
+        .text()
+        .map_err(|e| InternalError::from(e.without_url()))
+        .await?;
+
+    serde_json::from_str(&text).map_err(|e| InternalError::from(e).into())
+}
+
+fn without_url(error: MobileInternalError) -> InternalError {
+    match error {
+        MobileInternalError::HttpError(e) => InternalError::NetworkError(e.without_url()),
+        e => e.into(),
+    }
+}
+
+/// Parses the active offers from the trade offers pages.
+pub(crate) async fn offers_from_pages(
+    authenticator: &SteamCompleteAuthenticator,
+    sent: bool,
+    received: bool,
+) -> Result<GetTradeOffersResponse, TradeError> {
+    let mut response = CEcon_GetTradeOffers_Response {
+        trade_offers_sent: None,
+        trade_offers_received: None,
+    };
+
+    if sent {
+        let page = fetch_page(authenticator, SENT_TRADEOFFERS_PAGE).await?;
+        response.trade_offers_sent = Some(parse_offers(&page, true));
+    }
+    if received {
+        let page = fetch_page(authenticator, TRADEOFFERS_PAGE).await?;
+        response.trade_offers_received = Some(parse_offers(&page, false));
+    }
+
+    Ok(GetTradeOffersResponse { response })
+}
+
+async fn fetch_page(authenticator: &SteamCompleteAuthenticator, endpoint: &str) -> Result<String, TradeError> {
+    let response = authenticator
+        .request_custom_endpoint(endpoint.to_owned(), Method::GET, None, None::<&u8>)
+        .err_into::<InternalError>()
+        .await?;
+    response.text().err_into::<InternalError>().await.map_err(Into::into)
+}
+
+/// Parses every offer of a trade offers page.
+///
+/// Pages don't show when offers were created or updated, so those are zeroed.
+fn parse_offers(document: &str, is_our_offer: bool) -> Vec<TradeOffer_Trade> {
+    let document = Html::parse_document(document);
+
+    // safe to unwrap
+    let offer_selector = Selector::parse("div.tradeoffer").unwrap();
+
+    document
+        .select(&offer_selector)
+        .filter_map(|element| parse_offer(element, is_our_offer))
+        .collect()
+}
+
+fn parse_offer(element: ElementRef, is_our_offer: bool) -> Option<TradeOffer_Trade> {
+    // safe to unwrap
+    let partner_selector = Selector::parse(".tradeoffer_partner [data-miniprofile]").unwrap();
+    let message_selector = Selector::parse(".tradeoffer_message .quote").unwrap();
+    let banner_selector = Selector::parse(".tradeoffer_items_banner").unwrap();
+
+    let tradeofferid = element
+        .value()
+        .id()?
+        .strip_prefix("tradeofferid_")?
+        .parse::<u64>()
+        .ok()?;
+    let accountid_other = element
+        .select(&partner_selector)
+        .next()?
+        .value()
+        .attr("data-miniprofile")?
+        .parse::<u64>()
+        .ok()?;
+    let message = element
+        .select(&message_selector)
+        .next()
+        .map(|quote| quote.text().collect::<String>().trim().to_string())
+        .unwrap_or_default();
+    let state = element
+        .select(&banner_selector)
+        .next()
+        .map_or(ETradeOfferState::Active, |banner| {
+            state_from_banner(&banner.text().collect::<String>())
+        });
+
+    // some fields of the offer are private, so it is deserialized instead
+    serde_json::from_value(serde_json::json!({
+        "tradeofferid": tradeofferid.to_string(),
+        "accountid_other": accountid_other,
+        "message": message,
+        "expiration_time": 0,
+        "trade_offer_state": state as i32,
+        "is_our_offer": is_our_offer,
+        "time_created": 0,
+        "time_updated": 0,
+        "from_real_time_trade": false,
+        "escrow_end_date": 0,
+        "confirmation_method": 0
+    }))
+    .ok()
+}
+
+/// Offers that are no longer active show a banner with their state.
+fn state_from_banner(banner: &str) -> ETradeOfferState {
+    let banner = banner.to_lowercase();
+
+    if banner.contains("accepted") {
+        ETradeOfferState::Accepted
+    } else if banner.contains("declined") {
+        ETradeOfferState::Declined
+    } else if banner.contains("counter") {
+        ETradeOfferState::Countered
+    } else if banner.contains("canceled") || banner.contains("cancelled") {
+        ETradeOfferState::Canceled
+    } else if banner.contains("expired") {
+        ETradeOfferState::Expired
+    } else if banner.contains("hold") {
+        ETradeOfferState::InEscrow
+    } else if banner.contains("confirmation") {
+        ETradeOfferState::CreatedNeedsConfirmation
+    } else if banner.contains("unavailable") {
+        ETradeOfferState::InvalidItems
+    } else {
+        ETradeOfferState::Invalid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offers_from_page() {
+        let page = r#"
+            <div class="tradeoffer" id="tradeofferid_4127395150">
+                <div class="tradeoffer_partner">
+                    <a href="https://steamcommunity.com/profiles/76561198040191316">
+                        <div class="playerAvatar offline" data-miniprofile="79925588"></div>
+                    </a>
+                </div>
+                <div class="tradeoffer_message"><div class="quote"> Hello there </div></div>
+                <div class="tradeoffer_items_ctn active"></div>
+            </div>
+            <div class="tradeoffer" id="tradeofferid_4127395151">
+                <div class="tradeoffer_partner">
+                    <div class="playerAvatar online" data-miniprofile="24569668"></div>
+                </div>
+                <div class="tradeoffer_items_ctn inactive">
+                    <div class="tradeoffer_items_banner">Trade Accepted</div>
+                </div>
+            </div>"#;
+
+        let offers = parse_offers(page, false);
+        assert_eq!(offers.len(), 2);
+        assert_eq!(offers[0].tradeofferid, 4127395150);
+        assert_eq!(offers[0].accountid_other, 79925588);
+        assert_eq!(offers[0].message, "Hello there");
+        assert_eq!(offers[0].state, ETradeOfferState::Active);
+        assert!(!offers[0].is_our_offer);
+        assert_eq!(offers[1].state, ETradeOfferState::Accepted);
+        assert_eq!(offers[1].message, "");
+    }
+
+    #[test]
+    fn refused_credentials() {
+        assert!(is_unauthorized(&SteamAPIError::SteamHttpError("403".to_string())));
+        assert!(is_unauthorized(&SteamAPIError::SteamHttpError("401".to_string())));
+        assert!(is_unauthorized(&SteamAPIError::EResult(
+            EResult::AccessDenied,
+            "Value: 15".to_string()
+        )));
+
+        assert!(!is_unauthorized(&SteamAPIError::SteamHttpError("429".to_string())));
+        assert!(!is_unauthorized(&SteamAPIError::SteamHttpError("500".to_string())));
+        assert!(!is_unauthorized(&SteamAPIError::EResult(
+            EResult::RateLimitExceeded,
+            "Value: 84".to_string()
+        )));
+    }
+}
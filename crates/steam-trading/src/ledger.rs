@@ -0,0 +1,388 @@
+//! Ledger of asset ids across trades.
+//!
+//! Steam gives every traded item a new asset id, found later on the trade history as
+//! [`TradeHistory_TradedAsset::new_assetid`]. If the trade is rolled back, the item gets yet another id,
+//! [`TradeHistory_TradedAsset::rollback_new_assetid`]. The ledger records every one of these links, so the current id of
+//! an item can be found from any id it ever had.
+//!
+//! The ledger is persisted through a [`LedgerStore`]. [`FileLedgerStore`] keeps it as a JSON file.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+use std::path::PathBuf;
+
+use futures::future;
+use futures::TryStreamExt;
+use serde::Deserialize;
+use serde::Serialize;
+use tappet::response_types::ETradeStatus;
+use tappet::response_types::TradeHistory_Trade;
+use tappet::response_types::TradeHistory_TradedAsset;
+use tracing::debug;
+
+use crate::api_extensions::HasAssets;
+use crate::history::TradeHistoryOptions;
+use crate::json_file;
+use crate::SteamTradeManager;
+use crate::TradeError;
+
+/// Trades this recent are read again on every sync, since they can still be rolled back while in escrow.
+const ROLLBACK_WINDOW_SECS: i64 = 16 * 24 * 60 * 60;
+
+/// Identifies an asset at a point in time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AssetKey {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    #[allow(missing_docs)]
+    pub contextid: u32,
+    #[allow(missing_docs)]
+    pub assetid: i64,
+}
+
+impl AssetKey {
+    #[allow(missing_docs)]
+    pub fn new(appid: u32, contextid: u32, assetid: i64) -> Self {
+        Self {
+            appid,
+            contextid,
+            assetid,
+        }
+    }
+}
+
+/// An asset that changed its id.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetLink {
+    #[allow(missing_docs)]
+    pub from: AssetKey,
+    #[allow(missing_docs)]
+    pub to: AssetKey,
+    /// Trade that changed the id.
+    pub tradeid: i64,
+    /// Whether the id changed because the trade was rolled back.
+    pub rollback: bool,
+}
+
+/// Everything the ledger persists.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerState {
+    /// Every link recorded, in the order they were recorded.
+    pub links: Vec<AssetLink>,
+    /// Trades already recorded, and whether their rollback was recorded as well.
+    pub recorded_trades: HashMap<i64, bool>,
+    /// Time of the newest trade recorded.
+    pub last_trade_time: i64,
+}
+
+/// Where the ledger is persisted.
+pub trait LedgerStore: Debug {
+    /// Loads the state. A store without a state yet returns the default one.
+    fn load(&self) -> Result<LedgerState, TradeError>;
+
+    /// Replaces the persisted state with `state`.
+    fn save(&self, state: &LedgerState) -> Result<(), TradeError>;
+}
+
+/// Persists the ledger as a JSON file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLedgerStore {
+    path: PathBuf,
+}
+
+impl FileLedgerStore {
+    #[allow(missing_docs)]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    #[allow(missing_docs)]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl LedgerStore for FileLedgerStore {
+    fn load(&self) -> Result<LedgerState, TradeError> {
+        json_file::load(&self.path)
+    }
+
+    /// The file is written next to its destination first and then renamed, so a crash never leaves it truncated.
+    fn save(&self, state: &LedgerState) -> Result<(), TradeError> {
+        json_file::save(&self.path, state)
+    }
+}
+
+/// Records asset id changes and finds the current id of any asset ever traded.
+#[derive(Debug)]
+pub struct AssetLedger<S: LedgerStore = FileLedgerStore> {
+    store: S,
+    state: LedgerState,
+    /// Position on `state.links` of the newest link from each asset.
+    next: HashMap<AssetKey, usize>,
+}
+
+impl AssetLedger<FileLedgerStore> {
+    /// Ledger persisted on the JSON file at `path`.
+    pub fn with_file(path: impl Into<PathBuf>) -> Result<Self, TradeError> {
+        Self::open(FileLedgerStore::new(path))
+    }
+}
+
+impl<S: LedgerStore> AssetLedger<S> {
+    /// Loads the ledger from `store`.
+    pub fn open(store: S) -> Result<Self, TradeError> {
+        let state = store.load()?;
+        let next = state
+            .links
+            .iter()
+            .enumerate()
+            .map(|(position, link)| (link.from, position))
+            .collect();
+
+        Ok(Self { store, state, next })
+    }
+
+    /// Current state of the ledger.
+    pub fn state(&self) -> &LedgerState {
+        &self.state
+    }
+
+    /// Persists the ledger on its store.
+    pub fn save(&self) -> Result<(), TradeError> {
+        self.store.save(&self.state)
+    }
+
+    /// Records the id changes of `trade`.
+    ///
+    /// Returns whether anything new was recorded. Recording the same trade again is a no-op, unless it was rolled back
+    /// since.
+    pub fn record_trade(&mut self, trade: TradeHistory_Trade) -> bool {
+        if !moves_items(&trade.status) {
+            return false;
+        }
+
+        let rolled_back = is_rollback(&trade.status);
+        match self.state.recorded_trades.get(&trade.tradeid) {
+            Some(true) => return false,
+            Some(false) if !rolled_back => return false,
+            _ => {}
+        }
+
+        let tradeid = trade.tradeid;
+        let time_init = trade.time_init;
+        let already_recorded = self.state.recorded_trades.contains_key(&tradeid);
+
+        for asset in trade.every_asset() {
+            let (old, new) = keys_of(&asset);
+            if !already_recorded {
+                self.link(AssetLink {
+                    from: old,
+                    to: new,
+                    tradeid,
+                    rollback: false,
+                });
+            }
+
+            let rollback_assetid = asset
+                .rollback_new_assetid
+                .as_deref()
+                .and_then(|id| id.parse::<i64>().ok());
+            if let Some(rollback_assetid) = rollback_assetid.filter(|id| *id != 0) {
+                self.link(AssetLink {
+                    from: new,
+                    to: AssetKey::new(old.appid, old.contextid, rollback_assetid),
+                    tradeid,
+                    rollback: true,
+                });
+            }
+        }
+
+        self.state.recorded_trades.insert(tradeid, rolled_back);
+        self.state.last_trade_time = self.state.last_trade_time.max(time_init);
+        true
+    }
+
+    /// Current id of an asset that had the id `key` at some point.
+    ///
+    /// Returns `key` itself if it never changed.
+    pub fn current_id(&self, key: AssetKey) -> AssetKey {
+        self.lineage(key).pop().unwrap_or(key)
+    }
+
+    /// Every id the asset had from `key` onwards, starting with `key` itself.
+    pub fn lineage(&self, key: AssetKey) -> Vec<AssetKey> {
+        let mut lineage = vec![key];
+        let mut current = key;
+
+        while let Some(&position) = self.next.get(&current) {
+            current = self.state.links[position].to;
+            // links are never expected to loop, but a corrupted store must not hang us
+            if lineage.contains(&current) {
+                break;
+            }
+            lineage.push(current);
+        }
+        lineage
+    }
+
+    /// Records every trade of the history made since the last sync, and saves the ledger.
+    ///
+    /// Recent trades are read again, to record rollbacks of trades that were in escrow. Returns how many trades were
+    /// recorded.
+    pub async fn sync(&mut self, manager: &SteamTradeManager<'_>) -> Result<usize, TradeError> {
+        let since = if self.state.recorded_trades.is_empty() {
+            i64::MIN
+        } else {
+            self.state.last_trade_time - ROLLBACK_WINDOW_SECS
+        };
+
+        let options = TradeHistoryOptions {
+            include_failed: true,
+            ..Default::default()
+        };
+        let trades = manager
+            .trade_history(options)
+            .try_take_while(|trade| future::ready(Ok(trade.time_init >= since)))
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        // oldest first, so rollbacks are recorded after the trade itself
+        let recorded = trades
+            .into_iter()
+            .rev()
+            .map(|trade| self.record_trade(trade))
+            .filter(|recorded| *recorded)
+            .count();
+
+        debug!("Asset ledger synced with {} new trades.", recorded);
+        self.save()?;
+        Ok(recorded)
+    }
+
+    fn link(&mut self, link: AssetLink) {
+        self.next.insert(link.from, self.state.links.len());
+        self.state.links.push(link);
+    }
+}
+
+/// Old and new keys of a traded asset.
+fn keys_of(asset: &TradeHistory_TradedAsset) -> (AssetKey, AssetKey) {
+    (
+        AssetKey::new(asset.appid, asset.contextid as u32, asset.assetid),
+        AssetKey::new(asset.appid, asset.new_contextid, asset.new_assetid),
+    )
+}
+
+/// Whether the items of a trade with `status` changed owners at some point.
+fn moves_items(status: &ETradeStatus) -> bool {
+    !matches!(
+        status,
+        ETradeStatus::Init | ETradeStatus::PreCommitted | ETradeStatus::Failed
+    )
+}
+
+fn is_rollback(status: &ETradeStatus) -> bool {
+    matches!(
+        status,
+        ETradeStatus::PartialSupportRollback
+            | ETradeStatus::FullSupportRollback
+            | ETradeStatus::SupportRollbackSelective
+            | ETradeStatus::EscrowRollback
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(tradeid: i64, status: u8, assetid: i64, new_assetid: i64, rollback: Option<i64>) -> TradeHistory_Trade {
+        serde_json::from_value(serde_json::json!({
+            "tradeid": tradeid.to_string(),
+            "steamid_other": "76561198040191316",
+            "time_init": 1603998438,
+            "status": status,
+            "assets_given": [{
+                "appid": 730,
+                "contextid": "2",
+                "assetid": assetid.to_string(),
+                "amount": "1",
+                "classid": "3035569977",
+                "instanceid": "302028390",
+                "new_assetid": new_assetid.to_string(),
+                "new_contextid": "2",
+                "rollback_new_assetid": rollback.map(|id| id.to_string())
+            }]
+        }))
+        .unwrap()
+    }
+
+    fn memory_ledger() -> AssetLedger<MemoryStore> {
+        AssetLedger::open(MemoryStore::default()).unwrap()
+    }
+
+    #[derive(Debug, Default)]
+    struct MemoryStore(std::sync::Mutex<LedgerState>);
+
+    impl LedgerStore for MemoryStore {
+        fn load(&self) -> Result<LedgerState, TradeError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn save(&self, state: &LedgerState) -> Result<(), TradeError> {
+            *self.0.lock().unwrap() = state.clone();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn follows_ids_across_trades() {
+        let mut ledger = memory_ledger();
+        assert!(ledger.record_trade(trade(1, 3, 100, 200, None)));
+        assert!(ledger.record_trade(trade(2, 3, 200, 300, None)));
+        assert!(!ledger.record_trade(trade(2, 3, 200, 300, None)));
+        // failed trades don't move items
+        assert!(!ledger.record_trade(trade(3, 4, 300, 400, None)));
+
+        let first = AssetKey::new(730, 2, 100);
+        assert_eq!(ledger.current_id(first), AssetKey::new(730, 2, 300));
+        assert_eq!(ledger.lineage(first).len(), 3);
+        assert_eq!(
+            ledger.current_id(AssetKey::new(730, 2, 999)),
+            AssetKey::new(730, 2, 999)
+        );
+    }
+
+    #[test]
+    fn records_rollbacks() {
+        let mut ledger = memory_ledger();
+        assert!(ledger.record_trade(trade(1, 10, 100, 200, None)));
+        // the trade in escrow is rolled back later
+        assert!(ledger.record_trade(trade(1, 11, 100, 200, Some(150))));
+        assert!(!ledger.record_trade(trade(1, 11, 100, 200, Some(150))));
+
+        let key = AssetKey::new(730, 2, 100);
+        assert_eq!(ledger.current_id(key), AssetKey::new(730, 2, 150));
+        assert_eq!(ledger.state().links.iter().filter(|l| l.rollback).count(), 1);
+    }
+
+    #[test]
+    fn persists_on_store() {
+        let mut ledger = memory_ledger();
+        ledger.record_trade(trade(1, 3, 100, 200, None));
+        ledger.save().unwrap();
+
+        let reopened = AssetLedger::open(MemoryStore(std::sync::Mutex::new(ledger.state().clone()))).unwrap();
+        assert_eq!(
+            reopened.current_id(AssetKey::new(730, 2, 100)),
+            AssetKey::new(730, 2, 200)
+        );
+
+        let serialized = serde_json::to_string(ledger.state()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LedgerState>(&serialized).unwrap(),
+            *ledger.state()
+        );
+    }
+}
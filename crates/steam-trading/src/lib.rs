@@ -11,7 +11,8 @@
 //! But this also means that you will need to keep track of trades and polling yourself, but it won't be much work,
 //! since there are convenience functions for almost every need.
 //!
-//! Perhaps the event based trading experience will be an extension someday, but for now this works fine.
+//! If you prefer reacting to events, check [`poller::TradeOfferPoller`], which polls trade offers for you and emits
+//! typed events whenever they change.
 //!
 //! Compiles on stable Rust.
 
@@ -27,54 +28,104 @@
     unused_qualifications
 )]
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ops::Deref;
+use std::pin::pin;
 use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use const_format::concatcp;
 pub use errors::ConfirmationError;
+pub use errors::EventSinkError;
+pub use errors::GemsError;
+pub use errors::GuardViolation;
 pub use errors::InternalError;
+pub use errors::InventoryError;
+pub use errors::MarketError;
 pub use errors::OfferError;
 pub use errors::TradeError;
 pub use errors::TradelinkError;
+use futures::future;
+use futures::future::BoxFuture;
+use futures::future::Either;
+use futures::lock::Mutex;
 use futures::stream::FuturesOrdered;
+use futures::FutureExt;
+use futures::Stream;
 use futures::StreamExt;
 use futures::TryFutureExt;
+use futures::TryStreamExt;
 use futures_timer::Delay;
+use parking_lot::RwLock;
 use serde::de::DeserializeOwned;
+use steam_language_gen::generated::enums::EFriendRelationship;
 use steam_language_gen::generated::enums::ETradeOfferState;
+use steam_mobile::cookie_export::ExportedCookie;
+use steam_mobile::errors::InternalError as TransportError;
+use steam_mobile::proxy_pool::ProxyPool;
+use steam_mobile::proxy_pool::ProxyPoolTransport;
+use steam_mobile::transport::HttpTransport;
 use steam_mobile::user::PresentMaFile;
 use steam_mobile::Authenticated;
+use steam_mobile::Confirmation;
 use steam_mobile::ConfirmationAction;
 use steam_mobile::HeaderMap;
+use steam_mobile::HttpRequest;
+use steam_mobile::HttpResponse;
 use steam_mobile::Method;
 use steam_mobile::SteamAuthenticator;
 use steam_mobile::STEAM_COMMUNITY_HOST;
 use steamid_parser::SteamID;
-use tappet::response_types::GetTradeHistoryResponse;
+use tappet::errors::SteamAPIError;
+use tappet::response_types::GetTradeOfferResponse;
 use tappet::response_types::GetTradeOffersResponse;
 use tappet::response_types::TradeHistory_Trade;
 use tappet::response_types::TradeOffer_Trade;
 use tappet::ExecutorResponse;
+use tappet::RequestExecutor;
 use tappet::SteamAPI;
 use tracing::debug;
+use tracing::warn;
 pub use types::asset_collection::AssetCollection;
 pub use types::trade_link::Tradelink;
+pub use types::trade_offer::AcceptOutcome;
 pub use types::trade_offer::TradeOffer;
+pub use types::trade_offer::TradeOfferBuilder;
 
 use crate::additional_checks::check_steam_guard_error;
 use crate::api_extensions::FilterBy;
 use crate::api_extensions::HasAssets;
-use crate::errors::error_from_strmessage;
-use crate::errors::tradeoffer_error_from_eresult;
+use crate::batch::OfferBatch;
+use crate::batch::OfferOutcome;
+use crate::batch::OngoingOffers;
+use crate::errors::offer_error_from_json;
+use crate::errors::offer_error_from_response;
 use crate::errors::TradeError::GeneralError;
+use crate::events::EventSink;
+use crate::events::OfferEvent;
+use crate::events::OfferEventKind;
+use crate::events::SINK_TIMEOUT;
+use crate::gems::Gems;
+use crate::guard::partner_of;
+use crate::guard::ExpectedOffer;
+use crate::guard::OfferGuard;
+use crate::history::TradeHistoryOptions;
+use crate::history::TradeHistoryPage;
+use crate::inventory::fetch_inventory;
+use crate::inventory::InventoryItem;
+use crate::market::Market;
+use crate::trade_hold::trade_hold_from_page;
+use crate::trade_hold::TradeHoldDurations;
+use crate::types::sessionid::session_id_from_page;
 use crate::types::sessionid::HasSessionID;
 use crate::types::trade_offer_web::TradeOfferAcceptRequest;
+use crate::types::trade_offer_web::TradeOfferAcceptResponse;
 use crate::types::trade_offer_web::TradeOfferCancelResponse;
 use crate::types::trade_offer_web::TradeOfferCommonParameters;
 use crate::types::trade_offer_web::TradeOfferCreateRequest;
 use crate::types::trade_offer_web::TradeOfferCreateResponse;
-use crate::types::trade_offer_web::TradeOfferGenericErrorResponse;
 use crate::types::trade_offer_web::TradeOfferGenericRequest;
 use crate::types::trade_offer_web::TradeOfferParams;
 use crate::types::TradeKind;
@@ -83,9 +134,26 @@ use proxied::{Proxy, ProxifyClient};
 
 mod additional_checks;
 pub mod api_extensions;
+pub mod balancer;
+pub mod batch;
+mod community;
 mod errors;
+pub mod events;
+pub mod friends;
+pub mod gems;
+pub mod guard;
+pub mod history;
+pub mod inventory;
+mod json_file;
+mod keyless;
+pub mod ledger;
+pub mod market;
+pub mod poller;
+#[cfg(test)]
+mod test_fixtures;
 #[cfg(feature = "time")]
 pub mod time;
+pub mod trade_hold;
 mod types;
 
 const TRADEOFFER_BASE: &str = "https://steamcommunity.com/tradeoffer/";
@@ -103,67 +171,257 @@ pub const TRADE_MAX_TRADES_PER_SINGLE_USER: u8 = 5;
 /// Max total sent trade offers.
 pub const TRADE_MAX_ONGOING_TRADES: u8 = 30;
 
+/// Max characters of a trade offer message.
+pub const TRADE_MAX_MESSAGE_LENGTH: usize = 128;
+
 /// Standard delay, in milliseconds
 const STANDARD_DELAY: u64 = 1000;
 
+/// How many times the mobile confirmation of an offer is looked up, before giving up.
+const CONFIRMATION_LOOKUP_ATTEMPTS: u32 = 5;
+
 const MAX_HISTORICAL_CUTOFF: u32 = u32::MAX;
 
 pub(crate) type SteamCompleteAuthenticator = SteamAuthenticator<Authenticated, PresentMaFile>;
 
+/// A [`SteamTradeManager`] that owns its authenticator, so it can be stored in long-lived tasks, moved into spawned
+/// futures and shared across services.
+pub type SharedTradeManager = SteamTradeManager<'static>;
+
+/// Either a borrowed or a shared authenticator.
+#[derive(Debug, Clone)]
+enum AuthenticatorHandle<'a> {
+    Borrowed(&'a SteamCompleteAuthenticator),
+    Shared(Arc<SteamCompleteAuthenticator>),
+}
+
+impl Deref for AuthenticatorHandle<'_> {
+    type Target = SteamCompleteAuthenticator;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Borrowed(authenticator) => authenticator,
+            Self::Shared(authenticator) => authenticator,
+        }
+    }
+}
+
+/// Sends the Web API requests through a transport of the authenticator, so they rotate proxies like its own requests.
 #[derive(Debug)]
+struct WebApiTransport(Arc<dyn HttpTransport>);
+
+impl RequestExecutor for WebApiTransport {
+    fn execute(&self, request: HttpRequest) -> BoxFuture<'_, tappet::Result<HttpResponse>> {
+        self.0
+            .execute(request)
+            .map_err(|e| match e {
+                TransportError::HttpError(e) => SteamAPIError::HttpError(e.without_url()),
+                e => SteamAPIError::SteamHttpError(e.to_string()),
+            })
+            .boxed()
+    }
+}
+
+/// Cloning is cheap, and clones act on the same account: they share the Web API client. Every manager of the same
+/// authenticator takes turns handling mobile confirmations.
+///
+/// Web API calls are made with the API Key cached by the authenticator. Accounts without a key, or whose key is
+/// refused, call it with the access token of the session instead, and if the token is refused too, trade offers are
+/// parsed from the trade offers pages, which only list the active ones. Check [`keyless`].
+///
+/// Only offers created or accepted through the manager are confirmed, and only if they still have the partner and
+/// items they were created with. Check [`guard`].
+#[derive(Debug, Clone)]
 pub struct SteamTradeManager<'a> {
-    authenticator: &'a SteamCompleteAuthenticator,
-    api_client: SteamAPI,
+    authenticator: AuthenticatorHandle<'a>,
+    /// `None` if the account has no API Key.
+    api_client: Option<Arc<SteamAPI>>,
+    /// Offers that may be confirmed, and the ones canceled by this account.
+    guard: Arc<Mutex<OfferGuard>>,
+    /// Receive the offer events of every clone of the manager. Check [`events`].
+    event_sinks: Arc<RwLock<Vec<Arc<dyn EventSink>>>>,
+}
+
+impl SharedTradeManager {
+    /// Returns a new [`SharedTradeManager`], owning a reference to `authenticator`.
+    ///
+    /// The API Key cached by `authenticator` is optional. Check [`SteamTradeManager`].
+    pub fn new_shared(
+        authenticator: Arc<SteamAuthenticator<Authenticated, PresentMaFile>>,
+    ) -> Result<Self, TradeError> {
+        Self::build(AuthenticatorHandle::Shared(authenticator), None)
+    }
+
+    /// Returns a new [`SharedTradeManager`] with a proxy. Check [`Self::new_shared`].
+    ///
+    /// The API Key cached by `authenticator` is optional. Check [`SteamTradeManager`].
+    pub fn new_shared_with_proxy(
+        authenticator: Arc<SteamAuthenticator<Authenticated, PresentMaFile>>,
+        proxy: Option<Proxy>,
+    ) -> Result<Self, TradeError> {
+        Self::build(AuthenticatorHandle::Shared(authenticator), proxy)
+    }
 }
 
 impl<'a> SteamTradeManager<'a> {
     /// Returns a new `[SteamTradeManager]`.
     ///
-    /// # Errors
-    ///
-    /// Returns an error if API Key is not cached by `authenticator`.
+    /// The API Key cached by `authenticator` is optional. Check [`SteamTradeManager`].
     pub fn new(
         authenticator: &'a SteamAuthenticator<Authenticated, PresentMaFile>,
     ) -> Result<SteamTradeManager<'a>, TradeError> {
-        let api_key = authenticator
-            .api_key()
-            .ok_or_else(|| GeneralError("Can't build without an API Key cached.".to_string()))?;
-
-        Ok(Self {
-            authenticator,
-            api_client: SteamAPI::new(api_key),
-        })
+        Self::build(AuthenticatorHandle::Borrowed(authenticator), None)
     }
 
     /// Returns a new `[SteamTradeManager]` with a proxy.
     ///
-    /// # Errors
-    ///
-    /// Returns an error if API Key is not cached by `authenticator`.
+    /// The API Key cached by `authenticator` is optional. Check [`SteamTradeManager`].
     pub fn new_with_proxy(
         authenticator: &'a SteamAuthenticator<Authenticated, PresentMaFile>,
         proxy: Option<Proxy>,
     ) -> Result<SteamTradeManager<'a>, TradeError> {
-        let api_key = authenticator
-            .api_key()
-            .ok_or_else(|| GeneralError("Can't build without an API Key cached.".to_string()))?;
+        Self::build(AuthenticatorHandle::Borrowed(authenticator), proxy)
+    }
+
+    /// Returns a new `[SteamTradeManager]` using the proxy that `pool` assigned to the authenticator account.
+    ///
+    /// Share the same pool given to [`SteamAuthenticator::with_proxy_pool`], so both the authenticator and the Web API
+    /// requests go through the same proxy. Web API requests made with the API Key are sent through a
+    /// [`ProxyPoolTransport`] of the account, so they rotate to another proxy when Steam throttles them, too.
+    ///
+    /// The API Key cached by `authenticator` is optional. Check [`SteamTradeManager`].
+    pub fn new_with_proxy_pool(
+        authenticator: &'a SteamAuthenticator<Authenticated, PresentMaFile>,
+        pool: &ProxyPool,
+    ) -> Result<SteamTradeManager<'a>, TradeError> {
+        let api_client = authenticator.api_key().map(|api_key| {
+            let transport = ProxyPoolTransport::new(pool.clone(), authenticator.account_name());
+            SteamAPI::with_executor(api_key, Arc::new(WebApiTransport(Arc::new(transport))))
+        });
+
+        Self::build_with_api(AuthenticatorHandle::Borrowed(authenticator), api_client)
+    }
+
+    fn build(
+        authenticator: AuthenticatorHandle<'a>,
+        proxy: Option<Proxy>,
+    ) -> Result<SteamTradeManager<'a>, TradeError> {
+        let api_client = authenticator.api_key().map(|api_key| match proxy {
+            None => SteamAPI::new(api_key),
+            proxy => SteamAPI::new_with_proxy(api_key, proxy),
+        });
+        Self::build_with_api(authenticator, api_client)
+    }
+
+    fn build_with_api(
+        authenticator: AuthenticatorHandle<'a>,
+        api_client: Option<SteamAPI>,
+    ) -> Result<SteamTradeManager<'a>, TradeError> {
+        if api_client.is_none() {
+            debug!("No API Key cached. Using the session access token for the Web API instead.");
+        }
 
         Ok(Self {
             authenticator,
-            api_client: SteamAPI::new_with_proxy(api_key, proxy),
+            api_client: api_client.map(Arc::new),
+            guard: Arc::new(Mutex::new(OfferGuard::default())),
+            event_sinks: Arc::new(RwLock::new(vec![])),
         })
     }
 
+    /// Delivers the offer events of this manager, and of its clones, to `sink`. Check [`events`].
+    pub fn add_event_sink(&self, sink: impl EventSink + 'static) {
+        self.event_sinks.write().push(Arc::new(sink));
+    }
+
+    /// Lets an offer that was not created by this manager be confirmed, as long as it still has the partner and items of
+    /// `offer`, such as one created before a restart or by another process. Check [`guard`].
+    pub async fn expect_offer(&self, tradeoffer_id: u64, offer: &TradeOffer) {
+        self.guard
+            .lock()
+            .await
+            .expect(tradeoffer_id, ExpectedOffer::from_offer(offer));
+    }
+
+    /// Confirms every offer, even the ones this manager doesn't know, for this manager and its clones. Check [`guard`].
+    ///
+    /// **Note: This removes the protection against stolen API Keys.**
+    pub async fn disable_offer_guard(&self) {
+        self.guard.lock().await.disable();
+    }
 
     /// Checks whether the user of `tradelink` has recently activated his mobile SteamGuard.
     pub async fn check_steam_guard_recently_activated(&self, tradelink: Tradelink) -> Result<(), TradeError> {
         let Tradelink { partner_id, token, .. } = tradelink;
 
-        check_steam_guard_error(self.authenticator, partner_id, &*token)
+        check_steam_guard_error(&self.authenticator, partner_id, &*token)
             .err_into()
             .await
     }
 
+    /// Returns for how many days the items of a trade with the owner of `tradelink` would be held by Steam.
+    ///
+    /// Asks the Web API first, and falls back to parsing the new trade offer page if the API doesn't answer it.
+    pub async fn get_trade_hold_durations(&self, tradelink: &Tradelink) -> Result<TradeHoldDurations, TradeError> {
+        let Some(api_client) = &self.api_client else {
+            return trade_hold_from_page(&self.authenticator, tradelink).await;
+        };
+
+        let api_response = api_client
+            .get()
+            .IEconService()
+            .GetTradeHoldDurations(tradelink.partner_id.to_steam64(), tradelink.token.clone())
+            .execute_with_response()
+            .await;
+
+        match api_response.map(|r| TradeHoldDurations::from_api(r.response)) {
+            Ok(Some(durations)) => Ok(durations),
+            Ok(None) => {
+                debug!("Web API did not answer the trade hold durations. Falling back to the trade offer page.");
+                trade_hold_from_page(&self.authenticator, tradelink).await
+            }
+            Err(e) => {
+                debug!(
+                    "Failed to fetch trade hold durations: {}. Falling back to the trade offer page.",
+                    e
+                );
+                trade_hold_from_page(&self.authenticator, tradelink).await
+            }
+        }
+    }
+
+    /// Fetches every item of our own inventory for `appid` and `contextid`.
+    ///
+    /// Items that are not tradable are included as well. Check [`InventoryItem::is_tradable`].
+    pub async fn get_inventory(&self, appid: u32, contextid: u32) -> Result<Vec<InventoryItem>, TradeError> {
+        let steamid = self.authenticator.steam_id();
+        fetch_inventory(&self.authenticator, &steamid, appid, contextid).await
+    }
+
+    /// Fetches every item of the inventory of the owner of `tradelink`, for `appid` and `contextid`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InventoryError::Private`] if the partner inventory is not public.
+    pub async fn get_partner_inventory(
+        &self,
+        tradelink: &Tradelink,
+        appid: u32,
+        contextid: u32,
+    ) -> Result<Vec<InventoryItem>, TradeError> {
+        self.get_inventory_of(&tradelink.partner_id, appid, contextid).await
+    }
+
+    /// Fetches every item of the inventory of `steamid`, for `appid` and `contextid`.
+    pub async fn get_inventory_of(
+        &self,
+        steamid: &SteamID,
+        appid: u32,
+        contextid: u32,
+    ) -> Result<Vec<InventoryItem>, TradeError> {
+        fetch_inventory(&self.authenticator, steamid, appid, contextid).await
+    }
+
     /// Call to GetTradeOffers endpoint.
     ///
     /// Convenience function that fetches information about active trades for the current logged in account.
@@ -173,42 +431,227 @@ impl<'a> SteamTradeManager<'a> {
         received: bool,
         active_only: bool,
     ) -> Result<GetTradeOffersResponse, TradeError> {
-        self.api_client
-            .get()
-            .IEconService()
-            .GetTradeOffers(
-                sent,
-                received,
-                MAX_HISTORICAL_CUTOFF,
-                Some(active_only),
-                None,
-                None,
-                None,
-            )
-            .execute_with_response()
-            .err_into()
+        self.fetch_trade_offers(sent, received, MAX_HISTORICAL_CUTOFF, active_only)
             .await
     }
 
-    /// Returns `[GetTradeHistoryResponse]` after a call to `GetTradeHistory` endpoint.
-    /// `max_trades` If not set will default to a maximum of 500 trade offers.
+    /// Call to GetTradeOffers endpoint.
+    ///
+    /// Fetches both sent and received offers that are either active, or were updated after `time_historical_cutoff`,
+    /// a unix timestamp.
+    pub async fn get_trade_offers_updated_since(
+        &self,
+        time_historical_cutoff: u32,
+    ) -> Result<GetTradeOffersResponse, TradeError> {
+        self.fetch_trade_offers(true, true, time_historical_cutoff, true).await
+    }
+
+    /// Calls GetTradeOffers with the API Key, then with the access token, and lastly parses the trade offers pages.
+    ///
+    /// The pages only list active offers, so asking them for inactive offers, or for the ones updated since a time,
+    /// fails with [`OfferError::ActiveOffersOnly`].
+    async fn fetch_trade_offers(
+        &self,
+        sent: bool,
+        received: bool,
+        time_historical_cutoff: u32,
+        active_only: bool,
+    ) -> Result<GetTradeOffersResponse, TradeError> {
+        if let Some(api_client) = &self.api_client {
+            let api_response = api_client
+                .get()
+                .IEconService()
+                .GetTradeOffers(
+                    sent,
+                    received,
+                    time_historical_cutoff,
+                    Some(active_only),
+                    None,
+                    None,
+                    None,
+                )
+                .execute_with_response()
+                .await;
+
+            match api_response {
+                Ok(response) => return Ok(response),
+                Err(e) if keyless::is_unauthorized(&e) => {
+                    warn!("GetTradeOffers refused the API Key: {}. Trying the access token.", e)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let parameters = [
+            ("get_sent_offers", sent.to_string()),
+            ("get_received_offers", received.to_string()),
+            ("time_historical_cutoff", time_historical_cutoff.to_string()),
+            ("active_only", active_only.to_string()),
+        ];
+        match keyless::call_with_access_token(&self.authenticator, "GetTradeOffers", &parameters).await {
+            Err(TradeError::SteamAPIError(e)) if keyless::is_unauthorized(&e) => {
+                if !active_only || time_historical_cutoff != MAX_HISTORICAL_CUTOFF {
+                    return Err(OfferError::ActiveOffersOnly.into());
+                }
+                warn!(
+                    "GetTradeOffers refused the access token: {}. Parsing the trade offers pages.",
+                    e
+                );
+                keyless::offers_from_pages(&self.authenticator, sent, received).await
+            }
+            result => result,
+        }
+    }
+
+    /// Streams the complete trade history, newest first, following the `GetTradeHistory` cursor until the end.
     ///
     /// Contains Information about completed trades and recovery of the new asset ids that were generated after the
     /// trade.
-    async fn get_trade_offers_history(
+    pub fn trade_history(
         &self,
-        max_trades: Option<u32>,
-        include_failed: bool,
-    ) -> Result<GetTradeHistoryResponse, TradeError> {
-        let max_trades = max_trades.unwrap_or(500);
+        options: TradeHistoryOptions,
+    ) -> impl Stream<Item = Result<TradeHistory_Trade, TradeError>> + '_ {
+        history::history_trades(self, options)
+    }
 
-        self.api_client
-            .get()
-            .IEconService()
-            .GetTradeHistory(max_trades, include_failed, false, None, None, None, None, None)
-            .execute_with_response()
-            .err_into()
-            .await
+    /// Same as [`Self::trade_history`], but yields whole pages, along with the item descriptions if requested.
+    pub fn trade_history_pages(
+        &self,
+        options: TradeHistoryOptions,
+    ) -> impl Stream<Item = Result<TradeHistoryPage, TradeError>> + '_ {
+        history::history_pages(self, options)
+    }
+
+    /// Community Market client on the session of this account. Check [`market`].
+    pub fn market(&self) -> Market<'a> {
+        Market::new(self.clone())
+    }
+
+    /// Gems, booster pack and badge crafting client on the session of this account. Check [`gems`].
+    pub fn gems(&self) -> Gems<'a> {
+        Gems::new(self.clone())
+    }
+
+    /// Relationship of this account with every account of its friends list, including pending friend requests, by
+    /// SteamID64.
+    pub async fn friend_relationships(&self) -> Result<HashMap<u64, EFriendRelationship>, TradeError> {
+        friends::relationships(self).await
+    }
+
+    /// Tradelink without a token of a friend of this account, to send offers with. Check [`friends`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OfferError::NotFriend`] if `steamid` is not a friend of this account.
+    pub async fn friend_tradelink(&self, steamid: &SteamID) -> Result<Tradelink, TradeError> {
+        let steam64 = steamid.to_steam64();
+        match self.friend_relationships().await?.get(&steam64) {
+            Some(EFriendRelationship::Friend) => Ok(Tradelink::friend(steamid.clone())),
+            _ => Err(OfferError::NotFriend(steam64).into()),
+        }
+    }
+
+    /// Sends a friend request to `steamid`.
+    pub async fn send_friend_request(&self, steamid: &SteamID) -> Result<(), TradeError> {
+        friends::add_friend(self, steamid.to_steam64(), false).await
+    }
+
+    /// Accepts the pending friend request of `steamid`.
+    pub async fn accept_friend_request(&self, steamid: &SteamID) -> Result<(), TradeError> {
+        friends::add_friend(self, steamid.to_steam64(), true).await
+    }
+
+    /// Accepts every pending friend request sent by the SteamID64s of `allowed`, such as other bots of the same owner.
+    /// Requests of anyone else are left pending.
+    ///
+    /// Returns the SteamID64s of the accepted requests.
+    pub async fn accept_friend_requests(&self, allowed: &HashSet<u64>) -> Result<Vec<u64>, TradeError> {
+        let pending = self
+            .friend_relationships()
+            .await?
+            .into_iter()
+            .filter(|(steamid, relationship)| {
+                *relationship == EFriendRelationship::RequestRecipient && allowed.contains(steamid)
+            })
+            .map(|(steamid, _)| steamid)
+            .collect::<Vec<_>>();
+
+        for (index, steamid) in pending.iter().enumerate() {
+            if index > 0 {
+                Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+            }
+            friends::add_friend(self, *steamid, true).await?;
+        }
+        Ok(pending)
+    }
+
+    /// Makes this account and the one of `other` friends, sending and accepting the friend request as needed.
+    ///
+    /// Returns the tradelink without a token of `other`, to send offers with.
+    pub async fn befriend(&self, other: &SteamTradeManager<'_>) -> Result<Tradelink, TradeError> {
+        let my_steamid = self.authenticator.steam_id();
+        let their_steamid = other.authenticator.steam_id();
+
+        match self.friend_relationships().await?.get(&their_steamid.to_steam64()) {
+            Some(EFriendRelationship::Friend) => {}
+            Some(EFriendRelationship::RequestRecipient) => self.accept_friend_request(&their_steamid).await?,
+            relationship => {
+                // a request sent before is still pending
+                if relationship != Some(&EFriendRelationship::RequestInitiator) {
+                    self.send_friend_request(&their_steamid).await?;
+                    Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+                }
+                other.accept_friend_request(&my_steamid).await?;
+            }
+        }
+        Ok(Tradelink::friend(their_steamid))
+    }
+
+    /// Call to GetTradeOffer endpoint.
+    ///
+    /// Returns a single trade offer, either sent or received, including the ones that are no longer active.
+    ///
+    /// If both the API Key and the access token are refused, only active offers are found, and without their items.
+    pub async fn get_trade_offer(&self, tradeoffer_id: u64) -> Result<TradeOffer_Trade, TradeError> {
+        if let Some(api_client) = &self.api_client {
+            let api_response = api_client
+                .get()
+                .IEconService()
+                .GetTradeOffer(tradeoffer_id as i64, None)
+                .execute_with_response()
+                .await;
+
+            match api_response {
+                Ok(response) => return Ok(response.response.offer),
+                Err(e) if keyless::is_unauthorized(&e) => {
+                    warn!("GetTradeOffer refused the API Key: {}. Trying the access token.", e)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let parameters = [("tradeofferid", tradeoffer_id.to_string())];
+        match keyless::call_with_access_token::<GetTradeOfferResponse>(
+            &self.authenticator,
+            "GetTradeOffer",
+            &parameters,
+        )
+        .await
+        {
+            Ok(response) => Ok(response.response.offer),
+            Err(TradeError::SteamAPIError(e)) if keyless::is_unauthorized(&e) => {
+                warn!(
+                    "GetTradeOffer refused the access token: {}. Parsing the trade offers pages.",
+                    e
+                );
+                keyless::offers_from_pages(&self.authenticator, true, true)
+                    .await?
+                    .filter_by(|offer| offer.tradeofferid == tradeoffer_id)
+                    .pop()
+                    .ok_or_else(|| OfferError::NoMatch.into())
+            }
+            Err(e) => Err(e),
+        }
     }
 
     /// Returns a single trade offer.
@@ -220,13 +663,14 @@ impl<'a> SteamTradeManager<'a> {
 
     /// Returns the new asset ids for a trade of `tradeid`.
     ///
-    /// Convenience function that internally calls `get_trade_offers_history` but filters it directly.
+    /// Convenience function that pages through [`Self::trade_history`] until the trade is found.
+    ///
+    /// Fails with [`OfferError::TradeNotFound`] if the trade is not on the history.
     pub async fn get_new_assetids(&self, tradeid: i64) -> Result<Vec<i64>, TradeError> {
-        let found_trade: TradeHistory_Trade = self
-            .get_trade_offers_history(None, false)
-            .map_ok(|tradeoffers| tradeoffers.filter_by(|trade| trade.tradeid == tradeid))
-            .await?
-            .swap_remove(0);
+        let mut trades = pin!(self
+            .trade_history(TradeHistoryOptions::default())
+            .try_filter(|trade| future::ready(trade.tradeid == tradeid)));
+        let found_trade: TradeHistory_Trade = trades.try_next().await?.ok_or(OfferError::TradeNotFound(tradeid))?;
 
         Ok(found_trade
             .every_asset()
@@ -273,48 +717,302 @@ impl<'a> SteamTradeManager<'a> {
 
     /// Creates a new trade offer, and confirms it with the inner [`SteamAuthenticator`].
     ///
-    /// Returns the `tradeoffer_id` on success and if the confirmation was not found but the trade created.
+    /// Returns the `tradeoffer_id` on success. If the offer was created but its confirmation could not be found, fails
+    /// with [`ConfirmationError::NotFoundButTradeCreated`].
     pub async fn create_offer_and_confirm(&self, tradeoffer: TradeOffer) -> Result<u64, TradeError> {
-        let tradeoffer_id = self.create_offer(tradeoffer).await?;
-        Delay::new(Duration::from_millis(STANDARD_DELAY));
-        self.accept_offer(tradeoffer_id).await?;
+        let response = self.send_offer(tradeoffer).await?;
+        let tradeoffer_id = Self::created_offer_id(&response)?;
+
+        if response.needs_mobile_confirmation == Some(true) {
+            self.confirm_offer(tradeoffer_id).await.map_err(|e| match e {
+                TradeError::ConfirmationError(ConfirmationError::NotFound) => {
+                    ConfirmationError::NotFoundButTradeCreated(tradeoffer_id).into()
+                }
+                e => e,
+            })?;
+        }
         Ok(tradeoffer_id)
     }
 
     /// Creates a new trade offer and return its `tradeoffer_id`.
+    ///
+    /// If [`TradeOffer::refuse_trade_hold`] is set, fails with [`OfferError::TradeHold`] without sending the offer
+    /// if its items would be held.
     pub async fn create_offer(&self, tradeoffer: TradeOffer) -> Result<u64, TradeError> {
-        self.request::<TradeOfferCreateResponse>(TradeKind::Create(tradeoffer), None)
-            .map_ok(|c| {
-                c.tradeofferid
-                    .and_then(|id| u64::from_str(&id).ok())
-                    .expect("Safe to unwrap.")
+        let response = self.send_offer(tradeoffer).await?;
+        Self::created_offer_id(&response)
+    }
+
+    async fn send_offer(&self, tradeoffer: TradeOffer) -> Result<TradeOfferCreateResponse, TradeError> {
+        if tradeoffer.refuse_trade_hold {
+            self.get_trade_hold_durations(&tradeoffer.their_tradelink)
+                .await?
+                .ensure_not_held()?;
+        }
+
+        let expected = ExpectedOffer::from_offer(&tradeoffer);
+        let partner = tradeoffer.their_tradelink.partner_id.to_steam64();
+        let response = self
+            .request::<TradeOfferCreateResponse>(TradeKind::Create(tradeoffer), None)
+            .await?;
+        if let Ok(tradeoffer_id) = Self::created_offer_id(&response) {
+            self.guard.lock().await.expect(tradeoffer_id, expected);
+            self.emit(OfferEventKind::Created, tradeoffer_id, Some(partner)).await;
+        }
+        Ok(response)
+    }
+
+    fn created_offer_id(response: &TradeOfferCreateResponse) -> Result<u64, TradeError> {
+        response
+            .tradeofferid
+            .as_deref()
+            .and_then(|id| u64::from_str(id).ok())
+            .ok_or_else(|| {
+                OfferError::GeneralFailure("Steam did not return the new trade offer id.".to_string()).into()
             })
-            .await
     }
 
     /// Accepts a trade offer made to this account and confirms it with inner [SteamAuthenticator]
     ///
+    /// Returns whether the items were exchanged, are held by Steam, or if Steam is still waiting for a confirmation
+    /// that can't be done by this library, such as an email one.
+    ///
     /// **Note: This is irreversable, be extra careful when accepting any trade offer.**
-    pub async fn accept_offer(&self, tradeoffer_id: u64) -> Result<(), TradeError> {
-        let resp: TradeOfferCreateResponse = self.request(TradeKind::Accept, Some(tradeoffer_id)).await?;
+    pub async fn accept_offer(&self, tradeoffer_id: u64) -> Result<AcceptOutcome, TradeError> {
+        let response: TradeOfferAcceptResponse = self.request(TradeKind::Accept, Some(tradeoffer_id)).await?;
+
+        if response.needs_email_confirmation == Some(true) {
+            self.guard.lock().await.await_acceptance(tradeoffer_id);
+            return Ok(AcceptOutcome::PendingConfirmation {
+                email_domain: response.email_domain,
+            });
+        }
 
-        if resp.needs_mobile_confirmation.is_none() && !resp.needs_mobile_confirmation.unwrap() {
-            return Ok(());
+        if response.needs_mobile_confirmation == Some(true) {
+            self.confirm_offer(tradeoffer_id).await?;
         }
 
-        let confirmation = self
-            .authenticator
-            .fetch_confirmations()
-            .inspect_ok(|_| debug!("Confirmations fetched successfully."))
-            .await?
-            .into_iter()
-            .find(|c| c.has_trade_offer_id(tradeoffer_id))
-            .ok_or_else(|| TradeError::from(ConfirmationError::NotFound))?;
+        let offer = self.get_trade_offer(tradeoffer_id).await?;
+        let partner = partner_of(&offer);
+        let outcome = AcceptOutcome::from_offer(offer, response.tradeid)?;
+
+        match outcome {
+            AcceptOutcome::Completed { .. } => self.emit(OfferEventKind::Accepted, tradeoffer_id, Some(partner)).await,
+            AcceptOutcome::InEscrow { .. } => self.emit(OfferEventKind::Escrowed, tradeoffer_id, Some(partner)).await,
+            // reported by a poller of the manager once Steam completes it
+            AcceptOutcome::PendingConfirmation { .. } => self.guard.lock().await.await_acceptance(tradeoffer_id),
+        }
+        Ok(outcome)
+    }
+
+    /// Sends every offer of `batch`, without exceeding [`TRADE_MAX_ONGOING_TRADES`] and
+    /// [`TRADE_MAX_TRADES_PER_SINGLE_USER`], counting the sent offers that are already active.
+    ///
+    /// Offers are spaced by [`STANDARD_DELAY`], and the ones that need a mobile confirmation are confirmed together once
+    /// every offer was sent. Returns the outcome of each offer, in the order of the batch. Only fails if the active
+    /// offers could not be fetched.
+    pub async fn send_batch(&self, batch: OfferBatch) -> Result<Vec<OfferOutcome>, TradeError> {
+        let active_offers = self.get_trade_offers(true, false, true).await?.filter_by(|_| true);
+        let mut ongoing = OngoingOffers::from_offers(&active_offers);
+
+        let mut outcomes = Vec::with_capacity(batch.len());
+        let mut needs_confirmation = vec![];
+        let mut first_offer = true;
+
+        for offer in batch {
+            let partner = offer.their_tradelink.partner_id.to_steam64();
+            if let Err(limit) = ongoing.reserve(partner) {
+                debug!("Offer to {} skipped, limit reached: {:?}", partner, limit);
+                outcomes.push(OfferOutcome::Skipped(limit));
+                continue;
+            }
+
+            if !first_offer {
+                Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+            }
+            first_offer = false;
+
+            let sent = self.send_offer(offer).await.and_then(|response| {
+                Self::created_offer_id(&response).map(|id| (id, response.needs_mobile_confirmation == Some(true)))
+            });
+            let outcome = match sent {
+                Ok((tradeoffer_id, true)) => {
+                    needs_confirmation.push(tradeoffer_id);
+                    OfferOutcome::Unconfirmed { tradeoffer_id }
+                }
+                Ok((tradeoffer_id, false)) => OfferOutcome::Sent { tradeoffer_id },
+                Err(e) => {
+                    ongoing.release(partner);
+                    OfferOutcome::Failed(e)
+                }
+            };
+            outcomes.push(outcome);
+        }
+
+        match self.confirm_offers(&needs_confirmation).await {
+            Ok((confirmed, refused)) => outcomes.iter_mut().for_each(|outcome| {
+                if let OfferOutcome::Unconfirmed { tradeoffer_id } = *outcome {
+                    if confirmed.contains(&tradeoffer_id) {
+                        *outcome = OfferOutcome::Sent { tradeoffer_id };
+                    } else if let Some(violation) = refused.iter().find(|v| v.tradeoffer_id() == tradeoffer_id) {
+                        *outcome = OfferOutcome::Failed(ConfirmationError::Refused(*violation).into());
+                    }
+                }
+            }),
+            Err(e) => warn!("Could not confirm the offers of the batch: {}", e),
+        }
+
+        Ok(outcomes)
+    }
+
+    /// Finds the mobile confirmation of `tradeoffer_id` and accepts it.
+    async fn confirm_offer(&self, tradeoffer_id: u64) -> Result<(), TradeError> {
+        let (confirmed, refused) = self.confirm_offers(&[tradeoffer_id]).await?;
+        if let Some(violation) = refused.into_iter().next() {
+            return Err(ConfirmationError::Refused(violation).into());
+        }
+        if confirmed.is_empty() {
+            return Err(ConfirmationError::NotFound.into());
+        }
+        Ok(())
+    }
+
+    /// Finds the mobile confirmations of `tradeoffer_ids` and accepts them together.
+    ///
+    /// Steam may take a few moments to generate the confirmations, so the lookup is retried with an increasing delay
+    /// until every one is found. Offers that don't match what was created or accepted are refused by the guard.
+    ///
+    /// Returns the ids that were confirmed, and the refused ones.
+    async fn confirm_offers(&self, tradeoffer_ids: &[u64]) -> Result<(Vec<u64>, Vec<GuardViolation>), TradeError> {
+        if tradeoffer_ids.is_empty() {
+            return Ok((vec![], vec![]));
+        }
+
+        let _guard = self.authenticator.confirmation_lock().lock().await;
+        let confirmations = self
+            .lookup_confirmations(tradeoffer_ids.len(), |c| {
+                c.trade_offer_id().is_some_and(|id| tradeoffer_ids.contains(&id))
+            })
+            .await?;
+
+        let mut verified = Vec::with_capacity(confirmations.len());
+        let mut partners = Vec::with_capacity(confirmations.len());
+        let mut refused = vec![];
+        for confirmation in confirmations {
+            // safe to unwrap, only confirmations of trade offers were kept
+            let tradeoffer_id = confirmation.trade_offer_id().unwrap();
+            let offer = self.get_trade_offer(tradeoffer_id).await?;
+
+            match self.guard.lock().await.verify(&offer) {
+                Ok(()) => {
+                    verified.push(confirmation);
+                    partners.push(partner_of(&offer));
+                }
+                Err(violation) => {
+                    warn!("Refusing to confirm trade offer {}: {}", tradeoffer_id, violation);
+                    refused.push(violation);
+                }
+            }
+        }
+
+        if verified.is_empty() {
+            return Ok((vec![], refused));
+        }
 
+        let confirmed = verified.iter().filter_map(|c| c.trade_offer_id()).collect::<Vec<_>>();
         self.authenticator
-            .process_confirmations(ConfirmationAction::Accept, std::iter::once(confirmation))
-            .err_into()
-            .await
+            .process_confirmations(ConfirmationAction::Accept, verified)
+            .err_into::<TradeError>()
+            .await?;
+
+        for (tradeoffer_id, partner) in confirmed.iter().zip(partners) {
+            self.emit(OfferEventKind::Confirmed, *tradeoffer_id, Some(partner))
+                .await;
+        }
+        Ok((confirmed, refused))
+    }
+
+    /// Fetches the mobile confirmations that pass `filter`.
+    ///
+    /// Steam may take a few moments to generate the confirmations, so the lookup is retried with an increasing delay
+    /// until `expected` are found. Must be called while holding the confirmation lock of the authenticator.
+    async fn lookup_confirmations<F>(&self, expected: usize, filter: F) -> Result<Vec<Confirmation>, TradeError>
+    where
+        F: Fn(&Confirmation) -> bool,
+    {
+        let mut delay = Duration::from_millis(STANDARD_DELAY);
+        let mut confirmations = vec![];
+
+        for attempt in 1..=CONFIRMATION_LOOKUP_ATTEMPTS {
+            confirmations = self
+                .authenticator
+                .fetch_confirmations()
+                .inspect_ok(|_| debug!("Confirmations fetched successfully."))
+                .await?
+                .into_iter()
+                .filter(|c| filter(c))
+                .collect::<Vec<_>>();
+
+            if confirmations.len() >= expected {
+                break;
+            }
+
+            if attempt < CONFIRMATION_LOOKUP_ATTEMPTS {
+                debug!(
+                    "Found {} of {} confirmations. Retrying in {:?}.",
+                    confirmations.len(),
+                    expected,
+                    delay
+                );
+                Delay::new(delay).await;
+                delay *= 2;
+            }
+        }
+        Ok(confirmations)
+    }
+
+    /// Counters a trade offer sent to this account, replacing it with a new offer with `my_assets` and `their_assets`.
+    ///
+    /// Returns the `tradeoffer_id` of the new offer. Like [`Self::create_offer`], it needs to be confirmed if we are
+    /// giving away any items, and the countered offer changes to [`ETradeOfferState::Countered`].
+    pub async fn counter_offer<MA, TA, S>(
+        &self,
+        tradeoffer_id: u64,
+        my_assets: MA,
+        their_assets: TA,
+        message: S,
+    ) -> Result<u64, TradeError>
+    where
+        MA: Into<Option<AssetCollection>>,
+        TA: Into<Option<AssetCollection>>,
+        S: Into<Option<String>>,
+    {
+        let my_assets = my_assets.into();
+        let their_assets = their_assets.into();
+        TradeOffer::validate(&my_assets, &their_assets)?;
+
+        let offer = self.get_trade_offer(tradeoffer_id).await?;
+        if offer.is_our_offer || offer.state != ETradeOfferState::Active {
+            return Err(OfferError::InvalidState.into());
+        }
+        let partner_id = partner_of(&offer);
+        let expected = ExpectedOffer::new(partner_id, my_assets.as_ref(), their_assets.as_ref());
+
+        let request = TradeOfferCreateRequest::counter(
+            partner_id,
+            tradeoffer_id,
+            my_assets,
+            their_assets,
+            message.into().unwrap_or_default(),
+        );
+        let response = self
+            .request::<TradeOfferCreateResponse>(TradeKind::Counter(request), Some(tradeoffer_id))
+            .await?;
+        let counter_offer_id = Self::created_offer_id(&response)?;
+        self.guard.lock().await.expect(counter_offer_id, expected);
+        self.emit(OfferEventKind::Created, counter_offer_id, Some(partner_id))
+            .await;
+        Ok(counter_offer_id)
     }
 
     /// Denies a trade offer sent to this account.
@@ -324,8 +1022,9 @@ impl<'a> SteamTradeManager<'a> {
     /// Will error if couldn't deny the trade offer.
     pub async fn deny_offer(&self, tradeoffer_id: u64) -> Result<(), TradeError> {
         self.request::<TradeOfferCancelResponse>(TradeKind::Decline, Some(tradeoffer_id))
-            .await
-            .map(|_| ())
+            .await?;
+        self.emit(OfferEventKind::Declined, tradeoffer_id, None).await;
+        Ok(())
     }
 
     /// Cancel a trade offer sent by this account.
@@ -334,12 +1033,17 @@ impl<'a> SteamTradeManager<'a> {
     ///
     /// Will error if couldn't cancel the tradeoffer.
     pub async fn cancel_offer(&self, tradeoffer_id: u64) -> Result<(), TradeError> {
+        self.guard.lock().await.cancel(tradeoffer_id);
         self.request::<TradeOfferCancelResponse>(TradeKind::Cancel, Some(tradeoffer_id))
-            .await
-            .map(|_| ())
+            .await?;
+        self.emit(OfferEventKind::Canceled, tradeoffer_id, None).await;
+        Ok(())
     }
 
     /// Check current session health, injects SessionID cookie, and send the request.
+    ///
+    /// Failures are classified into [`OfferError`]. If Steam refuses the session, the sessionid is refreshed and the
+    /// request is sent once more.
     async fn request<OUTPUT>(&self, operation: TradeKind, tradeoffer_id: Option<u64>) -> Result<OUTPUT, TradeError>
     where
         OUTPUT: DeserializeOwned + Send + Sync,
@@ -352,17 +1056,20 @@ impl<'a> SteamTradeManager<'a> {
         match &operation {
             TradeKind::Create(offer) => {
                 header.replace(HeaderMap::new());
-                header
-                    .as_mut()
-                    .unwrap()
-                    .insert("Referer", (TRADEOFFER_BASE.to_owned() + "new").parse().unwrap());
+                let referer = new_offer_page(&offer.their_tradelink.partner_id, &offer.their_tradelink.token);
+                header.as_mut().unwrap().insert(
+                    "Referer",
+                    referer
+                        .parse()
+                        .unwrap_or_else(|_| (TRADEOFFER_BASE.to_owned() + "new").parse().unwrap()),
+                );
 
                 partner_id_and_token = Some((
                     offer.their_tradelink.partner_id.clone(),
                     offer.their_tradelink.token.clone(),
                 ));
             }
-            TradeKind::Accept => {
+            TradeKind::Accept | TradeKind::Counter(_) => {
                 header.replace(HeaderMap::new());
                 header.as_mut().unwrap().insert(
                     "Referer",
@@ -376,13 +1083,15 @@ impl<'a> SteamTradeManager<'a> {
 
         let mut request: Box<dyn HasSessionID> = match operation {
             TradeKind::Accept => {
-                let partner_id = self
-                    .get_tradeoffer_by_id(tradeoffer_id.unwrap())
-                    .await?
-                    .first()
-                    .map(|c| SteamID::from_steam3(c.tradeofferid as u32, None, None))
-                    .map(|steamid| steamid.to_steam64())
-                    .ok_or(OfferError::NoMatch)?;
+                let offer = self.get_trade_offer(tradeoffer_id.unwrap()).await?;
+                if offer.is_our_offer || offer.state != ETradeOfferState::Active {
+                    return Err(OfferError::InvalidState.into());
+                }
+                let partner_id = partner_of(&offer);
+                self.guard
+                    .lock()
+                    .await
+                    .expect(offer.tradeofferid, ExpectedOffer::from_trade(&offer));
 
                 let trade_request_data = TradeOfferAcceptRequest {
                     common: TradeOfferCommonParameters {
@@ -399,60 +1108,131 @@ impl<'a> SteamTradeManager<'a> {
 
             TradeKind::Cancel | TradeKind::Decline => Box::<TradeOfferGenericRequest>::default(),
             TradeKind::Create(offer) => Box::new(Self::prepare_offer(offer)?),
+            TradeKind::Counter(request) => Box::new(request),
         };
 
-        // TODO: Check if session is ok, then inject cookie
-        let session_id_cookie = self
-            .authenticator
-            .dump_cookie(STEAM_COMMUNITY_HOST, "sessionid")
-            .ok_or_else(|| {
-                GeneralError("Somehow you don't have a sessionid cookie. You need to login first.".to_string())
-            })?;
+        let mut session_refreshed = false;
+        loop {
+            request.set_sessionid(self.session_id()?);
+
+            let response = self
+                .authenticator
+                .request_custom_endpoint(
+                    tradeoffer_endpoint.clone(),
+                    Method::POST,
+                    header.clone(),
+                    Some(&*request),
+                )
+                .err_into::<InternalError>()
+                .await?;
+            let status = response.status().as_u16();
+            let response_text = response.text().err_into::<InternalError>().await?;
+
+            // every field of the successful responses is optional, so JSON errors would parse as one of them
+            let error = match offer_error_from_json(&response_text) {
+                Some(error) => Some(error),
+                None => match serde_json::from_str::<OUTPUT>(&response_text) {
+                    Ok(response) => return Ok(response),
+                    Err(_) => offer_error_from_response(status, &response_text),
+                },
+            };
+
+            let error = match error {
+                Some(OfferError::SessionExpired) if !session_refreshed => {
+                    warn!("Steam refused the session. Refreshing the sessionid and trying once more.");
+                    self.refresh_session_id().await?;
+                    session_refreshed = true;
+                    continue;
+                }
+                Some(error) => error,
+                None => {
+                    // offers to accounts that can't trade are answered with a bare error page, while other pages are
+                    // Steam failing on its own
+                    let is_page = status == 200 && response_text.trim_start().starts_with('<');
+                    if let Some((steamid, token)) = partner_id_and_token.as_ref().filter(|_| is_page) {
+                        check_steam_guard_error(&self.authenticator, steamid.clone(), token).await?;
+                    }
 
-        request.set_sessionid(session_id_cookie);
-        let request: Arc<dyn HasSessionID> = Arc::from(request);
+                    tracing::error!(
+                        "Failure to deserialize a valid response Steam Offer response. Status: {}. Maybe Steam \
+                         Servers are offline.",
+                        status
+                    );
+                    OfferError::GeneralFailure(format!("Steam Response ({}): {}", status, response_text))
+                }
+            };
+            return Err(error.into());
+        }
+    }
 
+    /// Replaces the `sessionid` cookie with the one Steam Community currently expects, for when forms are refused with
+    /// a stale one.
+    ///
+    /// Fails with [`OfferError::SessionExpired`] if the community session itself is gone.
+    async fn refresh_session_id(&self) -> Result<(), TradeError> {
         let response = self
             .authenticator
-            .request_custom_endpoint(tradeoffer_endpoint, Method::POST, header, Some(request))
+            .request_custom_endpoint(
+                format!("https://{}/", STEAM_COMMUNITY_HOST),
+                Method::GET,
+                None,
+                None::<&u8>,
+            )
             .err_into::<InternalError>()
             .await?;
-        let response_text = response.text().err_into::<InternalError>().await?;
-
-        match serde_json::from_str::<OUTPUT>(&response_text) {
-            Ok(response) => Ok(response),
-            Err(_) => {
-                // try to match into a generic message
-                if let Ok(resp) = serde_json::from_str::<TradeOfferGenericErrorResponse>(&response_text) {
-                    if resp.error_message.is_some() {
-                        let err_msg = resp.error_message.unwrap();
-                        Err(error_from_strmessage(&*err_msg).unwrap().into())
-                    } else if resp.eresult.is_some() {
-                        let eresult = resp.eresult.unwrap();
-                        Err(tradeoffer_error_from_eresult(eresult).into())
-                    } else {
-                        tracing::error!("Unable to understand Steam Response. Please report it as bug.");
-                        Err(OfferError::GeneralFailure(format!(
-                            "Steam Response: {}\nThis is a bug, please report it.",
-                            response_text
-                        ))
-                        .into())
-                    }
-                } else {
-                    if let Some((steamid, token)) = partner_id_and_token {
-                        check_steam_guard_error(self.authenticator, steamid, &token).await?;
-                    }
+        let page = response.text().err_into::<InternalError>().await?;
 
-                    tracing::error!(
-                        "Failure to deserialize a valid response Steam Offer response. Maybe Steam Servers are \
-                         offline."
-                    );
-                    Err(OfferError::GeneralFailure(format!("Steam Response: {}", response_text)).into())
-                }
+        if page.contains("g_steamID = false") {
+            return Err(OfferError::SessionExpired.into());
+        }
+        let sessionid = session_id_from_page(&page).ok_or(OfferError::SessionExpired)?;
+
+        if self.session_id().ok().as_deref() != Some(sessionid) {
+            self.authenticator.import_cookies([ExportedCookie {
+                name: "sessionid".to_string(),
+                value: sessionid.to_string(),
+                domain: STEAM_COMMUNITY_HOST.to_string(),
+                path: "/".to_string(),
+                expires: None,
+                secure: true,
+                http_only: false,
+            }]);
+        }
+        debug!("Community sessionid refreshed.");
+        Ok(())
+    }
+
+    /// Delivers an offer event to every sink, logging the ones that fail.
+    async fn emit(&self, kind: OfferEventKind, tradeoffer_id: u64, partner: Option<u64>) {
+        let sinks = self.event_sinks.read().clone();
+        if sinks.is_empty() {
+            return;
+        }
+
+        let event = OfferEvent::new(kind, tradeoffer_id, partner, self.authenticator.steam_id().to_steam64());
+        for sink in sinks {
+            let delivery = match future::select(sink.deliver(&event), Delay::new(SINK_TIMEOUT)).await {
+                Either::Left((delivered, _)) => delivered,
+                Either::Right(_) => Err(EventSinkError::Timeout(SINK_TIMEOUT)),
+            };
+            if let Err(e) = delivery {
+                warn!(
+                    "Could not deliver {:?} of trade offer {} to {:?}: {}",
+                    kind, tradeoffer_id, sink, e
+                );
             }
         }
     }
 
+    /// The `sessionid` cookie of the community session, sent along every form.
+    fn session_id(&self) -> Result<String, TradeError> {
+        self.authenticator
+            .dump_cookie(STEAM_COMMUNITY_HOST, "sessionid")
+            .ok_or_else(|| {
+                GeneralError("Somehow you don't have a sessionid cookie. You need to login first.".to_string())
+            })
+    }
+
     /// Checks that the tradeoffer is valid, and process it, getting the trade token and steamid3, into a
     /// `TradeOfferCreateRequest`, ready to send it.
     fn prepare_offer(tradeoffer: TradeOffer) -> Result<TradeOfferCreateRequest, TradeError> {
@@ -461,8 +1241,9 @@ impl<'a> SteamTradeManager<'a> {
         let tradelink = tradeoffer.their_tradelink.clone();
 
         let their_steamid64 = tradelink.partner_id.to_steam64();
+        // offers to friends are sent without a token
         let trade_offer_params = TradeOfferParams {
-            trade_offer_access_token: tradelink.token,
+            trade_offer_access_token: Some(tradelink.token).filter(|token| !token.is_empty()),
         };
 
         Ok(TradeOfferCreateRequest::new(
@@ -476,6 +1257,13 @@ impl<'a> SteamTradeManager<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::balancer::BalancePlan;
+    use crate::balancer::Balancer;
+    use crate::ledger::AssetLedger;
+    use steam_mobile::transport::CannedTransport;
+    use steam_mobile::user::SteamUser;
+    use steam_mobile::MobileAuthFile;
+    use tappet::response_types::GetTradeHistoryResponse;
 
     fn get_tradeoffer_url_with_token() -> &'static str {
         "https://steamcommunity.com/tradeoffer/new/?partner=79925588&token=Ob27qXzn"
@@ -719,6 +1507,115 @@ mod tests {
         serde_json::from_str::<GetTradeHistoryResponse>(&response).unwrap()
     }
 
+    #[test]
+    fn shared_manager_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + Clone + 'static>() {}
+        assert_send_sync::<SharedTradeManager>();
+    }
+
+    /// Never called: only checks that the futures can be spawned on a multithreaded runtime.
+    fn futures_are_send(
+        manager: &SharedTradeManager,
+        balancer: &Balancer<'_, 'static>,
+        plan: BalancePlan,
+        ledger: &mut AssetLedger,
+    ) {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(manager.get_new_assetids(1));
+        assert_send(balancer.execute(plan, ledger));
+    }
+
+    /// Authenticator of a resumed session, whose requests are all answered with `status` and `body`.
+    fn canned_authenticator(status: u16, body: &str) -> SteamCompleteAuthenticator {
+        let user = SteamUser::new("bot".to_string(), String::new()).with_mafile(MobileAuthFile::new(
+            String::new(),
+            String::new(),
+            None::<String>,
+        ));
+        let authenticator = SteamAuthenticator::with_transport(user, Arc::new(CannedTransport::new(status, body)))
+            .resume_session(SteamID::from_steam64(76561198040191316), String::new());
+        authenticator.import_cookies([ExportedCookie {
+            name: "sessionid".to_string(),
+            value: "a1b2c3d4e5f6".to_string(),
+            domain: STEAM_COMMUNITY_HOST.to_string(),
+            path: "/".to_string(),
+            expires: None,
+            secure: false,
+            http_only: false,
+        }]);
+        authenticator
+    }
+
+    #[tokio::test]
+    async fn json_errors_of_offer_endpoints() {
+        let authenticator = canned_authenticator(
+            200,
+            r#"{"strError":"There was an error accepting this trade offer. Please try again later. (26)"}"#,
+        );
+        let manager = SteamTradeManager::new(&authenticator).unwrap();
+        assert!(matches!(
+            manager
+                .request::<TradeOfferCancelResponse>(TradeKind::Cancel, Some(4127395150))
+                .await,
+            Err(TradeError::TradeOfferError(OfferError::Revoked))
+        ));
+
+        let authenticator = canned_authenticator(200, r#"{"success":11}"#);
+        let manager = SteamTradeManager::new(&authenticator).unwrap();
+        assert!(matches!(
+            manager
+                .request::<TradeOfferCancelResponse>(TradeKind::Decline, Some(4127395150))
+                .await,
+            Err(TradeError::TradeOfferError(OfferError::InvalidState))
+        ));
+
+        let authenticator = canned_authenticator(200, r#"{"tradeofferid":"4127395150"}"#);
+        let manager = SteamTradeManager::new(&authenticator).unwrap();
+        let response = manager
+            .request::<TradeOfferCancelResponse>(TradeKind::Cancel, Some(4127395150))
+            .await
+            .unwrap();
+        assert_eq!(response.tradeofferid.as_deref(), Some("4127395150"));
+    }
+
+    #[tokio::test]
+    async fn offers_without_credentials() {
+        // the access token is only replaced by the pages when refused, and they can't list past updates
+        let authenticator = canned_authenticator(403, "<html>Access is denied.</html>");
+        let manager = SteamTradeManager::new(&authenticator).unwrap();
+        assert!(matches!(
+            manager.get_trade_offers_updated_since(1690000000).await,
+            Err(TradeError::TradeOfferError(OfferError::ActiveOffersOnly))
+        ));
+
+        let authenticator = canned_authenticator(429, "");
+        let manager = SteamTradeManager::new(&authenticator).unwrap();
+        assert!(matches!(
+            manager.get_trade_offers(true, true, true).await,
+            Err(TradeError::SteamAPIError(SteamAPIError::SteamHttpError(status))) if status == "429"
+        ));
+    }
+
+    #[tokio::test]
+    async fn web_api_through_transport() {
+        let transport = Arc::new(CannedTransport::new(200, r#"{"response":{"trade_offers_sent":[]}}"#));
+        let api_client = SteamAPI::with_executor("APIKEY", Arc::new(WebApiTransport(transport.clone())));
+
+        let response = api_client
+            .get()
+            .IEconService()
+            .GetTradeOffers(true, false, MAX_HISTORICAL_CUTOFF, Some(true), None, None, None)
+            .execute_with_response()
+            .await
+            .unwrap();
+        assert_eq!(response.response.trade_offers_sent.map(|offers| offers.len()), Some(0));
+
+        let sent = transport.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].url.path(), "/IEconService/GetTradeOffers/v1");
+        assert!(sent[0].url.query().unwrap().starts_with("key=APIKEY&"));
+    }
+
     #[test]
     fn new_assets() {
         let raw_response = sample_trade_history_response();
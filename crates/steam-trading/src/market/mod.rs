@@ -0,0 +1,838 @@
+//! Community Market, on the session of the authenticator.
+//!
+//! Prices sent to Steam are a [`Price`] in the wallet currency of the account. When selling, the price is what we
+//! receive, and Steam adds its fees on top of it for the buyer. When buying, the price includes the fees.
+//!
+//! Listings, buy orders and purchases that need a mobile confirmation are confirmed right away. Check
+//! [`crate::SteamTradeManager::market`].
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use futures::stream;
+use futures::Stream;
+use futures_timer::Delay;
+use lazy_static::lazy_static;
+pub use price::Price;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use serde_with::serde_as;
+use serde_with::DisplayFromStr;
+use serde_with::PickFirst;
+use steam_language_gen::generated::enums::ECurrencyCode;
+use steam_mobile::Confirmation;
+use steam_mobile::ConfirmationAction;
+use steam_mobile::EConfirmationType;
+use steam_mobile::Url;
+use tracing::debug;
+pub use types::BuyOrder;
+pub use types::BuyOrderStatus;
+pub use types::ListingAsset;
+pub use types::MarketEventKind;
+pub use types::MarketHistoryEvent;
+pub use types::MarketHistoryPage;
+pub use types::MarketListing;
+pub use types::MarketPurchase;
+pub use types::MyListings;
+pub use types::OrderGraphPoint;
+pub use types::PriceHistogram;
+pub use types::PriceOverview;
+pub use types::WalletInfo;
+
+use crate::community;
+use crate::community::CommunityError;
+use crate::errors::market_error_from_message;
+use crate::errors::InternalError;
+use crate::errors::MarketError;
+use crate::market::types::ItemListingsPage;
+use crate::market::types::MyListingsPage;
+use crate::ConfirmationError;
+use crate::SteamTradeManager;
+use crate::TradeError;
+use crate::TryFutureExt;
+use crate::STANDARD_DELAY;
+
+mod price;
+mod types;
+
+const MARKET_BASE: &str = "https://steamcommunity.com/market/";
+
+/// Maximum number of listings or history events Steam returns on a single page.
+const MARKET_PAGE_SIZE: u32 = 100;
+
+lazy_static! {
+    static ref ITEM_NAMEID_REGEX: Regex = Regex::new(r"Market_LoadOrderSpread\(\s*(?P<nameid>\d+)\s*\)").unwrap();
+    static ref WALLET_INFO_REGEX: Regex = Regex::new(r"g_rgWalletInfo\s*=\s*(?P<wallet>\{.*?\});").unwrap();
+}
+
+/// What happened to an item put up for sale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SellOutcome {
+    /// Item is for sale, or will be once it is no longer held by Steam.
+    Listed {
+        /// `None` if the listing could not be found among the active ones right away.
+        listingid: Option<u64>,
+    },
+    /// Steam is waiting for a confirmation that can't be done by this library, such as an email one.
+    PendingConfirmation {
+        #[allow(missing_docs)]
+        email_domain: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+struct SellItemRequest {
+    sessionid: String,
+    appid: u32,
+    contextid: u32,
+    assetid: i64,
+    amount: u64,
+    price: Price,
+}
+
+#[derive(Debug, Deserialize)]
+struct SellItemResponse {
+    needs_mobile_confirmation: Option<bool>,
+    needs_email_confirmation: Option<bool>,
+    email_domain: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RemoveListingRequest {
+    sessionid: String,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateBuyOrderRequest<'a> {
+    sessionid: String,
+    currency: i32,
+    appid: u32,
+    market_hash_name: &'a str,
+    price_total: Price,
+    quantity: u64,
+    billing_state: &'static str,
+    save_my_address: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirmation: Option<u64>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct CreateBuyOrderResponse {
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    buy_orderid: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CancelBuyOrderRequest {
+    sessionid: String,
+    buy_orderid: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct BuyListingRequest {
+    sessionid: String,
+    currency: i32,
+    subtotal: Price,
+    fee: Price,
+    total: Price,
+    quantity: u64,
+    billing_state: &'static str,
+    save_my_address: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirmation: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BuyListingResponse {
+    wallet_info: WalletInfo,
+}
+
+/// Sent instead of the result when the buy order or purchase must be confirmed on the mobile app first.
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct NeedConfirmationResponse {
+    #[serde(default)]
+    need_confirmation: bool,
+    confirmation: Option<ConfirmationId>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize)]
+struct ConfirmationId {
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    confirmation_id: u64,
+}
+
+/// Community Market client of a [`SteamTradeManager`].
+#[derive(Debug, Clone)]
+pub struct Market<'a> {
+    manager: SteamTradeManager<'a>,
+}
+
+impl<'a> Market<'a> {
+    pub(crate) fn new(manager: SteamTradeManager<'a>) -> Self {
+        Self { manager }
+    }
+
+    /// Summary of the sell listings of an item, with prices formatted in `currency`.
+    pub async fn price_overview(
+        &self,
+        appid: u32,
+        market_hash_name: &str,
+        currency: ECurrencyCode,
+    ) -> Result<PriceOverview, TradeError> {
+        let endpoint = Url::parse_with_params(
+            &format!("{}priceoverview/", MARKET_BASE),
+            &[
+                ("appid", appid.to_string()),
+                ("currency", (currency as i32).to_string()),
+                ("market_hash_name", market_hash_name.to_string()),
+            ],
+        )
+        .map_err(|e| MarketError::GeneralFailure(e.to_string()))?;
+
+        self.get(endpoint).await
+    }
+
+    /// Id of an item on the order book, needed by [`Self::price_histogram`]. It never changes, so it can be cached.
+    ///
+    /// It is parsed from the listings page of the item.
+    pub async fn item_nameid(&self, appid: u32, market_hash_name: &str) -> Result<u64, TradeError> {
+        let endpoint = listing_page(appid, market_hash_name)?;
+        let (status, page) = community::get(&self.manager.authenticator, endpoint).await?;
+        if status == 429 {
+            return Err(MarketError::RateLimited.into());
+        }
+
+        ITEM_NAMEID_REGEX
+            .captures(&page)
+            .and_then(|captures| captures.name("nameid"))
+            .and_then(|nameid| nameid.as_str().parse().ok())
+            .ok_or_else(|| MarketError::ItemNotFound.into())
+    }
+
+    /// Buy and sell orders of the item with `item_nameid`, with prices in `currency`.
+    pub async fn price_histogram(
+        &self,
+        item_nameid: u64,
+        currency: ECurrencyCode,
+    ) -> Result<PriceHistogram, TradeError> {
+        let endpoint = Url::parse_with_params(
+            &format!("{}itemordershistogram", MARKET_BASE),
+            &[
+                ("country", "US".to_string()),
+                ("language", "english".to_string()),
+                ("currency", (currency as i32).to_string()),
+                ("item_nameid", item_nameid.to_string()),
+                ("two_factor", "0".to_string()),
+            ],
+        )
+        .map_err(|e| MarketError::GeneralFailure(e.to_string()))?;
+
+        self.get(endpoint).await
+    }
+
+    /// Lists `amount` of an inventory item for sale, receiving `price` in the wallet currency for each one.
+    ///
+    /// The listing is confirmed with the inner authenticator if needed.
+    pub async fn sell_item(
+        &self,
+        appid: u32,
+        contextid: u32,
+        assetid: i64,
+        amount: u64,
+        price: Price,
+    ) -> Result<SellOutcome, TradeError> {
+        let form = SellItemRequest {
+            sessionid: self.manager.session_id()?,
+            appid,
+            contextid,
+            assetid,
+            amount,
+            price,
+        };
+        let referer = format!("{}inventory/", community::profile_url(&self.manager.authenticator));
+
+        let response: SellItemResponse = self.post(format!("{}sellitem/", MARKET_BASE), &referer, form).await?;
+        let needs_mobile_confirmation = response.needs_mobile_confirmation == Some(true);
+        if response.needs_email_confirmation == Some(true) && !needs_mobile_confirmation {
+            return Ok(SellOutcome::PendingConfirmation {
+                email_domain: response.email_domain,
+            });
+        }
+
+        let listingid = self
+            .my_listings()
+            .await?
+            .every_listing()
+            .find(|listing| {
+                listing.asset.appid == appid && listing.asset.contextid == contextid && listing.asset.assetid == assetid
+            })
+            .map(|listing| listing.listingid);
+
+        if needs_mobile_confirmation {
+            let listingid = listingid.ok_or(ConfirmationError::NotFound)?;
+            let creator_id = listingid.to_string();
+            self.accept_confirmation(|c| c.kind == EConfirmationType::Market && c.creator_id == creator_id)
+                .await?;
+        }
+        Ok(SellOutcome::Listed { listingid })
+    }
+
+    /// Sell listings of an item, cheapest first, with prices converted to `currency` when possible.
+    ///
+    /// Pass these to [`Self::buy_listing`] to purchase them.
+    pub async fn item_listings(
+        &self,
+        appid: u32,
+        market_hash_name: &str,
+        currency: ECurrencyCode,
+        start: u32,
+        count: u32,
+    ) -> Result<Vec<MarketListing>, TradeError> {
+        let mut endpoint = Url::parse(&format!("{}/render/", listing_page(appid, market_hash_name)?))
+            .map_err(|e| MarketError::GeneralFailure(e.to_string()))?;
+        endpoint
+            .query_pairs_mut()
+            .append_pair("query", "")
+            .append_pair("start", &start.to_string())
+            .append_pair("count", &count.to_string())
+            .append_pair("country", "US")
+            .append_pair("language", "english")
+            .append_pair("currency", &(currency as i32).to_string())
+            .append_pair("format", "json");
+
+        let page: ItemListingsPage = self.get(endpoint).await?;
+        let mut listings = page.listinginfo.into_values().collect::<Vec<_>>();
+        listings.sort_by(|a, b| match a.buyer_price().0.cmp(&b.buyer_price().0) {
+            Ordering::Equal => a.listingid.cmp(&b.listingid),
+            ordering => ordering,
+        });
+        Ok(listings)
+    }
+
+    /// Wallet of this account, parsed from the market page.
+    pub async fn wallet(&self) -> Result<WalletInfo, TradeError> {
+        let (status, page) = community::get(&self.manager.authenticator, MARKET_BASE.to_string()).await?;
+        if status == 429 {
+            return Err(MarketError::RateLimited.into());
+        }
+
+        let wallet = WALLET_INFO_REGEX
+            .captures(&page)
+            .and_then(|captures| captures.name("wallet"))
+            .ok_or_else(|| MarketError::GeneralFailure("Wallet not found on the market page.".to_string()))?;
+        serde_json::from_str(wallet.as_str()).map_err(|e| InternalError::from(e).into())
+    }
+
+    /// Places a buy order for `quantity` of an item, paying at most `price` for each one, fees included.
+    ///
+    /// The price is in the wallet currency, and the funds are checked before placing the order. Returns the id of the
+    /// buy order.
+    pub async fn create_buy_order(
+        &self,
+        appid: u32,
+        market_hash_name: &str,
+        price: Price,
+        quantity: u64,
+    ) -> Result<u64, TradeError> {
+        let wallet = self.wallet().await?;
+        let price_total = price.checked_mul(quantity).ok_or(MarketError::PriceOverflow)?;
+        wallet.ensure_funds(price_total)?;
+
+        let sessionid = self.manager.session_id()?;
+        let referer = listing_page(appid, market_hash_name)?;
+        let response: CreateBuyOrderResponse = self
+            .post_confirmed(format!("{}createbuyorder/", MARKET_BASE), &referer, |confirmation| {
+                CreateBuyOrderRequest {
+                    sessionid: sessionid.clone(),
+                    currency: wallet.currency as i32,
+                    appid,
+                    market_hash_name,
+                    price_total,
+                    quantity,
+                    billing_state: "",
+                    save_my_address: 0,
+                    confirmation,
+                }
+            })
+            .await?;
+
+        debug!("Buy order {} created.", response.buy_orderid);
+        Ok(response.buy_orderid)
+    }
+
+    /// Progress of a buy order of this account.
+    pub async fn buy_order_status(&self, buy_orderid: u64) -> Result<BuyOrderStatus, TradeError> {
+        let endpoint = format!(
+            "{}getbuyorderstatus/?sessionid={}&buy_orderid={}",
+            MARKET_BASE,
+            self.manager.session_id()?,
+            buy_orderid
+        );
+        self.get(endpoint).await
+    }
+
+    /// Polls a buy order every `interval`, yielding its status until it is no longer active.
+    ///
+    /// The stream ends after yielding the inactive status or an error.
+    pub fn watch_buy_order(
+        &self,
+        buy_orderid: u64,
+        interval: Duration,
+    ) -> impl Stream<Item = Result<BuyOrderStatus, TradeError>> + '_ {
+        // `false` once the last status was yielded
+        let initial_state = (true, false);
+
+        stream::unfold(initial_state, move |(watching, waited)| async move {
+            if !watching {
+                return None;
+            }
+            if waited {
+                Delay::new(interval).await;
+            }
+
+            match self.buy_order_status(buy_orderid).await {
+                Ok(status) => {
+                    let active = status.active;
+                    Some((Ok(status), (active, true)))
+                }
+                Err(e) => Some((Err(e), (false, true))),
+            }
+        })
+    }
+
+    /// Cancels a buy order of this account.
+    pub async fn cancel_buy_order(&self, buy_orderid: u64) -> Result<(), TradeError> {
+        let form = CancelBuyOrderRequest {
+            sessionid: self.manager.session_id()?,
+            buy_orderid,
+        };
+        self.post::<IgnoredAny, _>(format!("{}cancelbuyorder/", MARKET_BASE), MARKET_BASE, form)
+            .await?;
+        Ok(())
+    }
+
+    /// Buys a listing found with [`Self::item_listings`], at its buyer price.
+    ///
+    /// Fails with [`MarketError::CurrencyMismatch`] if the price is not in the wallet currency, and with
+    /// [`MarketError::InsufficientFunds`] if the wallet can't pay it. Returns the wallet after the purchase.
+    pub async fn buy_listing(&self, listing: &MarketListing) -> Result<WalletInfo, TradeError> {
+        let wallet = self.wallet().await?;
+        let (subtotal, fee, currency) = listing.buyer_price();
+        if currency != wallet.currency {
+            return Err(MarketError::CurrencyMismatch {
+                wallet: wallet.currency,
+                found: currency,
+            }
+            .into());
+        }
+        let total = subtotal.checked_add(fee).ok_or(MarketError::PriceOverflow)?;
+        wallet.ensure_funds(total)?;
+
+        let sessionid = self.manager.session_id()?;
+        let referer = listing_page(listing.asset.appid, &listing.asset.market_hash_name)?;
+        let endpoint = format!("{}buylisting/{}", MARKET_BASE, listing.listingid);
+        let response: BuyListingResponse = self
+            .post_confirmed(endpoint, &referer, |confirmation| BuyListingRequest {
+                sessionid: sessionid.clone(),
+                currency: wallet.currency as i32,
+                subtotal,
+                fee,
+                total,
+                quantity: 1,
+                billing_state: "",
+                save_my_address: 0,
+                confirmation,
+            })
+            .await?;
+
+        debug!("Listing {} bought for {}.", listing.listingid, total);
+        Ok(response.wallet_info)
+    }
+
+    /// Finds a single mobile confirmation matching `filter` and accepts it.
+    async fn accept_confirmation<F>(&self, filter: F) -> Result<(), TradeError>
+    where
+        F: Fn(&Confirmation) -> bool,
+    {
+        let _guard = self.manager.authenticator.confirmation_lock().lock().await;
+        let confirmations = self.manager.lookup_confirmations(1, filter).await?;
+
+        if confirmations.is_empty() {
+            return Err(ConfirmationError::NotFound.into());
+        }
+        self.manager
+            .authenticator
+            .process_confirmations(ConfirmationAction::Accept, confirmations)
+            .err_into::<TradeError>()
+            .await
+    }
+
+    /// Removes a listing of this account, returning the item to the inventory.
+    pub async fn remove_listing(&self, listingid: u64) -> Result<(), TradeError> {
+        let form = RemoveListingRequest {
+            sessionid: self.manager.session_id()?,
+        };
+        let endpoint = format!("{}removelisting/{}", MARKET_BASE, listingid);
+        let (status, body) = self.send_form(endpoint, MARKET_BASE, form).await?;
+        removed_listing(listingid, status, &body)
+    }
+
+    /// Every active listing and buy order of this account.
+    pub async fn my_listings(&self) -> Result<MyListings, TradeError> {
+        let mut my_listings: Option<MyListings> = None;
+        let mut start = 0;
+
+        loop {
+            let endpoint = format!(
+                "{}mylistings/?norender=1&start={}&count={}",
+                MARKET_BASE, start, MARKET_PAGE_SIZE
+            );
+            let page: MyListingsPage = self.get(endpoint).await?;
+            start += MARKET_PAGE_SIZE;
+
+            let last_page = start >= page.total_count;
+            match &mut my_listings {
+                // listings on hold, to confirm and buy orders are repeated on every page
+                Some(my_listings) => my_listings.listings.extend(page.listings.listings),
+                None => my_listings = Some(page.listings),
+            }
+
+            if last_page {
+                break;
+            }
+            Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+        }
+
+        Ok(my_listings.unwrap_or_default())
+    }
+
+    /// Streams every page of the market history of this account, newest first.
+    pub fn history_pages(&self) -> impl Stream<Item = Result<MarketHistoryPage, TradeError>> + '_ {
+        // `None` once the last page was yielded
+        let initial_state = Some(0);
+
+        stream::unfold(initial_state, move |start: Option<u32>| async move {
+            let start = start?;
+            if start > 0 {
+                Delay::new(Duration::from_millis(STANDARD_DELAY)).await;
+            }
+
+            let endpoint = format!(
+                "{}myhistory/?norender=1&start={}&count={}",
+                MARKET_BASE, start, MARKET_PAGE_SIZE
+            );
+            match self.get::<MarketHistoryPage>(endpoint).await {
+                Ok(page) => {
+                    debug!("Market history page fetched with {} events.", page.events.len());
+                    let next = start + MARKET_PAGE_SIZE;
+                    let next = Some(next).filter(|next| *next < page.total_count && !page.events.is_empty());
+                    Some((Ok(page), next))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
+
+    async fn get<T>(&self, endpoint: impl ToString) -> Result<T, TradeError>
+    where
+        T: DeserializeOwned,
+    {
+        let (status, body) = community::get(&self.manager.authenticator, endpoint.to_string()).await?;
+        parse_response(status, &body)
+    }
+
+    async fn post<T, F>(&self, endpoint: String, referer: &str, form: F) -> Result<T, TradeError>
+    where
+        T: DeserializeOwned,
+        F: Serialize + Send + Sync,
+    {
+        let (status, body) = self.send_form(endpoint, referer, form).await?;
+        parse_response(status, &body)
+    }
+
+    /// Posts the form built by `form`. If Steam asks for a mobile confirmation, it is accepted and the form is posted
+    /// again with its id.
+    async fn post_confirmed<T, F, B>(&self, endpoint: String, referer: &str, form: B) -> Result<T, TradeError>
+    where
+        T: DeserializeOwned,
+        F: Serialize + Send + Sync,
+        B: Fn(Option<u64>) -> F,
+    {
+        let (status, body) = self.send_form(endpoint.clone(), referer, form(None)).await?;
+        let Some(confirmation_id) = needed_confirmation(&body) else {
+            return parse_response(status, &body);
+        };
+
+        debug!("Market confirmation {} needed.", confirmation_id);
+        let creator_id = confirmation_id.to_string();
+        self.accept_confirmation(|c| c.creator_id == creator_id).await?;
+
+        let (status, body) = self.send_form(endpoint, referer, form(Some(confirmation_id))).await?;
+        parse_response(status, &body)
+    }
+
+    async fn send_form<F>(&self, endpoint: String, referer: &str, form: F) -> Result<(u16, String), TradeError>
+    where
+        F: Serialize + Send + Sync,
+    {
+        community::send_form::<MarketError, _>(&self.manager.authenticator, endpoint, referer, form).await
+    }
+}
+
+/// Url of the listings page of an item.
+fn listing_page(appid: u32, market_hash_name: &str) -> Result<String, TradeError> {
+    let mut page = Url::parse(&format!("{}listings/{}/", MARKET_BASE, appid))
+        .map_err(|e| MarketError::GeneralFailure(e.to_string()))?;
+    // safe to unwrap, the base is not a cannot-be-a-base url
+    page.path_segments_mut().unwrap().pop_if_empty().push(market_hash_name);
+    Ok(page.to_string())
+}
+
+/// Id of the mobile confirmation Steam asks for before accepting a buy order or purchase, if any.
+fn needed_confirmation(body: &str) -> Option<u64> {
+    serde_json::from_str::<NeedConfirmationResponse>(body)
+        .ok()
+        .filter(|response| response.need_confirmation)
+        .and_then(|response| response.confirmation)
+        .map(|confirmation| confirmation.confirmation_id)
+}
+
+/// Maps the response of a listing removal, which is an empty array once removed.
+fn removed_listing(listingid: u64, status: u16, body: &str) -> Result<(), TradeError> {
+    if status == 404 {
+        return Err(MarketError::ListingNotFound(listingid).into());
+    }
+
+    match parse_response::<IgnoredAny>(status, body) {
+        Ok(_) if status == 200 => Ok(()),
+        Ok(_) => Err(MarketError::GeneralFailure(format!("Request failed with status {}.", status)).into()),
+        Err(TradeError::MarketError(MarketError::GeneralFailure(message)))
+            if message.to_lowercase().contains("not found") =>
+        {
+            Err(MarketError::ListingNotFound(listingid).into())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Parses a market response, mapping failures to typed errors.
+fn parse_response<T>(status: u16, body: &str) -> Result<T, TradeError>
+where
+    T: DeserializeOwned,
+{
+    community::parse_response::<T, MarketError>(status, body)
+}
+
+impl CommunityError for MarketError {
+    fn rate_limited() -> Self {
+        Self::RateLimited
+    }
+
+    fn general_failure(reason: String) -> Self {
+        Self::GeneralFailure(reason)
+    }
+
+    fn from_response(response: &Value, status: u16) -> Option<Self> {
+        let success = match response.get("success") {
+            Some(Value::Bool(success)) => *success,
+            Some(Value::Number(success)) => success.as_u64() == Some(1),
+            // some endpoints only send a message when they fail
+            _ => response.get("message").is_none(),
+        };
+        if success {
+            return None;
+        }
+
+        Some(match response.get("message").and_then(Value::as_str) {
+            Some(message) => market_error_from_message(message),
+            None => Self::GeneralFailure(format!("Request failed with status {}.", status)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn price_overview() {
+        let overview: PriceOverview = parse_response(
+            200,
+            r#"{"success":true,"lowest_price":"$0.05","volume":"12,345","median_price":"$0.04"}"#,
+        )
+        .unwrap();
+        assert_eq!(overview.lowest_price.as_deref(), Some("$0.05"));
+        assert_eq!(overview.volume, 12345);
+
+        assert!(matches!(
+            parse_response::<PriceOverview>(429, "null"),
+            Err(TradeError::MarketError(MarketError::RateLimited))
+        ));
+        assert!(matches!(
+            parse_response::<PriceOverview>(
+                200,
+                r#"{"success":false,"message":"You cannot sell any items until your previous action completes."}"#
+            ),
+            Err(TradeError::MarketError(MarketError::RateLimited))
+        ));
+    }
+
+    #[test]
+    fn histogram() {
+        let histogram: PriceHistogram = parse_response(
+            200,
+            r#"{
+                "success": 1,
+                "highest_buy_order": "2710",
+                "lowest_sell_order": null,
+                "buy_order_graph": [[27.1, 5, "5 buy orders at $27.10 or higher"], [27, 12, "12 buy orders at $27.00 or higher"]],
+                "sell_order_graph": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(histogram.highest_buy_order, Some(Price::from_cents(2710)));
+        assert_eq!(histogram.lowest_sell_order, None);
+        assert_eq!(histogram.buy_order_graph[0].price, Price::from_cents(2710));
+        assert_eq!(
+            histogram.buy_order_graph[1],
+            OrderGraphPoint {
+                price: Price::from_cents(2700),
+                quantity: 12
+            }
+        );
+
+        let page = "<script>$J(function() { Market_LoadOrderSpread( 176241017 ); });</script>";
+        let nameid = ITEM_NAMEID_REGEX.captures(page).unwrap().name("nameid").unwrap();
+        assert_eq!(nameid.as_str(), "176241017");
+    }
+
+    #[test]
+    fn history_page() {
+        let page: MarketHistoryPage = parse_response(
+            200,
+            r#"{
+                "success": true,
+                "pagesize": 100,
+                "total_count": 2,
+                "start": 0,
+                "events": [
+                    {"listingid": "4380951236486853520", "purchaseid": "4380951236486853521", "event_type": 3, "time_event": 1696000100},
+                    {"listingid": "4380951236486853520", "event_type": 1, "time_event": 1696000000}
+                ],
+                "listings": {
+                    "4380951236486853520": {
+                        "listingid": "4380951236486853520",
+                        "price": 2450,
+                        "fee": 366,
+                        "currencyid": "2001",
+                        "asset": {"currency": 0, "appid": 730, "contextid": "2", "id": "33432352345", "amount": "0"}
+                    }
+                },
+                "purchases": {
+                    "4380951236486853520_4380951236486853521": {
+                        "listingid": "4380951236486853520",
+                        "purchaseid": "4380951236486853521",
+                        "time_sold": 1696000100,
+                        "asset": {"currency": 0, "appid": 730, "contextid": "2", "id": "33432352346", "amount": "1"},
+                        "paid_amount": 2450,
+                        "paid_fee": 366,
+                        "currencyid": "2001",
+                        "received_amount": 2450,
+                        "received_currencyid": "2001"
+                    }
+                },
+                "assets": []
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(page.events[0].kind, MarketEventKind::ListingSold);
+        assert_eq!(page.listing_of(&page.events[0]).unwrap().price, Price::from_cents(2450));
+        assert_eq!(page.purchase_of(&page.events[0]).unwrap().asset.assetid, 33432352346);
+        assert!(page.purchase_of(&page.events[1]).is_none());
+
+        let empty: MarketHistoryPage = parse_response(
+            200,
+            r#"{"success": true, "total_count": 0, "start": 0, "events": [], "listings": [], "purchases": []}"#,
+        )
+        .unwrap();
+        assert!(empty.listings.is_empty());
+    }
+
+    #[test]
+    fn buy_orders() {
+        let body = r#"{"success":22,"need_confirmation":true,"confirmation":{"confirmation_id":"13968434452"}}"#;
+        assert_eq!(needed_confirmation(body), Some(13968434452));
+        assert_eq!(needed_confirmation(r#"{"success":1,"buy_orderid":"6183702461"}"#), None);
+
+        let order: CreateBuyOrderResponse = parse_response(200, r#"{"success":1,"buy_orderid":"6183702461"}"#).unwrap();
+        assert_eq!(order.buy_orderid, 6183702461);
+        assert!(matches!(
+            parse_response::<CreateBuyOrderResponse>(
+                200,
+                r#"{"success":29,"message":"You already have an active buy order for this item."}"#
+            ),
+            Err(TradeError::MarketError(MarketError::BuyOrderExists))
+        ));
+
+        let status: BuyOrderStatus = parse_response(
+            200,
+            r#"{"success":1,"active":0,"purchased":2,"quantity":"2","quantity_remaining":"0","purchases":[]}"#,
+        )
+        .unwrap();
+        assert!(!status.active);
+        assert_eq!(status.purchased, 2);
+
+        let page = r#"var g_rgWalletInfo = {"wallet_currency":3,"wallet_country":"DE","wallet_fee":"1","wallet_balance":"1250","wallet_delayed_balance":"0","wallet_max_balance":"200000"};"#;
+        let wallet = WALLET_INFO_REGEX.captures(page).unwrap().name("wallet").unwrap();
+        let wallet: WalletInfo = serde_json::from_str(wallet.as_str()).unwrap();
+        assert_eq!(wallet.currency, ECurrencyCode::EUR);
+        assert_eq!(
+            wallet.ensure_funds(Price::from_cents(1300)),
+            Err(MarketError::InsufficientFunds {
+                needed: Price::from_cents(1300),
+                balance: Price::from_cents(1250),
+            })
+        );
+    }
+
+    #[test]
+    fn listing_removals() {
+        const LISTING_ID: u64 = 4611686018427387904;
+
+        assert!(removed_listing(LISTING_ID, 200, "[]").is_ok());
+        assert!(matches!(
+            removed_listing(LISTING_ID, 404, ""),
+            Err(TradeError::MarketError(MarketError::ListingNotFound(LISTING_ID)))
+        ));
+        assert!(matches!(
+            removed_listing(
+                LISTING_ID,
+                200,
+                r#"{"success":false,"message":"The listing was not found."}"#
+            ),
+            Err(TradeError::MarketError(MarketError::ListingNotFound(LISTING_ID)))
+        ));
+
+        // failures that don't tell the listing is gone keep their reason
+        assert!(matches!(
+            removed_listing(LISTING_ID, 429, ""),
+            Err(TradeError::MarketError(MarketError::RateLimited))
+        ));
+        assert!(matches!(
+            removed_listing(LISTING_ID, 500, "<html>Internal Server Error</html>"),
+            Err(TradeError::MarketError(MarketError::GeneralFailure(message))) if message.contains("500")
+        ));
+        assert!(matches!(
+            removed_listing(LISTING_ID, 401, r#"{"success":false}"#),
+            Err(TradeError::MarketError(MarketError::GeneralFailure(message))) if message.contains("401")
+        ));
+    }
+}
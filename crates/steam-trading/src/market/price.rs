@@ -0,0 +1,130 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use num_traits::FromPrimitive;
+use serde::de;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use steam_language_gen::generated::enums::ECurrencyCode;
+
+/// Listings send the currency of the wallet as its code plus this offset.
+const WALLET_CURRENCY_OFFSET: i64 = 2000;
+
+/// An amount of money, in hundredths of the currency unit, such as cents.
+///
+/// Steam uses hundredths even for currencies without cents, such as JPY.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Price(u64);
+
+impl Price {
+    #[allow(missing_docs)]
+    pub const fn from_cents(cents: u64) -> Self {
+        Self(cents)
+    }
+
+    #[allow(missing_docs)]
+    pub const fn cents(self) -> u64 {
+        self.0
+    }
+
+    /// `None` if the sum overflows.
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(cents) => Some(Self(cents)),
+            None => None,
+        }
+    }
+
+    /// `None` if the product overflows.
+    pub const fn checked_mul(self, rhs: u64) -> Option<Self> {
+        match self.0.checked_mul(rhs) {
+            Some(cents) => Some(Self(cents)),
+            None => None,
+        }
+    }
+}
+
+impl Display for Price {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+/// Steam sends numbers either as JSON numbers or as strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NumberOrString {
+    Number(i64),
+    String(String),
+}
+
+impl NumberOrString {
+    fn into_number<E: de::Error>(self) -> Result<i64, E> {
+        match self {
+            Self::Number(number) => Ok(number),
+            Self::String(string) => string.trim().parse().map_err(E::custom),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let cents = NumberOrString::deserialize(deserializer)?.into_number()?;
+        u64::try_from(cents).map(Self).map_err(de::Error::custom)
+    }
+}
+
+/// Currency of `code`, with or without the wallet offset. [`ECurrencyCode::Invalid`] if unknown.
+pub(crate) fn currency_from_code(code: i64) -> ECurrencyCode {
+    let code = if code > WALLET_CURRENCY_OFFSET {
+        code - WALLET_CURRENCY_OFFSET
+    } else {
+        code
+    };
+    ECurrencyCode::from_i64(code).unwrap_or(ECurrencyCode::Invalid)
+}
+
+pub(crate) fn deserialize_currency<'de, D>(deserializer: D) -> Result<ECurrencyCode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let code = NumberOrString::deserialize(deserializer)?.into_number()?;
+    Ok(currency_from_code(code))
+}
+
+pub(crate) fn deserialize_optional_currency<'de, D>(deserializer: D) -> Result<Option<ECurrencyCode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<NumberOrString>::deserialize(deserializer)?
+        .map(|code| code.into_number().map(currency_from_code))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prices_and_currencies() {
+        let prices: Vec<Price> = serde_json::from_str(r#"[2450, "300"]"#).unwrap();
+        assert_eq!(prices, vec![Price::from_cents(2450), Price::from_cents(300)]);
+        let total = prices[1]
+            .checked_mul(2)
+            .and_then(|double| prices[0].checked_add(double));
+        assert_eq!(total.unwrap().to_string(), "30.50");
+        assert_eq!(Price::from_cents(u64::MAX).checked_mul(2), None);
+        assert_eq!(Price::from_cents(u64::MAX).checked_add(Price::from_cents(1)), None);
+        assert_eq!(Price::from_cents(5).to_string(), "0.05");
+
+        assert_eq!(currency_from_code(2003), ECurrencyCode::EUR);
+        assert_eq!(currency_from_code(1), ECurrencyCode::USD);
+        assert_eq!(currency_from_code(33), ECurrencyCode::Invalid);
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde_json::Value;
+use serde_with::serde_as;
+use serde_with::BoolFromInt;
+use serde_with::DisplayFromStr;
+use serde_with::PickFirst;
+use steam_language_gen::generated::enums::ECurrencyCode;
+
+use crate::market::price::deserialize_currency;
+use crate::market::price::deserialize_optional_currency;
+use crate::market::price::Price;
+use crate::MarketError;
+
+/// Summary of the sell listings of an item.
+///
+/// Prices are formatted in the requested currency, such as `$1.23`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PriceOverview {
+    /// Cheapest sell listing. `None` if nobody is selling the item.
+    pub lowest_price: Option<String>,
+    /// Median price of the sales of the last 24 hours.
+    pub median_price: Option<String>,
+    /// Items sold in the last 24 hours.
+    #[serde(default, deserialize_with = "number_with_separators")]
+    pub volume: u64,
+}
+
+/// Buy and sell orders of an item.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct PriceHistogram {
+    /// Highest buy order, in the requested currency.
+    #[serde(default)]
+    pub highest_buy_order: Option<Price>,
+    /// Cheapest sell listing, in the requested currency.
+    #[serde(default)]
+    pub lowest_sell_order: Option<Price>,
+    /// Buy orders grouped by price, highest price first.
+    #[serde(default)]
+    pub buy_order_graph: Vec<OrderGraphPoint>,
+    /// Sell listings grouped by price, cheapest first.
+    #[serde(default)]
+    pub sell_order_graph: Vec<OrderGraphPoint>,
+}
+
+/// A price of the order book.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "(f64, u64, String)")]
+pub struct OrderGraphPoint {
+    /// Price, in the requested currency.
+    pub price: Price,
+    /// Orders at this price or better.
+    pub quantity: u64,
+}
+
+impl From<(f64, u64, String)> for OrderGraphPoint {
+    fn from((price, quantity, _description): (f64, u64, String)) -> Self {
+        // the graph has prices in units of the currency, such as `27.1`, and floats aren't exact
+        let cents = (price * 100.0).round() as u64;
+        Self {
+            price: Price::from_cents(cents),
+            quantity,
+        }
+    }
+}
+
+/// An item listed for sale.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MarketListing {
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub listingid: u64,
+    /// Unix timestamp. Zero on the market history.
+    #[serde(default)]
+    pub time_created: i64,
+    #[allow(missing_docs)]
+    pub asset: ListingAsset,
+    /// What the seller receives, in `currency`.
+    #[serde(default)]
+    pub price: Price,
+    /// Fees paid by the buyer on top of `price`.
+    #[serde(default)]
+    pub fee: Price,
+    /// Wallet currency of the seller.
+    #[serde(rename = "currencyid", deserialize_with = "deserialize_currency")]
+    pub currency: ECurrencyCode,
+    /// `price` in `converted_currency`. Set on the listings of an item, converted to the requested currency.
+    #[serde(default)]
+    pub converted_price: Option<Price>,
+    /// `fee` in `converted_currency`.
+    #[serde(default)]
+    pub converted_fee: Option<Price>,
+    #[serde(
+        rename = "converted_currencyid",
+        default,
+        deserialize_with = "deserialize_optional_currency"
+    )]
+    #[allow(missing_docs)]
+    pub converted_currency: Option<ECurrencyCode>,
+}
+
+impl MarketListing {
+    /// Price, fee and currency paid by a buyer. Converted ones if available.
+    pub fn buyer_price(&self) -> (Price, Price, ECurrencyCode) {
+        match (self.converted_price, self.converted_fee, self.converted_currency) {
+            (Some(price), Some(fee), Some(currency)) => (price, fee, currency),
+            _ => (self.price, self.fee, self.currency),
+        }
+    }
+}
+
+/// The asset of a listing.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ListingAsset {
+    #[allow(missing_docs)]
+    pub appid: u32,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[allow(missing_docs)]
+    pub contextid: u32,
+    #[serde(rename = "id")]
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[allow(missing_docs)]
+    pub assetid: i64,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub amount: u64,
+    /// Empty on the market history.
+    #[serde(default)]
+    pub market_hash_name: String,
+}
+
+/// An order to buy an item at a price.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BuyOrder {
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub buy_orderid: u64,
+    #[allow(missing_docs)]
+    pub appid: u32,
+    /// Market hash name of the item.
+    pub hash_name: String,
+    #[serde(deserialize_with = "deserialize_currency")]
+    #[allow(missing_docs)]
+    pub wallet_currency: ECurrencyCode,
+    /// Price of each item, in `wallet_currency`.
+    pub price: Price,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[allow(missing_docs)]
+    pub quantity: u64,
+    /// Items not bought yet.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub quantity_remaining: u64,
+}
+
+/// Active listings and buy orders of this account.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct MyListings {
+    /// Listings for sale.
+    #[serde(default)]
+    pub listings: Vec<MarketListing>,
+    /// Listings that are not for sale yet, because the item is held by Steam.
+    #[serde(default)]
+    pub listings_on_hold: Vec<MarketListing>,
+    /// Listings waiting for a mobile or email confirmation.
+    #[serde(default)]
+    pub listings_to_confirm: Vec<MarketListing>,
+    #[serde(default)]
+    #[allow(missing_docs)]
+    pub buy_orders: Vec<BuyOrder>,
+}
+
+impl MyListings {
+    /// Every listing, whatever its status.
+    pub fn every_listing(&self) -> impl Iterator<Item = &MarketListing> {
+        self.listings
+            .iter()
+            .chain(self.listings_on_hold.iter())
+            .chain(self.listings_to_confirm.iter())
+    }
+}
+
+/// A page of `/market/mylistings`. Only the active listings are paginated.
+#[derive(Debug, Deserialize)]
+pub(crate) struct MyListingsPage {
+    #[serde(default)]
+    pub total_count: u32,
+    #[serde(flatten)]
+    pub listings: MyListings,
+}
+
+/// A page of the market history, newest first.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MarketHistoryPage {
+    /// Events on every page.
+    pub total_count: u32,
+    /// Position of the first event of this page.
+    pub start: u32,
+    #[allow(missing_docs)]
+    pub events: Vec<MarketHistoryEvent>,
+    /// Listings of the events, by listing id.
+    #[serde(default, deserialize_with = "map_or_empty_list")]
+    pub listings: HashMap<u64, MarketListing>,
+    /// Sales and purchases of the events. Check [`Self::purchase_of`].
+    #[serde(default, deserialize_with = "map_or_empty_list")]
+    pub purchases: HashMap<String, MarketPurchase>,
+}
+
+impl MarketHistoryPage {
+    /// Listing that `event` is about.
+    pub fn listing_of(&self, event: &MarketHistoryEvent) -> Option<&MarketListing> {
+        self.listings.get(&event.listingid)
+    }
+
+    /// Sale or purchase of `event`, if it is one.
+    pub fn purchase_of(&self, event: &MarketHistoryEvent) -> Option<&MarketPurchase> {
+        let purchaseid = event.purchaseid?;
+        self.purchases.get(&format!("{}_{}", event.listingid, purchaseid))
+    }
+}
+
+/// An event of the market history.
+#[serde_as]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub struct MarketHistoryEvent {
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub listingid: u64,
+    /// Set on sales and purchases.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub purchaseid: Option<u64>,
+    #[serde(rename = "event_type")]
+    #[allow(missing_docs)]
+    pub kind: MarketEventKind,
+    /// Unix timestamp.
+    pub time_event: i64,
+}
+
+/// What happened to a listing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u8", into = "u8")]
+pub enum MarketEventKind {
+    /// Item was listed by this account.
+    ListingCreated,
+    /// Listing of this account was removed.
+    ListingCanceled,
+    /// Item listed by this account was sold.
+    ListingSold,
+    /// Item was bought by this account.
+    ListingPurchased,
+    /// Unknown event type.
+    Other(u8),
+}
+
+impl From<u8> for MarketEventKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            1 => Self::ListingCreated,
+            2 => Self::ListingCanceled,
+            3 => Self::ListingSold,
+            4 => Self::ListingPurchased,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl From<MarketEventKind> for u8 {
+    fn from(kind: MarketEventKind) -> Self {
+        match kind {
+            MarketEventKind::ListingCreated => 1,
+            MarketEventKind::ListingCanceled => 2,
+            MarketEventKind::ListingSold => 3,
+            MarketEventKind::ListingPurchased => 4,
+            MarketEventKind::Other(other) => other,
+        }
+    }
+}
+
+/// A sale or purchase of the market history.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MarketPurchase {
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub listingid: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    #[allow(missing_docs)]
+    pub purchaseid: u64,
+    /// Unix timestamp.
+    pub time_sold: i64,
+    #[allow(missing_docs)]
+    pub asset: ListingAsset,
+    /// What the buyer paid without fees, in `currency`.
+    #[serde(default)]
+    pub paid_amount: Price,
+    /// Fees the buyer paid, in `currency`.
+    #[serde(default)]
+    pub paid_fee: Price,
+    /// Wallet currency of the buyer.
+    #[serde(rename = "currencyid", deserialize_with = "deserialize_currency")]
+    pub currency: ECurrencyCode,
+    /// What the seller received, in `received_currency`.
+    #[serde(default)]
+    pub received_amount: Price,
+    /// Wallet currency of the seller.
+    #[serde(rename = "received_currencyid", deserialize_with = "deserialize_currency")]
+    pub received_currency: ECurrencyCode,
+}
+
+/// Steam Wallet of this account.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WalletInfo {
+    /// Every market price of this account is in this currency.
+    #[serde(rename = "wallet_currency", deserialize_with = "deserialize_currency")]
+    pub currency: ECurrencyCode,
+    #[serde(rename = "wallet_country", default)]
+    #[allow(missing_docs)]
+    pub country: String,
+    /// Funds that can be spent.
+    #[serde(rename = "wallet_balance")]
+    pub balance: Price,
+    /// Funds that can't be spent yet.
+    #[serde(rename = "wallet_delayed_balance", default)]
+    pub delayed_balance: Price,
+    /// The balance can't go over this.
+    #[serde(rename = "wallet_max_balance", default)]
+    pub max_balance: Price,
+}
+
+impl WalletInfo {
+    /// Returns [`MarketError::InsufficientFunds`] if `needed` can't be spent.
+    pub fn ensure_funds(&self, needed: Price) -> Result<(), MarketError> {
+        if needed > self.balance {
+            return Err(MarketError::InsufficientFunds {
+                needed,
+                balance: self.balance,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Progress of a buy order.
+#[serde_as]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub struct BuyOrderStatus {
+    /// `false` once the order is filled or canceled.
+    #[serde_as(as = "BoolFromInt")]
+    pub active: bool,
+    /// Items bought so far.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub purchased: u64,
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[allow(missing_docs)]
+    pub quantity: u64,
+    /// Items not bought yet.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub quantity_remaining: u64,
+}
+
+/// A page of the listings of an item.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ItemListingsPage {
+    #[serde(default, deserialize_with = "map_or_empty_list")]
+    pub listinginfo: HashMap<u64, MarketListing>,
+}
+
+/// Counts formatted with thousands separators, such as `1,234`.
+fn number_with_separators<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number = Option::<String>::deserialize(deserializer)?;
+    Ok(number
+        .map(|n| n.replace(',', "").parse().unwrap_or_default())
+        .unwrap_or_default())
+}
+
+/// Steam sends empty maps as empty lists.
+fn map_or_empty_list<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: DeserializeOwned + Eq + Hash,
+    V: DeserializeOwned,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Array(_) | Value::Null => Ok(HashMap::new()),
+        map => serde_json::from_value(map).map_err(serde::de::Error::custom),
+    }
+}
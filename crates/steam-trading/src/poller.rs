@@ -0,0 +1,467 @@
+//! Event based trading, built on top of [`SteamTradeManager::get_trade_offers_updated_since`].
+//!
+//! [`TradeOfferPoller`] periodically fetches trade offers, compares them with the ones seen on the previous poll, and
+//! emits a [`TradeOfferEvent`] for every change.
+//!
+//! Polling needs the API Key or the access token to be accepted. The trade offers pages can't tell which offers were
+//! updated, so polls fail with [`OfferError::ActiveOffersOnly`](crate::OfferError::ActiveOffersOnly) without them.
+//!
+//! The last seen state can be saved to disk, so a restarted poller picks up where it stopped. When polled as a
+//! stream, the state is saved only after every event of a poll was consumed, so a crash while handling events
+//! re-emits the events of that poll, instead of missing them.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use futures::stream;
+use futures::Stream;
+use futures_timer::Delay;
+use serde::Deserialize;
+use serde::Serialize;
+use steam_language_gen::generated::enums::ETradeOfferState;
+use tappet::response_types::TradeOffer_Trade;
+use tracing::debug;
+
+use crate::api_extensions::FilterBy;
+use crate::events::OfferEventKind;
+use crate::guard::partner_of;
+use crate::json_file;
+use crate::SteamTradeManager;
+use crate::TradeError;
+
+/// Offers updated this many seconds before the last poll are fetched again, to cover clock differences with Steam.
+const POLL_CUTOFF_SLACK_SECS: u32 = 60;
+
+/// A change of a trade offer between two polls.
+#[derive(Debug, Clone)]
+pub enum TradeOfferEvent {
+    /// A new offer was received and is waiting for our answer.
+    NewReceived(TradeOffer_Trade),
+    /// A new offer was sent by this account, possibly from outside this library.
+    NewSent(TradeOffer_Trade),
+    /// Offer was accepted, and the items were exchanged.
+    Accepted(TradeOffer_Trade),
+    /// Offer was declined by the receiving side.
+    Declined(TradeOffer_Trade),
+    /// Offer sent by this account was replaced by a counter offer of the partner, which is received as a new offer.
+    ///
+    /// Received offers countered by this account, as with [`SteamTradeManager::counter_offer`], emit
+    /// [`Self::StateChanged`] instead.
+    Countered(TradeOffer_Trade),
+    /// Offer expired without an answer.
+    Expired(TradeOffer_Trade),
+    /// Offer was accepted, but the items are held by Steam for a while.
+    EscrowStarted(TradeOffer_Trade),
+    /// Hold period is over, and the items were exchanged.
+    EscrowEnded(TradeOffer_Trade),
+    /// Offer created by this account was canceled, but not with [`SteamTradeManager::cancel_offer`].
+    ///
+    /// This is how offers are hijacked with a stolen API Key. Revoke the key, and check the sent offers for
+    /// look-alikes. Check [`crate::guard`].
+    CanceledByOthers(TradeOffer_Trade),
+    /// Any other change of state, such as canceled offers or offers with items no longer available.
+    StateChanged {
+        #[allow(missing_docs)]
+        offer: TradeOffer_Trade,
+        /// State seen on the previous poll. `None` if the offer was not seen before, such as one created and canceled
+        /// between two polls.
+        previous: Option<ETradeOfferState>,
+    },
+}
+
+impl TradeOfferEvent {
+    /// The offer that triggered this event, with its current state.
+    pub fn offer(&self) -> &TradeOffer_Trade {
+        match self {
+            Self::NewReceived(offer)
+            | Self::NewSent(offer)
+            | Self::Accepted(offer)
+            | Self::Declined(offer)
+            | Self::Countered(offer)
+            | Self::Expired(offer)
+            | Self::EscrowStarted(offer)
+            | Self::EscrowEnded(offer)
+            | Self::CanceledByOthers(offer)
+            | Self::StateChanged { offer, .. } => offer,
+        }
+    }
+}
+
+/// What is remembered of an offer between polls.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfferSnapshot {
+    #[allow(missing_docs)]
+    pub state: ETradeOfferState,
+    #[allow(missing_docs)]
+    pub is_our_offer: bool,
+    #[allow(missing_docs)]
+    pub time_updated: i64,
+}
+
+impl From<&TradeOffer_Trade> for OfferSnapshot {
+    fn from(offer: &TradeOffer_Trade) -> Self {
+        Self {
+            state: offer.state,
+            is_our_offer: offer.is_our_offer,
+            time_updated: offer.time_updated,
+        }
+    }
+}
+
+/// Last seen state of the poller. Serializable, so it can be persisted across restarts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PollState {
+    /// Unix timestamp of the last successful poll. Zero if never polled.
+    pub last_poll: u32,
+    /// Offers that may still change, by trade offer id.
+    pub offers: HashMap<u64, OfferSnapshot>,
+}
+
+impl PollState {
+    /// Loads the state from a JSON file. A missing file is read as an empty state.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TradeError> {
+        json_file::load(path.as_ref())
+    }
+
+    /// Saves the state as JSON to `path`.
+    ///
+    /// The file is written next to its destination first and then renamed, so a crash never leaves it truncated.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TradeError> {
+        json_file::save(path.as_ref(), self)
+    }
+
+    /// Compares `offers` with the known ones, returning the events and updating the state.
+    ///
+    /// Offers in a final state are forgotten once they are too old to be returned by the next poll.
+    pub fn apply<I>(&mut self, offers: I, polled_at: u32) -> Vec<TradeOfferEvent>
+    where
+        I: IntoIterator<Item = TradeOffer_Trade>,
+    {
+        let mut events = vec![];
+
+        for offer in offers {
+            let previous = self
+                .offers
+                .insert(offer.tradeofferid, OfferSnapshot::from(&offer))
+                .map(|p| p.state);
+            if previous == Some(offer.state) {
+                continue;
+            }
+
+            if let Some(event) = Self::classify(previous, offer) {
+                events.push(event);
+            }
+        }
+
+        let next_cutoff = i64::from(polled_at.saturating_sub(POLL_CUTOFF_SLACK_SECS));
+        self.offers
+            .retain(|_, offer| !is_final_state(offer.state) || offer.time_updated >= next_cutoff);
+
+        self.last_poll = polled_at;
+        events
+    }
+
+    fn classify(previous: Option<ETradeOfferState>, offer: TradeOffer_Trade) -> Option<TradeOfferEvent> {
+        let event = match (previous, offer.state) {
+            (None, ETradeOfferState::Active) if offer.is_our_offer => TradeOfferEvent::NewSent(offer),
+            (None, ETradeOfferState::Active) => TradeOfferEvent::NewReceived(offer),
+            // sent offers waiting on our own confirmation, we wait until they become active
+            (None, ETradeOfferState::CreatedNeedsConfirmation) => return None,
+            (Some(ETradeOfferState::CreatedNeedsConfirmation), ETradeOfferState::Active) => {
+                TradeOfferEvent::NewSent(offer)
+            }
+            (_, ETradeOfferState::InEscrow) => TradeOfferEvent::EscrowStarted(offer),
+            (Some(ETradeOfferState::InEscrow), ETradeOfferState::Accepted) => TradeOfferEvent::EscrowEnded(offer),
+            (_, ETradeOfferState::Accepted) => TradeOfferEvent::Accepted(offer),
+            (_, ETradeOfferState::Declined) => TradeOfferEvent::Declined(offer),
+            (_, ETradeOfferState::Countered) if offer.is_our_offer => TradeOfferEvent::Countered(offer),
+            (_, ETradeOfferState::Expired) => TradeOfferEvent::Expired(offer),
+            (previous, _) => TradeOfferEvent::StateChanged { offer, previous },
+        };
+        Some(event)
+    }
+}
+
+/// States from which an offer can't change anymore.
+pub(crate) const fn is_final_state(state: ETradeOfferState) -> bool {
+    !matches!(
+        state,
+        ETradeOfferState::Active | ETradeOfferState::CreatedNeedsConfirmation | ETradeOfferState::InEscrow
+    )
+}
+
+fn unix_now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+/// Long-running poller of trade offers. Check the [module documentation](self).
+#[derive(Debug)]
+pub struct TradeOfferPoller<'a> {
+    manager: SteamTradeManager<'a>,
+    interval: Duration,
+    state: PollState,
+    state_path: Option<PathBuf>,
+}
+
+impl<'a> TradeOfferPoller<'a> {
+    /// Poller that keeps its state only in memory.
+    ///
+    /// On the first poll, only active offers are reported. With a [`crate::SharedTradeManager`], the poller is
+    /// `'static` and its stream can be spawned into its own task.
+    pub fn new(manager: &SteamTradeManager<'a>, interval: Duration) -> Self {
+        Self {
+            manager: manager.clone(),
+            interval,
+            state: PollState::default(),
+            state_path: None,
+        }
+    }
+
+    /// Poller that loads its state from `path`, if present, and saves it back after every poll.
+    pub fn with_state_file(
+        manager: &SteamTradeManager<'a>,
+        interval: Duration,
+        path: impl Into<PathBuf>,
+    ) -> Result<Self, TradeError> {
+        let path = path.into();
+        let state = PollState::load(&path)?;
+
+        Ok(Self {
+            manager: manager.clone(),
+            interval,
+            state,
+            state_path: Some(path),
+        })
+    }
+
+    /// Current state of the poller.
+    pub fn state(&self) -> &PollState {
+        &self.state
+    }
+
+    /// Saves the state to the file given on [`Self::with_state_file`], if any.
+    pub fn save_state(&self) -> Result<(), TradeError> {
+        match &self.state_path {
+            Some(path) => self.state.save(path),
+            None => Ok(()),
+        }
+    }
+
+    /// Fetches offers once, returning what changed since the last poll.
+    ///
+    /// The state is updated in memory only. Call [`Self::save_state`] once the events are handled.
+    pub async fn poll_once(&mut self) -> Result<Vec<TradeOfferEvent>, TradeError> {
+        let now = unix_now();
+        let cutoff = if self.state.last_poll == 0 {
+            now
+        } else {
+            self.state.last_poll.saturating_sub(POLL_CUTOFF_SLACK_SECS)
+        };
+
+        let offers = self
+            .manager
+            .get_trade_offers_updated_since(cutoff)
+            .await?
+            .filter_by(|_| true);
+
+        let mut guard = self.manager.guard.lock().await;
+        let events = self
+            .state
+            .apply(offers, now)
+            .into_iter()
+            .map(|event| guard.screen(event))
+            .collect::<Vec<_>>();
+        let settled_acceptances = events
+            .iter()
+            .filter(|event| !event.offer().is_our_offer && guard.settles_acceptance(event))
+            .map(|event| event.offer().tradeofferid)
+            .collect::<HashSet<_>>();
+        drop(guard);
+        debug!("Trade offer poll finished with {} events.", events.len());
+
+        // offers answered by this account were already reported by the manager, except the received ones it accepted
+        // that were still waiting for a confirmation
+        for event in events
+            .iter()
+            .filter(|event| event.offer().is_our_offer || settled_acceptances.contains(&event.offer().tradeofferid))
+        {
+            let kind = match event {
+                TradeOfferEvent::Accepted(_) => OfferEventKind::Accepted,
+                TradeOfferEvent::Declined(_) => OfferEventKind::Declined,
+                TradeOfferEvent::EscrowStarted(_) => OfferEventKind::Escrowed,
+                _ => continue,
+            };
+            let offer = event.offer();
+            let partner = partner_of(offer);
+            self.manager.emit(kind, offer.tradeofferid, Some(partner)).await;
+        }
+        Ok(events)
+    }
+
+    /// Turns the poller into an endless stream of events, polling every `interval`.
+    ///
+    /// Failed polls are yielded as errors and retried on the next interval.
+    pub fn into_stream(self) -> impl Stream<Item = Result<TradeOfferEvent, TradeError>> + 'a {
+        let initial_state = (self, VecDeque::new(), true);
+
+        stream::unfold(
+            initial_state,
+            |(mut poller, mut pending, mut first_poll): (Self, VecDeque<TradeOfferEvent>, bool)| async move {
+                loop {
+                    if let Some(event) = pending.pop_front() {
+                        return Some((Ok(event), (poller, pending, first_poll)));
+                    }
+
+                    if !first_poll {
+                        // every event of the previous poll was consumed at this point
+                        if let Err(e) = poller.save_state() {
+                            return Some((Err(e), (poller, pending, first_poll)));
+                        }
+                        Delay::new(poller.interval).await;
+                    }
+                    first_poll = false;
+
+                    match poller.poll_once().await {
+                        Ok(events) => pending.extend(events),
+                        Err(e) => return Some((Err(e), (poller, pending, first_poll))),
+                    }
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::trade_offer;
+
+    const OFFER_TIME_UPDATED: u32 = 1_690_000_000;
+
+    fn offer(tradeofferid: u64, state: ETradeOfferState, is_our_offer: bool) -> TradeOffer_Trade {
+        trade_offer(
+            tradeofferid,
+            state,
+            serde_json::json!({"is_our_offer": is_our_offer, "time_updated": OFFER_TIME_UPDATED}),
+        )
+    }
+
+    #[test]
+    fn new_offers() {
+        let mut state = PollState::default();
+        let events = state.apply(
+            vec![
+                offer(1, ETradeOfferState::Active, false),
+                offer(2, ETradeOfferState::Active, true),
+                offer(3, ETradeOfferState::CreatedNeedsConfirmation, true),
+            ],
+            100,
+        );
+
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], TradeOfferEvent::NewReceived(_)));
+        assert!(matches!(events[1], TradeOfferEvent::NewSent(_)));
+        assert_eq!(state.offers.len(), 3);
+        assert_eq!(state.last_poll, 100);
+
+        // nothing changed
+        assert!(state
+            .apply(vec![offer(1, ETradeOfferState::Active, false)], 200)
+            .is_empty());
+
+        // created and canceled between two polls
+        let events = state.apply(vec![offer(4, ETradeOfferState::Canceled, true)], 300);
+        assert!(matches!(
+            events[0],
+            TradeOfferEvent::StateChanged { previous: None, .. }
+        ));
+    }
+
+    #[test]
+    fn countered_by_us() {
+        let mut state = PollState::default();
+        state.apply(vec![offer(1, ETradeOfferState::Active, false)], 100);
+
+        let events = state.apply(vec![offer(1, ETradeOfferState::Countered, false)], 200);
+        assert!(matches!(
+            events[0],
+            TradeOfferEvent::StateChanged {
+                previous: Some(ETradeOfferState::Active),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn state_transitions() {
+        let mut state = PollState::default();
+        state.apply(
+            vec![
+                offer(1, ETradeOfferState::Active, false),
+                offer(2, ETradeOfferState::Active, true),
+                offer(3, ETradeOfferState::Active, true),
+                offer(4, ETradeOfferState::Active, true),
+            ],
+            100,
+        );
+
+        let events = state.apply(
+            vec![
+                offer(1, ETradeOfferState::InEscrow, false),
+                offer(2, ETradeOfferState::Declined, true),
+                offer(3, ETradeOfferState::Countered, true),
+                offer(4, ETradeOfferState::Canceled, true),
+            ],
+            200,
+        );
+        assert!(matches!(events[0], TradeOfferEvent::EscrowStarted(_)));
+        assert!(matches!(events[1], TradeOfferEvent::Declined(_)));
+        assert!(matches!(events[2], TradeOfferEvent::Countered(_)));
+        assert!(matches!(
+            events[3],
+            TradeOfferEvent::StateChanged {
+                previous: Some(ETradeOfferState::Active),
+                ..
+            }
+        ));
+        // offers in a final state are kept while the next poll may still return them
+        assert_eq!(state.offers.len(), 4);
+        assert!(state
+            .apply(vec![offer(2, ETradeOfferState::Declined, true)], 200)
+            .is_empty());
+
+        // only the offer in escrow may still change
+        state.apply(vec![], OFFER_TIME_UPDATED + 3600);
+        assert_eq!(state.offers.len(), 1);
+
+        let events = state.apply(
+            vec![offer(1, ETradeOfferState::Accepted, false)],
+            OFFER_TIME_UPDATED + 3600,
+        );
+        assert!(matches!(events[0], TradeOfferEvent::EscrowEnded(_)));
+        state.apply(vec![], OFFER_TIME_UPDATED + 7200);
+        assert!(state.offers.is_empty());
+    }
+
+    #[test]
+    fn state_roundtrip() {
+        let mut state = PollState::default();
+        state.apply(vec![offer(1, ETradeOfferState::Active, false)], 100);
+
+        let path = std::env::temp_dir().join(format!("steam-trading-poll-{}.json", std::process::id()));
+        state.save(&path).unwrap();
+        assert_eq!(PollState::load(&path).unwrap(), state);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(PollState::load(&path).unwrap(), PollState::default());
+    }
+}
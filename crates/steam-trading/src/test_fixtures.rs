@@ -0,0 +1,62 @@
+//! Trade offers and inventory items shared by the tests of this crate.
+
+use serde_json::Value;
+use steam_language_gen::generated::enums::ETradeOfferState;
+use tappet::response_types::TradeOffer_Trade;
+
+use crate::inventory::InventoryItem;
+
+/// Adds the fields of the `overrides` object to the `base` object, replacing the ones already there.
+fn merge(mut base: Value, overrides: Value) -> Value {
+    if let (Some(base), Value::Object(overrides)) = (base.as_object_mut(), overrides) {
+        base.extend(overrides);
+    }
+    base
+}
+
+/// An offer sent by this account, as returned by the Web API, with the fields of `overrides` on top.
+pub(crate) fn trade_offer(tradeofferid: u64, state: ETradeOfferState, overrides: Value) -> TradeOffer_Trade {
+    let offer = serde_json::json!({
+        "tradeofferid": tradeofferid.to_string(),
+        "accountid_other": 79925588,
+        "message": "",
+        "expiration_time": 1700000000,
+        "trade_offer_state": state as i32,
+        "is_our_offer": true,
+        "time_created": 1690000000,
+        "time_updated": 1690000000,
+        "from_real_time_trade": false,
+        "escrow_end_date": 0,
+        "confirmation_method": 0
+    });
+    serde_json::from_value(merge(offer, overrides)).unwrap()
+}
+
+/// A tradable item of `appid` and `contextid`, with the fields of `description` on top of its description.
+pub(crate) fn inventory_item(
+    appid: u32,
+    contextid: u32,
+    assetid: i64,
+    classid: u64,
+    amount: u64,
+    description: Value,
+) -> InventoryItem {
+    let base = serde_json::json!({
+        "appid": appid,
+        "classid": classid.to_string(),
+        "instanceid": "0",
+        "name": format!("Item {}", classid),
+        "tradable": 1,
+        "marketable": 1
+    });
+
+    InventoryItem {
+        appid,
+        contextid,
+        assetid,
+        classid,
+        instanceid: 0,
+        amount,
+        description: Some(serde_json::from_value(merge(base, description)).unwrap()),
+    }
+}
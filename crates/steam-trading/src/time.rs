@@ -1,17 +1,30 @@
 //! Allows to estimate the end of the trade lock, i.e, the time you can trade again after trading an item.
 //!
 //! The alternative way to do this is to call the inventory endpoint and get the `cache_expiration`.
+//! [`TradabilityTracker`] combines both, along with the "Tradable After" line of the item description, and
+//! [`UnlockScheduler`] wakes up when a batch of items becomes tradable again.
 //!
 //! Can be enabled by adding the snippet below in your Cargo.toml:
 //! ```toml
 //! steam-trading = { version = "*", features = ["time"] }
 //! ```
 
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
 use chrono::DateTime;
 use chrono::Duration;
 use chrono::NaiveDateTime;
 use chrono::TimeZone;
 use chrono::Utc;
+use futures_timer::Delay;
+use lazy_static::lazy_static;
+use regex::Regex;
+use tappet::response_types::ETradeStatus;
+use tappet::response_types::TradeHistory_Trade;
+
+use crate::inventory::InventoryItem;
+use crate::ledger::AssetKey;
 
 pub const ONE_HOUR_SECONDS: i64 = 3600;
 pub const ONE_WEEK_SECONDS: i64 = ONE_HOUR_SECONDS * 24 * 7;
@@ -20,6 +33,13 @@ pub const ONE_WEEK_SECONDS: i64 = ONE_HOUR_SECONDS * 24 * 7;
 const STEAM_MIDNIGHT_OFFSET_UTC_SECONDS: i64 = ONE_HOUR_SECONDS * 18;
 const PST_TO_UTC_OFFSET_SECONDS: i64 = ONE_HOUR_SECONDS * 8;
 
+lazy_static! {
+    static ref TRADABLE_AFTER_REGEX: Regex = Regex::new(
+        r"Tradable(?:/Marketable)? After (?P<date>[A-Za-z]{3} \d{1,2}, \d{4}) \((?P<time>\d{1,2}:\d{2}:\d{2})\) GMT"
+    )
+    .unwrap();
+}
+
 /// Adds the Steam Midnight offset to a completed trade time epoch
 fn trade_time_with_offset(trade_complete_time_epoch: i64) -> DateTime<Utc> {
     // safe to unwrap
@@ -37,9 +57,209 @@ pub fn estimate_tradelock_end(trade_completed_on_epoch: i64, trade_lock_duration
     end_date + Duration::seconds(PST_TO_UTC_OFFSET_SECONDS)
 }
 
+/// Parses the "Tradable After Nov 10, 2020 (7:00:00) GMT" line shown to the owner of an item on trade hold.
+#[must_use]
+pub fn tradable_after_from_description(line: &str) -> Option<DateTime<Utc>> {
+    let captures = TRADABLE_AFTER_REGEX.captures(line)?;
+    let date_time = format!("{} {}", &captures["date"], &captures["time"]);
+    NaiveDateTime::parse_from_str(&date_time, "%b %d, %Y %H:%M:%S")
+        .ok()
+        .map(|date_time| date_time.and_utc())
+}
+
+/// Where the unlock time of an item comes from, most precise first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnlockSource {
+    /// `cache_expiration` of the item description.
+    CacheExpiration,
+    /// "Tradable After" line of the item description.
+    OwnerDescription,
+    /// Estimated with [`estimate_tradelock_end`] from the trade the item was received in.
+    TradeHistory,
+}
+
+/// When an item becomes tradable again.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ItemUnlock {
+    #[allow(missing_docs)]
+    pub asset: AssetKey,
+    #[allow(missing_docs)]
+    pub unlocks_at: DateTime<Utc>,
+    #[allow(missing_docs)]
+    pub source: UnlockSource,
+}
+
+/// Finds when inventory items become tradable again.
+///
+/// The inventory is trusted first. Items without an unlock time there, but received in a trade recorded with
+/// [`Self::record_trade`], get an estimation of the end of their trade lock.
+#[derive(Debug, Clone)]
+pub struct TradabilityTracker {
+    trade_lock_seconds: i64,
+    /// Assets received in a trade, with the time the trade completed.
+    received: HashMap<AssetKey, i64>,
+}
+
+impl Default for TradabilityTracker {
+    fn default() -> Self {
+        Self::new(ONE_WEEK_SECONDS)
+    }
+}
+
+impl TradabilityTracker {
+    /// Tracker for items that are locked for `trade_lock_seconds` after being traded.
+    pub fn new(trade_lock_seconds: i64) -> Self {
+        Self {
+            trade_lock_seconds,
+            received: HashMap::new(),
+        }
+    }
+
+    /// Remembers the assets received in `trade`, if it completed.
+    pub fn record_trade(&mut self, trade: &TradeHistory_Trade) {
+        if !matches!(
+            trade.status,
+            ETradeStatus::Committed | ETradeStatus::Complete | ETradeStatus::InEscrow
+        ) {
+            return;
+        }
+
+        // items in escrow are locked once they are delivered
+        let completed_at = trade.time_escrow_end.filter(|end| *end > 0).unwrap_or(trade.time_init);
+        for asset in trade.assets_received.iter().flatten() {
+            let key = AssetKey::new(asset.appid, asset.new_contextid, asset.new_assetid);
+            self.received.insert(key, completed_at);
+        }
+    }
+
+    /// Same as [`Self::record_trade`], for every trade of `trades`.
+    pub fn record_trades<'t>(&mut self, trades: impl IntoIterator<Item = &'t TradeHistory_Trade>) {
+        trades.into_iter().for_each(|trade| self.record_trade(trade));
+    }
+
+    /// When `item` becomes tradable again, or `None` if it is tradable or the time is unknown.
+    ///
+    /// Unlock times already in the past are returned too, since the inventory may be cached.
+    pub fn unlock_of(&self, item: &InventoryItem) -> Option<ItemUnlock> {
+        if item.is_tradable() {
+            return None;
+        }
+
+        let asset = AssetKey::new(item.appid, item.contextid, item.assetid);
+        let unlock = |unlocks_at, source| ItemUnlock {
+            asset,
+            unlocks_at,
+            source,
+        };
+        let description = item.description.as_ref();
+
+        let from_cache = description
+            .and_then(|description| description.cache_expiration.as_deref())
+            .and_then(|expiration| DateTime::parse_from_rfc3339(expiration).ok())
+            .map(|expiration| unlock(expiration.with_timezone(&Utc), UnlockSource::CacheExpiration));
+        let from_description = || {
+            description
+                .into_iter()
+                .flat_map(|description| description.owner_descriptions.iter())
+                .find_map(|line| tradable_after_from_description(&line.value))
+                .map(|unlocks_at| unlock(unlocks_at, UnlockSource::OwnerDescription))
+        };
+        let from_history = || {
+            self.received.get(&asset).map(|completed_at| {
+                let unlocks_at = estimate_tradelock_end(*completed_at, self.trade_lock_seconds).and_utc();
+                unlock(unlocks_at, UnlockSource::TradeHistory)
+            })
+        };
+
+        from_cache.or_else(from_description).or_else(from_history)
+    }
+
+    /// Unlock times of every locked item of `items` that has one, the earliest first.
+    pub fn unlocks<'i>(&self, items: impl IntoIterator<Item = &'i InventoryItem>) -> Vec<ItemUnlock> {
+        let mut unlocks = items
+            .into_iter()
+            .filter_map(|item| self.unlock_of(item))
+            .collect::<Vec<_>>();
+        unlocks.sort_by_key(|unlock| unlock.unlocks_at);
+        unlocks
+    }
+}
+
+/// Items that became tradable, returned by [`UnlockScheduler::next_batch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnlockBatch {
+    /// Unlock time of the last item of the batch.
+    pub unlocked_at: DateTime<Utc>,
+    #[allow(missing_docs)]
+    pub assets: Vec<AssetKey>,
+}
+
+/// Wakes up when scheduled items become tradable, a batch at a time.
+///
+/// Items unlocking within `batch_window` of the first pending one are returned together, so a bot wakes up once for
+/// items received in the same trade, or on the same day.
+#[derive(Debug, Clone)]
+pub struct UnlockScheduler {
+    batch_window: Duration,
+    pending: BTreeMap<DateTime<Utc>, Vec<AssetKey>>,
+}
+
+impl UnlockScheduler {
+    #[allow(missing_docs)]
+    pub fn new(batch_window: Duration) -> Self {
+        Self {
+            batch_window,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Adds items to wake up for. Usually the ones returned by [`TradabilityTracker::unlocks`].
+    pub fn schedule(&mut self, unlocks: impl IntoIterator<Item = ItemUnlock>) {
+        for unlock in unlocks {
+            self.pending.entry(unlock.unlocks_at).or_default().push(unlock.asset);
+        }
+    }
+
+    /// When the next batch is ready, if any item is pending.
+    pub fn next_wake_up(&self) -> Option<DateTime<Utc>> {
+        let first = *self.pending.keys().next()?;
+        self.pending
+            .range(..=first + self.batch_window)
+            .next_back()
+            .map(|(unlocks_at, _)| *unlocks_at)
+    }
+
+    #[allow(missing_docs)]
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Removes the next batch, if it is already unlocked at `now`.
+    pub fn take_ready(&mut self, now: DateTime<Utc>) -> Option<UnlockBatch> {
+        let unlocked_at = self.next_wake_up().filter(|wake_up| *wake_up <= now)?;
+        let later = self.pending.split_off(&(unlocked_at + Duration::nanoseconds(1)));
+        let batch = std::mem::replace(&mut self.pending, later);
+
+        Some(UnlockBatch {
+            unlocked_at,
+            assets: batch.into_values().flatten().collect(),
+        })
+    }
+
+    /// Waits until the next batch is unlocked, and removes it. `None` if nothing is pending.
+    pub async fn next_batch(&mut self) -> Option<UnlockBatch> {
+        let wake_up = self.next_wake_up()?;
+        if let Ok(wait) = (wake_up - Utc::now()).to_std() {
+            Delay::new(wait).await;
+        }
+        self.take_ready(wake_up.max(Utc::now()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_fixtures::inventory_item;
 
     fn trade_complete_time_sample() -> i64 {
         1603998438
@@ -54,4 +274,80 @@ mod tests {
         let estimated = estimate_tradelock_end(trade_complete_time_sample(), ONE_WEEK_SECONDS);
         assert_eq!(estimated.timestamp(), expected_tradelock_end());
     }
+
+    fn locked_item(assetid: i64, mut description: serde_json::Value) -> InventoryItem {
+        description
+            .as_object_mut()
+            .unwrap()
+            .insert("tradable".to_string(), 0.into());
+        inventory_item(730, 2, assetid, 1989330488, 1, description)
+    }
+
+    #[test]
+    fn unlock_times() {
+        assert_eq!(
+            tradable_after_from_description("Tradable/Marketable After Nov 10, 2020 (7:00:00) GMT"),
+            Some(Utc.with_ymd_and_hms(2020, 11, 10, 7, 0, 0).unwrap())
+        );
+        assert_eq!(tradable_after_from_description("Exterior: Field-Tested"), None);
+
+        let trade: TradeHistory_Trade = serde_json::from_value(serde_json::json!({
+            "tradeid": "3622543526924228084",
+            "steamid_other": "76561198040191316",
+            "time_init": trade_complete_time_sample(),
+            "status": 3,
+            "assets_received": [{
+                "appid": 730, "contextid": "2", "assetid": "17034419698", "amount": "1", "classid": "1",
+                "instanceid": "0", "new_assetid": "19034292090", "new_contextid": "2"
+            }]
+        }))
+        .unwrap();
+        let mut tracker = TradabilityTracker::default();
+        tracker.record_trade(&trade);
+
+        let items = [
+            locked_item(
+                19034292089,
+                serde_json::json!({"cache_expiration": "2020-11-11T07:00:00Z"}),
+            ),
+            locked_item(
+                19034292091,
+                serde_json::json!({"owner_descriptions": [{"type": "html", "value": "Tradable After Nov 10, 2020 (7:00:00) GMT"}]}),
+            ),
+            locked_item(19034292090, serde_json::json!({})),
+            locked_item(19034292092, serde_json::json!({})),
+        ];
+        let unlocks = tracker.unlocks(&items);
+
+        assert_eq!(unlocks.len(), 3);
+        assert_eq!(unlocks[0].source, UnlockSource::TradeHistory);
+        assert_eq!(
+            unlocks[0].unlocks_at,
+            estimate_tradelock_end(trade_complete_time_sample(), ONE_WEEK_SECONDS).and_utc()
+        );
+        assert_eq!(unlocks[1].source, UnlockSource::OwnerDescription);
+        assert_eq!(unlocks[2].source, UnlockSource::CacheExpiration);
+        assert_eq!(unlocks[2].asset.assetid, 19034292089);
+    }
+
+    #[test]
+    fn unlock_batches() {
+        let at = |hour| Utc.with_ymd_and_hms(2020, 11, 10, hour, 0, 0).unwrap();
+        let unlock = |assetid, hour| ItemUnlock {
+            asset: AssetKey::new(730, 2, assetid),
+            unlocks_at: at(hour),
+            source: UnlockSource::CacheExpiration,
+        };
+
+        let mut scheduler = UnlockScheduler::new(Duration::hours(1));
+        scheduler.schedule([unlock(1, 7), unlock(2, 8), unlock(3, 12)]);
+        assert_eq!(scheduler.next_wake_up(), Some(at(8)));
+        assert_eq!(scheduler.take_ready(at(7)), None);
+
+        let batch = scheduler.take_ready(at(9)).unwrap();
+        assert_eq!(batch.unlocked_at, at(8));
+        assert_eq!(batch.assets.len(), 2);
+        assert_eq!(scheduler.next_wake_up(), Some(at(12)));
+        assert!(!scheduler.is_empty());
+    }
 }
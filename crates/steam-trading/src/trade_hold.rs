@@ -0,0 +1,147 @@
+//! Trade hold (escrow) checks, to find out beforehand if the items of an offer would be held by Steam.
+//!
+//! Durations are retrieved from `IEconService/GetTradeHoldDurations`. If the Web API doesn't answer them, they are
+//! parsed from the `g_daysMyEscrow` and `g_daysTheirEscrow` variables of the new trade offer page instead.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use serde::Serialize;
+use steam_mobile::Method;
+use tappet::response_types::EscrowData;
+use tappet::response_types::GetTradeHoldDurations;
+
+use crate::errors::InternalError;
+use crate::types::trade_link::new_offer_page;
+use crate::OfferError;
+use crate::SteamCompleteAuthenticator;
+use crate::TradeError;
+use crate::Tradelink;
+use crate::TryFutureExt;
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+lazy_static! {
+    static ref MY_ESCROW_REGEX: Regex = Regex::new(r"var g_daysMyEscrow = (?P<days>\d+);").unwrap();
+    static ref THEIR_ESCROW_REGEX: Regex = Regex::new(r"var g_daysTheirEscrow = (?P<days>\d+);").unwrap();
+}
+
+/// For how many days the items of a trade would be held by Steam, for each side.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TradeHoldDurations {
+    /// Days the items we give are held.
+    pub my_escrow_days: u32,
+    /// Days the items we receive are held.
+    pub their_escrow_days: u32,
+}
+
+impl TradeHoldDurations {
+    /// Whether any side of the trade would be held.
+    pub fn is_held(&self) -> bool {
+        self.my_escrow_days > 0 || self.their_escrow_days > 0
+    }
+
+    /// Returns [`OfferError::TradeHold`] if the trade would be held.
+    pub fn ensure_not_held(&self) -> Result<(), OfferError> {
+        if self.is_held() {
+            return Err(OfferError::TradeHold {
+                my_escrow_days: self.my_escrow_days,
+                their_escrow_days: self.their_escrow_days,
+            });
+        }
+        Ok(())
+    }
+
+    /// Reads the Web API response. `None` if the partner token was not accepted.
+    pub(crate) fn from_api(response: GetTradeHoldDurations) -> Option<Self> {
+        let to_days = |escrow: EscrowData| {
+            let days = (escrow.escrow_end_duration_seconds.max(0) + SECONDS_PER_DAY - 1) / SECONDS_PER_DAY;
+            u32::try_from(days).unwrap_or(u32::MAX)
+        };
+
+        Some(Self {
+            my_escrow_days: to_days(response.my_escrow?),
+            their_escrow_days: to_days(response.their_escrow?),
+        })
+    }
+
+    /// Reads the durations from the new trade offer page.
+    pub(crate) fn from_page(document: &str) -> Option<Self> {
+        let days = |regex: &Regex| {
+            regex
+                .captures(document)
+                .and_then(|captures| captures.name("days"))
+                .and_then(|days| days.as_str().parse::<u32>().ok())
+        };
+
+        Some(Self {
+            my_escrow_days: days(&MY_ESCROW_REGEX)?,
+            their_escrow_days: days(&THEIR_ESCROW_REGEX)?,
+        })
+    }
+}
+
+/// Fetches the new trade offer page of `tradelink` and parses the trade hold durations from it.
+pub(crate) async fn trade_hold_from_page(
+    authenticator: &SteamCompleteAuthenticator,
+    tradelink: &Tradelink,
+) -> Result<TradeHoldDurations, TradeError> {
+    let endpoint = new_offer_page(&tradelink.partner_id, &tradelink.token);
+
+    let response = authenticator
+        .request_custom_endpoint(endpoint, Method::GET, None, None::<&u8>)
+        .err_into::<InternalError>()
+        .await?;
+    let text = response.text().err_into::<InternalError>().await?;
+
+    TradeHoldDurations::from_page(&text).ok_or_else(|| {
+        OfferError::GeneralFailure("Could not find the trade hold durations on the trade offer page.".to_string())
+            .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_from_api() {
+        let response: GetTradeHoldDurations = serde_json::from_str(
+            r#"{
+                "my_escrow": {"escrow_end_duration_seconds": 0},
+                "their_escrow": {"escrow_end_duration_seconds": 1296000, "escrow_end_date": 1700000000},
+                "both_escrow": {"escrow_end_duration_seconds": 1296000}
+            }"#,
+        )
+        .unwrap();
+
+        let durations = TradeHoldDurations::from_api(response).unwrap();
+        assert_eq!(durations.my_escrow_days, 0);
+        assert_eq!(durations.their_escrow_days, 15);
+        assert!(durations.is_held());
+        assert_eq!(
+            durations.ensure_not_held(),
+            Err(OfferError::TradeHold {
+                my_escrow_days: 0,
+                their_escrow_days: 15
+            })
+        );
+
+        assert!(TradeHoldDurations::from_api(GetTradeHoldDurations::default()).is_none());
+    }
+
+    #[test]
+    fn durations_from_page() {
+        let page = r#"
+            <script type="text/javascript">
+                var g_bTradePartnerProbation = false;
+                var g_daysMyEscrow = 0;
+                var g_daysTheirEscrow = 2;
+            </script>"#;
+
+        let durations = TradeHoldDurations::from_page(page).unwrap();
+        assert_eq!(durations.my_escrow_days, 0);
+        assert_eq!(durations.their_escrow_days, 2);
+        assert!(TradeHoldDurations::from_page("<html></html>").is_none());
+    }
+}
@@ -12,10 +12,15 @@ impl AssetCollection {
     }
 
     pub fn add(&mut self, appid: u32, contextid: u32, assetid: i64) {
+        self.add_amount(appid, contextid, assetid, 1);
+    }
+
+    /// Adds `amount` of a stackable asset, such as gems.
+    pub fn add_amount(&mut self, appid: u32, contextid: u32, assetid: i64, amount: u64) {
         let asset = Asset {
             appid,
             contextid: contextid.to_string(),
-            amount: 1,
+            amount: amount as i64,
             assetid: assetid.to_string(),
         };
 
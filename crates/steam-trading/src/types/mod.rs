@@ -1,3 +1,4 @@
+use crate::types::trade_offer_web::TradeOfferCreateRequest;
 use crate::TradeOffer;
 use crate::TRADEOFFER_BASE;
 use crate::TRADEOFFER_NEW_URL;
@@ -9,16 +10,18 @@ pub mod trade_offer;
 pub mod trade_offer_web;
 
 #[derive(Debug, PartialEq)]
-pub enum TradeKind {
+pub(crate) enum TradeKind {
     Accept,
     Cancel,
     Create(TradeOffer),
+    /// Counter offer of the received offer passed as `tradeofferid`.
+    Counter(TradeOfferCreateRequest),
     Decline,
 }
 
 impl TradeKind {
     pub fn endpoint(&self, tradeofferid: Option<u64>) -> String {
-        if let TradeKind::Create(_) = self {
+        if let TradeKind::Create(_) | TradeKind::Counter(_) = self {
             return TRADEOFFER_NEW_URL.to_string();
         }
 
@@ -1,7 +1,13 @@
 use erased_serde::serialize_trait_object;
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::Deserialize;
 use serde::Serialize;
 
+lazy_static! {
+    static ref SESSION_ID_REGEX: Regex = Regex::new(r#"g_sessionID = "(?P<sessionid>[0-9a-f]+)""#).unwrap();
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SessionID {
     pub sessionid: String,
@@ -12,3 +18,23 @@ pub(crate) trait HasSessionID: erased_serde::Serialize + Send + Sync {
 }
 
 serialize_trait_object!(HasSessionID);
+
+/// The `sessionid` that a Steam Community page expects on its forms.
+pub(crate) fn session_id_from_page(page: &str) -> Option<&str> {
+    SESSION_ID_REGEX
+        .captures(page)
+        .and_then(|captures| captures.name("sessionid"))
+        .map(|sessionid| sessionid.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_id_of_page() {
+        let page = r#"<script>g_steamID = "76561198017653157"; g_sessionID = "9d3b9a25d2e6b04c3f6ac3b3";</script>"#;
+        assert_eq!(session_id_from_page(page), Some("9d3b9a25d2e6b04c3f6ac3b3"));
+        assert_eq!(session_id_from_page("<script>g_steamID = false;</script>"), None);
+    }
+}
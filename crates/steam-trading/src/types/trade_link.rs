@@ -7,10 +7,11 @@ use serde::{Deserialize, Serialize};
 use steamid_parser::SteamID;
 
 use crate::errors::TradelinkError;
+use crate::TRADEOFFER_BASE;
 
 lazy_static! {
     static ref TRADE_LINK_REGEX: Regex = Regex::new(
-        r#"https://steamcommunity\.com/tradeoffer/new/\?partner=(?P<partner>[\d]+)&token=(?P<token>[\w-]+)"#
+        r#"https://steamcommunity\.com/tradeoffer/new/\?partner=(?P<partner>[\d]+)(&token=(?P<token>[\w-]+))?"#
     )
     .unwrap();
 }
@@ -20,7 +21,8 @@ lazy_static! {
 ///
 /// # Notes
 /// A tradelink does not need a token if is from a friend.
-/// But Steam always generates one with a token, and it is also kind of pointless to remove the token, so they are standardized to have it.
+/// Steam always generates one with a token, but links without it are accepted too, and have an empty `token`. Check
+/// [`Tradelink::friend`].
 pub struct Tradelink {
     pub link: String,
     pub partner_id: SteamID,
@@ -71,6 +73,20 @@ impl Tradelink {
         Ok(())
     }
 
+    /// Tradelink of a friend, which doesn't need a token.
+    pub fn friend(partner_id: SteamID) -> Self {
+        Self {
+            link: new_offer_page(&partner_id, ""),
+            partner_id,
+            token: String::new(),
+        }
+    }
+
+    /// Whether this tradelink has no token, as the ones made with [`Self::friend`].
+    pub fn is_friend(&self) -> bool {
+        self.token.is_empty()
+    }
+
     pub fn new(trade_link: String) -> Result<Self, TradelinkError> {
         Self::validate(&*trade_link)?;
 
@@ -81,7 +97,7 @@ impl Tradelink {
             .map(|partner_id| SteamID::from_steam3(partner_id, None, None))
             .ok_or_else(|| TradelinkError::Invalid)?;
 
-        let token = captures.name("token").unwrap().as_str();
+        let token = captures.name("token").map_or("", |token| token.as_str());
 
         Ok(Self {
             partner_id,
@@ -91,6 +107,16 @@ impl Tradelink {
     }
 }
 
+/// New trade offer page for `partner`, with `token` unless it is empty.
+pub(crate) fn new_offer_page(partner: &SteamID, token: &str) -> String {
+    let mut page = format!("{}new/?partner={}", TRADEOFFER_BASE, partner.to_steam3());
+    if !token.is_empty() {
+        page.push_str("&token=");
+        page.push_str(token);
+    }
+    page
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,6 +165,22 @@ mod tests {
         assert_eq!(result, Ok(()))
     }
 
+    #[test]
+    fn friend_tradelink() {
+        let tradelink =
+            Tradelink::new("https://steamcommunity.com/tradeoffer/new/?partner=24569668".to_string()).unwrap();
+        assert!(tradelink.is_friend());
+        assert_eq!(tradelink.partner_id.to_steam64(), valid_steamid());
+        assert_eq!(Tradelink::friend(tradelink.partner_id.clone()), tradelink);
+
+        let tradelink = Tradelink::new(get_valid_tradelink().to_string()).unwrap();
+        assert_eq!(tradelink.token, "vnFisKdN");
+        assert_eq!(
+            new_offer_page(&tradelink.partner_id, &tradelink.token),
+            get_valid_tradelink()
+        );
+    }
+
     #[test]
     fn invalid_tradelink() {
         let result = Tradelink::validate(get_google());
@@ -1,11 +1,60 @@
+use std::collections::HashSet;
 use std::convert::TryInto;
 
+use steam_language_gen::generated::enums::ETradeOfferState;
+use steamid_parser::SteamID;
+use tappet::response_types::TradeOffer_Trade;
 use tracing::info;
 
+use crate::errors::OfferError;
 use crate::errors::OfferValidationError;
+use crate::inventory::InventoryItem;
 use crate::types::asset_collection::AssetCollection;
 use crate::Tradelink;
 use crate::TRADE_MAX_ITEMS;
+use crate::TRADE_MAX_MESSAGE_LENGTH;
+
+/// What happened to a trade offer after we accepted it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcceptOutcome {
+    /// The items were exchanged.
+    Completed {
+        /// Id of the resulting trade, used to find the new asset ids on the trade history.
+        tradeid: Option<String>,
+    },
+    /// Steam is still waiting for a confirmation. Either an email confirmation, which can't be done by this library,
+    /// or a mobile confirmation that Steam didn't process yet.
+    PendingConfirmation {
+        /// Domain of the email address the confirmation was sent to, if it was an email one.
+        email_domain: Option<String>,
+    },
+    /// The items are held by Steam until `escrow_end_date`.
+    InEscrow {
+        #[allow(missing_docs)]
+        tradeid: Option<String>,
+        /// Unix timestamp of when the items are delivered.
+        escrow_end_date: i64,
+    },
+}
+
+impl AcceptOutcome {
+    /// Describes `offer`, as fetched right after it was accepted.
+    pub(crate) fn from_offer(offer: TradeOffer_Trade, tradeid: Option<String>) -> Result<Self, OfferError> {
+        let tradeid = offer.tradeid.or(tradeid);
+
+        match offer.state {
+            ETradeOfferState::Accepted => Ok(Self::Completed { tradeid }),
+            ETradeOfferState::InEscrow => Ok(Self::InEscrow {
+                tradeid,
+                escrow_end_date: offer.escrow_end_date,
+            }),
+            ETradeOfferState::Active | ETradeOfferState::CreatedNeedsConfirmation => {
+                Ok(Self::PendingConfirmation { email_domain: None })
+            }
+            _ => Err(OfferError::InvalidState),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub struct TradeOffer {
@@ -17,6 +66,9 @@ pub struct TradeOffer {
     pub their_assets: Option<AssetCollection>,
     /// Optional trade offer message.
     pub message: String,
+    /// Refuses to send the offer if its items would be held by Steam. Check
+    /// [`crate::SteamTradeManager::get_trade_hold_durations`].
+    pub refuse_trade_hold: bool,
 }
 
 impl TradeOffer {
@@ -38,9 +90,16 @@ impl TradeOffer {
             my_assets: my_assets.into(),
             their_assets: their_assets.into(),
             message: message.into().unwrap_or(String::new()),
+            refuse_trade_hold: false,
         })
     }
 
+    /// Refuses to send this offer if its items would be held by Steam.
+    pub fn refusing_trade_hold(mut self) -> Self {
+        self.refuse_trade_hold = true;
+        self
+    }
+
     /// Validates if at least one item is being traded or if it exceeds the 255 items limit;
     pub fn validate(
         my_items: &Option<AssetCollection>,
@@ -68,3 +127,227 @@ impl TradeOffer {
         Ok(())
     }
 }
+
+/// Builds a [`TradeOffer`] from inventory items, validating each of them.
+///
+/// Items are added with [`Self::give`] and [`Self::receive`], and checked once on [`Self::build`]: every item must be
+/// tradable and added once per side, amounts must be available, and the message must fit
+/// [`TRADE_MAX_MESSAGE_LENGTH`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeOfferBuilder {
+    partner: Tradelink,
+    my_items: Vec<(InventoryItem, u64)>,
+    their_items: Vec<(InventoryItem, u64)>,
+    message: String,
+    refuse_trade_hold: bool,
+}
+
+impl TradeOfferBuilder {
+    /// Offer to the owner of `tradelink`.
+    pub fn new(tradelink: Tradelink) -> Self {
+        Self {
+            partner: tradelink,
+            my_items: vec![],
+            their_items: vec![],
+            message: String::new(),
+            refuse_trade_hold: false,
+        }
+    }
+
+    /// Offer to a friend, which doesn't need a tradelink.
+    pub fn to_friend(partner_id: SteamID) -> Self {
+        Self::new(Tradelink::friend(partner_id))
+    }
+
+    /// Gives the whole `item`, every unit if stackable.
+    pub fn give(self, item: &InventoryItem) -> Self {
+        let amount = item.amount;
+        self.give_amount(item, amount)
+    }
+
+    /// Gives `amount` units of a stackable `item`.
+    pub fn give_amount(mut self, item: &InventoryItem, amount: u64) -> Self {
+        self.my_items.push((item.clone(), amount));
+        self
+    }
+
+    /// Asks for the whole `item`, every unit if stackable.
+    pub fn receive(self, item: &InventoryItem) -> Self {
+        let amount = item.amount;
+        self.receive_amount(item, amount)
+    }
+
+    /// Asks for `amount` units of a stackable `item`.
+    pub fn receive_amount(mut self, item: &InventoryItem, amount: u64) -> Self {
+        self.their_items.push((item.clone(), amount));
+        self
+    }
+
+    #[allow(missing_docs)]
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = message.into();
+        self
+    }
+
+    /// Check [`TradeOffer::refusing_trade_hold`].
+    pub fn refusing_trade_hold(mut self) -> Self {
+        self.refuse_trade_hold = true;
+        self
+    }
+
+    /// Validates every item and the message, producing the offer.
+    pub fn build(self) -> Result<TradeOffer, OfferValidationError> {
+        let length = self.message.chars().count();
+        if length > TRADE_MAX_MESSAGE_LENGTH {
+            return Err(OfferValidationError::MessageTooLong {
+                length,
+                max: TRADE_MAX_MESSAGE_LENGTH,
+            });
+        }
+
+        let my_assets = Self::collect(&self.my_items)?;
+        let their_assets = Self::collect(&self.their_items)?;
+        TradeOffer::validate(&my_assets, &their_assets)?;
+
+        Ok(TradeOffer {
+            their_tradelink: self.partner,
+            my_assets,
+            their_assets,
+            message: self.message,
+            refuse_trade_hold: self.refuse_trade_hold,
+        })
+    }
+
+    /// Validates the items of one side of the trade. `None` if there are none.
+    fn collect(items: &[(InventoryItem, u64)]) -> Result<Option<AssetCollection>, OfferValidationError> {
+        let mut seen = HashSet::new();
+        let mut collection = AssetCollection::default();
+
+        for (item, amount) in items {
+            if !item.is_tradable() {
+                return Err(OfferValidationError::NotTradable(item.assetid));
+            }
+            if !seen.insert((item.appid, item.contextid, item.assetid)) {
+                return Err(OfferValidationError::DuplicateAsset(item.assetid));
+            }
+            if *amount == 0 || *amount > item.amount {
+                return Err(OfferValidationError::InvalidAmount {
+                    assetid: item.assetid,
+                    amount: *amount,
+                    available: item.amount,
+                });
+            }
+            collection.add_amount(item.appid, item.contextid, item.assetid, *amount);
+        }
+
+        if items.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(collection))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::inventory_item;
+    use crate::test_fixtures::trade_offer;
+
+    fn accepted_offer(state: ETradeOfferState, tradeid: Option<&str>) -> TradeOffer_Trade {
+        trade_offer(
+            4127395150,
+            state,
+            serde_json::json!({
+                "is_our_offer": false,
+                "time_updated": 1690000100,
+                "tradeid": tradeid,
+                "escrow_end_date": 1690600000,
+                "confirmation_method": 2
+            }),
+        )
+    }
+
+    fn item(assetid: i64, amount: u64, tradable: bool) -> InventoryItem {
+        inventory_item(
+            753,
+            6,
+            assetid,
+            667924416,
+            amount,
+            serde_json::json!({"tradable": tradable as u8}),
+        )
+    }
+
+    fn builder() -> TradeOfferBuilder {
+        TradeOfferBuilder::to_friend(SteamID::from_steam64(76561198040191316))
+    }
+
+    #[test]
+    fn builds_offer() {
+        let offer = builder()
+            .give_amount(&item(1, 500, true), 200)
+            .receive(&item(2, 1, true))
+            .message("gems")
+            .build()
+            .unwrap();
+
+        assert!(offer.their_tradelink.is_friend());
+        assert_eq!(offer.my_assets.as_ref().unwrap().0[0].amount, 200);
+        assert_eq!(offer.their_assets.as_ref().unwrap().0.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_items() {
+        let not_tradable = builder().give(&item(1, 1, false)).build();
+        assert_eq!(not_tradable, Err(OfferValidationError::NotTradable(1)));
+
+        let duplicate = builder().give(&item(1, 1, true)).give(&item(1, 1, true)).build();
+        assert_eq!(duplicate, Err(OfferValidationError::DuplicateAsset(1)));
+
+        let too_many = builder().give_amount(&item(1, 10, true), 11).build();
+        assert_eq!(
+            too_many,
+            Err(OfferValidationError::InvalidAmount {
+                assetid: 1,
+                amount: 11,
+                available: 10
+            })
+        );
+
+        let message = "a".repeat(TRADE_MAX_MESSAGE_LENGTH + 1);
+        let long_message = builder().give(&item(1, 1, true)).message(message).build();
+        assert!(matches!(long_message, Err(OfferValidationError::MessageTooLong { .. })));
+
+        assert!(builder().build().is_err());
+    }
+
+    #[test]
+    fn accept_outcome() {
+        let outcome = AcceptOutcome::from_offer(
+            accepted_offer(ETradeOfferState::Accepted, Some("3622543526924228084")),
+            None,
+        );
+        assert_eq!(
+            outcome,
+            Ok(AcceptOutcome::Completed {
+                tradeid: Some("3622543526924228084".to_string())
+            })
+        );
+
+        let outcome =
+            AcceptOutcome::from_offer(accepted_offer(ETradeOfferState::InEscrow, None), Some("1".to_string()));
+        assert_eq!(
+            outcome,
+            Ok(AcceptOutcome::InEscrow {
+                tradeid: Some("1".to_string()),
+                escrow_end_date: 1_690_600_000
+            })
+        );
+
+        let outcome = AcceptOutcome::from_offer(accepted_offer(ETradeOfferState::Active, None), None);
+        assert_eq!(outcome, Ok(AcceptOutcome::PendingConfirmation { email_domain: None }));
+
+        let outcome = AcceptOutcome::from_offer(accepted_offer(ETradeOfferState::Canceled, None), None);
+        assert_eq!(outcome, Err(OfferError::InvalidState));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Deserialize;
 use serde::Serialize;
-use serde_with::{serde_as, json::JsonString, DisplayFromStr};
+use serde_with::{serde_as, json::JsonString, DefaultOnError, DisplayFromStr};
 use steam_language_gen::generated::enums::EResult;
 
 use crate::types::sessionid::HasSessionID;
@@ -45,9 +45,12 @@ pub(crate) struct TradeOfferGenericRequest {
     pub sessionid: SessionID,
 }
 
+#[serde_as]
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub(crate) struct TradeOfferGenericErrorResponse {
-    #[serde(rename = "success")]
+    /// `None` if missing, or if it is not an EResult, such as `true`.
+    #[serde(rename = "success", default)]
+    #[serde_as(as = "DefaultOnError")]
     pub eresult: Option<EResult>,
     #[serde(rename = "strError")]
     pub error_message: Option<String>,
@@ -76,6 +79,16 @@ pub struct TradeOfferCreateResponse {
     pub email_domain: Option<String>,
 }
 
+/// Response after we accept a trade offer sent to us.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TradeOfferAcceptResponse {
+    /// Only present if the items were exchanged right away.
+    pub tradeid: Option<String>,
+    pub needs_mobile_confirmation: Option<bool>,
+    pub needs_email_confirmation: Option<bool>,
+    pub email_domain: Option<String>,
+}
+
 /// Response after we cancel an trade offer we've sent.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TradeOfferCancelResponse {
@@ -103,6 +116,9 @@ pub(crate) struct TradeOfferCreateRequest {
     /// If we intend to create a trade offer based on a trade partner link, we need to send the
     /// trade access token with it.
     pub trade_offer_create_params: Option<TradeOfferParams>,
+    /// Id of the received offer this one replaces, if it is a counter offer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tradeofferid_countered: Option<u64>,
 }
 
 impl TradeOfferCreateRequest {
@@ -120,15 +136,40 @@ impl TradeOfferCreateRequest {
             message: tradeoffer.message.clone(),
             json_tradeoffer: tradeoffer.into(),
             trade_offer_create_params: trade_token.into(),
+            tradeofferid_countered: None,
+        }
+    }
+
+    /// Counter offer of the received offer `tradeofferid_countered`.
+    ///
+    /// No access token is needed, since the partner already sent us an offer.
+    pub(crate) fn counter(
+        their_steamid64: u64,
+        tradeofferid_countered: u64,
+        my_assets: Option<AssetCollection>,
+        their_assets: Option<AssetCollection>,
+        message: String,
+    ) -> Self {
+        Self {
+            sessionid: Default::default(),
+            common: TradeOfferCommonParameters {
+                their_steamid: their_steamid64,
+                ..Default::default()
+            },
+            message,
+            json_tradeoffer: JsonTradeOffer::from_assets(my_assets, their_assets),
+            trade_offer_create_params: Some(TradeOfferParams::default()),
+            tradeofferid_countered: Some(tradeofferid_countered),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub(crate) struct TradeOfferParams {
     /// A trade offer link has an unique token that the user can invalidate at any time.
     /// We need to insert this token correct at the request.
-    pub trade_offer_access_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trade_offer_access_token: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -142,15 +183,13 @@ pub(crate) struct JsonTradeOffer {
     pub their_account: AssetList,
 }
 
-impl From<TradeOffer> for JsonTradeOffer {
-    fn from(tradeoffer: TradeOffer) -> Self {
-        let my_account = tradeoffer
-            .my_assets
+impl JsonTradeOffer {
+    fn from_assets(my_assets: Option<AssetCollection>, their_assets: Option<AssetCollection>) -> Self {
+        let my_account = my_assets
             .unwrap_or_else(|| AssetCollection::default())
             .dump_to_asset_list();
 
-        let their_account = tradeoffer
-            .their_assets
+        let their_account = their_assets
             .unwrap_or_else(|| AssetCollection::default())
             .dump_to_asset_list();
 
@@ -162,6 +201,12 @@ impl From<TradeOffer> for JsonTradeOffer {
     }
 }
 
+impl From<TradeOffer> for JsonTradeOffer {
+    fn from(tradeoffer: TradeOffer) -> Self {
+        Self::from_assets(tradeoffer.my_assets, tradeoffer.their_assets)
+    }
+}
+
 impl Default for JsonTradeOffer {
     fn default() -> Self {
         Self {
@@ -250,4 +295,24 @@ mod tests {
 }"#;
         serde_json::from_str::<JsonTradeOffer>(json_request).unwrap()
     }
+
+    #[test]
+    fn counter_request() {
+        let request = TradeOfferCreateRequest::counter(76561198040191316, 4127395150, None, None, String::new());
+        let value = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(value["tradeofferid_countered"], 4127395150u64);
+        assert_eq!(value["trade_offer_create_params"], "{}");
+        assert_eq!(value["partner"], 76561198040191316u64);
+
+        let tradeoffer = TradeOffer::new(
+            "https://steamcommunity.com/tradeoffer/new/?partner=79925588&token=Ob27qXzn".to_string(),
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let value = serde_json::to_value(&TradeOfferCreateRequest::new(76561198040191316, tradeoffer, None)).unwrap();
+        assert!(value.get("tradeofferid_countered").is_none());
+    }
 }
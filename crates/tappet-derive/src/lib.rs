@@ -86,6 +86,7 @@ pub fn derive_parameters(input: TokenStream) -> TokenStream {
             pub(crate) key: &'a str,
             pub(crate) request: reqwest::Request,
             pub(crate) client: &'a reqwest::Client,
+            pub(crate) executor: Option<&'a dyn crate::RequestExecutor>,
             pub(crate) parameters: #struct_parameters_name,
         }
 
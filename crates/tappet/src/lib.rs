@@ -146,7 +146,11 @@ pub mod blocking {
 mod async_client {
     use proxied::{Proxy, ProxifyClient};
     
+    use std::fmt::Debug;
+    use std::sync::Arc;
+
     use async_trait::async_trait;
+    use futures::future::BoxFuture;
     use serde::de::DeserializeOwned;
 
     use crate::Result;
@@ -164,11 +168,19 @@ mod async_client {
         async fn execute_with_response(self) -> Result<T>;
     }
 
+    /// Sends the requests of a [`SteamAPI`] instead of its own client, such as one that rotates proxies.
+    pub trait RequestExecutor: Debug + Send + Sync {
+        /// Sends `request` and returns its response.
+        fn execute(&self, request: reqwest::Request) -> BoxFuture<'_, Result<reqwest::Response>>;
+    }
+
     #[derive(Debug)]
     pub struct SteamAPI {
         pub(crate) client: reqwest::Client,
         /// Mandatory for some operations
         pub(crate) key: String,
+        /// Sends the requests instead of `client`, if set.
+        pub(crate) executor: Option<Arc<dyn RequestExecutor>>,
     }
 
     impl SteamAPI {
@@ -177,6 +189,16 @@ mod async_client {
             Self {
                 client: Default::default(),
                 key: api_key.to_string(),
+                executor: None,
+            }
+        }
+
+        /// Creates a new SteamAPI Client with an API Key, whose requests are sent by `executor`.
+        pub fn with_executor<T: ToString>(api_key: T, executor: Arc<dyn RequestExecutor>) -> SteamAPI {
+            Self {
+                client: Default::default(),
+                key: api_key.to_string(),
+                executor: Some(executor),
             }
         }
 
@@ -187,6 +209,7 @@ mod async_client {
                     .build()
                     .expect("Failed to build reqwest client with proxy"),
                 key: api_key.to_string(),
+                executor: None,
             }
         }
 
@@ -203,6 +226,20 @@ mod async_client {
         }
     }
 
+    /// Sends `request` through `executor` if there is one, or through `client` otherwise.
+    ///
+    /// Errors of `client` don't carry the URL, as its query has the API Key.
+    pub(crate) async fn send(
+        client: &reqwest::Client,
+        executor: Option<&dyn RequestExecutor>,
+        request: reqwest::Request,
+    ) -> Result<reqwest::Response> {
+        match executor {
+            Some(executor) => executor.execute(request).await,
+            None => client.execute(request).await.map_err(|e| e.without_url().into()),
+        }
+    }
+
     new_type!(GetQueryBuilder);
     new_type!(PostQueryBuilder);
 
@@ -56,6 +56,8 @@ macro_rules! from {
 
                 Self {
                     client: &api.client,
+                    #[cfg(feature = "async")]
+                    executor: api.executor.as_deref(),
                     key: &*api.key,
                     request,
                 }
@@ -77,6 +79,7 @@ macro_rules! new_type {
         pub struct $f<'a> {
             pub(crate) request: reqwest::Request,
             pub(crate) client: &'a reqwest::Client,
+            pub(crate) executor: Option<&'a dyn crate::RequestExecutor>,
             pub(crate) key: &'a str,
         }
     };
@@ -107,19 +110,15 @@ macro_rules! impl_executor {
         #[cfg(feature = "async")]
         impl<'a> ExecutorResponse<$ret> for $base<'a> {
             async fn execute_with_response(self) -> crate::Result<$ret> {
-                use futures::future::TryFutureExt;
-
                 let query: String = self.recover_params();
                 let api_key_parameter = format!("key={}", self.key);
                 let mut req = self.request;
                 let url = req.url_mut();
                 url.set_query(Some(&(api_key_parameter + "&" + &query)));
 
-                self.client
-                    .execute(req)
-                    .and_then(|res| res.json::<$ret>())
-                    .await
-                    .map_err(|e| e.into())
+                let response = crate::async_client::send(self.client, self.executor, req).await?;
+                headers_error_check(response.status(), response.headers())?;
+                response.json::<$ret>().await.map_err(|e| e.without_url().into())
             }
         }
 
@@ -154,11 +153,11 @@ macro_rules! impl_executor {
                         let url = req.url_mut();
                         url.set_query(Some(&(api_key_parameter + "&" + &query)));
 
-                        let response = self.client.execute(req).await?;
+                        let response = crate::async_client::send(self.client, self.executor, req).await?;
                         let headers = response.headers();
                         let status_code = response.status();
                         headers_error_check(status_code, headers)?;
-                        response.text().await.map_err(|e| e.into())
+                        response.text().await.map_err(|e| e.without_url().into())
                     }
 
                     &reqwest::Method::POST => {
@@ -171,11 +170,11 @@ macro_rules! impl_executor {
                         };
 
                         let new_req = self.client.post(url).form(&data).build().unwrap();
-                        let response = self.client.execute(new_req).await?;
+                        let response = crate::async_client::send(self.client, self.executor, new_req).await?;
                         let headers = response.headers();
                         let status_code = response.status();
                         headers_error_check(status_code, headers)?;
-                        response.text().await.map_err(|e| e.into())
+                        response.text().await.map_err(|e| e.without_url().into())
                     }
                     _ => unimplemented!(),
                 }
@@ -224,6 +223,8 @@ macro_rules! convert_with_endpoint {
                     request,
                     key: api.key,
                     client: api.client,
+                    #[cfg(feature = "async")]
+                    executor: api.executor,
                 }
             }
         }
@@ -238,6 +239,8 @@ macro_rules! convert_with_endpoint {
 
                 Self {
                     client: api.client,
+                    #[cfg(feature = "async")]
+                    executor: api.executor,
                     key: api.key,
                     parameters: Default::default(),
                     request,
@@ -43,7 +43,7 @@ pub enum ETradeStatus {
 }
 
 #[serde_as]
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Descriptions {
     pub appid: u32,
     #[serde_as(as = "DisplayFromStr")]
@@ -98,8 +98,10 @@ pub struct TradeOffer_Trade {
     /// State of trade offer
     #[serde(rename = "trade_offer_state")]
     pub state: ETradeOfferState,
-    items_to_give: Option<Vec<CEcon_Asset>>,
-    items_to_receive: Option<Vec<CEcon_Asset>>,
+    /// Items the account binded with the api key gives, regardless of who created the offer
+    pub items_to_give: Option<Vec<CEcon_Asset>>,
+    /// Items the account binded with the api key receives
+    pub items_to_receive: Option<Vec<CEcon_Asset>>,
     /// Indicates the account binded with the api key requested this trade
     pub is_our_offer: bool,
     pub time_created: i64,
@@ -148,6 +150,9 @@ pub struct GetTradeHistoryResponse {
 pub struct CEcon_GetTradeHistory_Response_Trade_Intermediate {
     pub more: bool,
     pub trades: Vec<TradeHistory_Trade>,
+    /// Only present if the descriptions were requested.
+    #[serde(default)]
+    pub descriptions: Vec<Descriptions>,
 }
 
 /// A trade returned by GetTradeHistory